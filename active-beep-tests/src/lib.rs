@@ -0,0 +1,17 @@
+//! Host tests for the active-beep firmware.
+//!
+//! The pattern and Morse modules only use `core`, so they are pulled in from
+//! the firmware with `#[path]` and run here with `cargo test`.
+
+// The firmware modules under test, at the paths they have in their crate
+#[cfg(test)]
+#[path = "../../active-beep/src/morse.rs"]
+mod morse;
+#[cfg(test)]
+mod morse_tests;
+#[cfg(test)]
+#[path = "../../active-beep/src/pattern.rs"]
+#[allow(dead_code)]
+mod pattern;
+#[cfg(test)]
+mod pattern_tests;
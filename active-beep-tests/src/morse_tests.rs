@@ -0,0 +1,59 @@
+//! Tests for the Morse code timings.
+
+use crate::morse::{Morse, code, unit_ms};
+use crate::pattern::Beep;
+
+/// A unit is 60 ms at 20 words per minute
+const WPM: u8 = 20;
+const UNIT: u32 = 60;
+
+/// The beeps of `text` as (on, off) in units
+fn units(text: &str) -> Vec<(u32, u32)> {
+    Morse::new(text, WPM)
+        .map(|Beep { on_ms, off_ms }| (on_ms / UNIT, off_ms / UNIT))
+        .collect()
+}
+
+#[test]
+fn unit_length() {
+    assert_eq!(unit_ms(20), UNIT);
+    assert_eq!(unit_ms(12), 100);
+    assert_eq!(unit_ms(5), 240);
+    // Zero can't be sent, it is as slow as 1 wpm
+    assert_eq!(unit_ms(0), 1200);
+}
+
+#[test]
+fn paris_is_one_word() {
+    // At 10 wpm the standard word takes a tenth of a minute
+    let total: u32 = Morse::new("PARIS", 10)
+        .map(|beep| beep.on_ms + beep.off_ms)
+        .sum();
+    assert_eq!(total, 6000);
+}
+
+#[test]
+fn dots_dashes_and_letter_gaps() {
+    assert_eq!(code('a'), Some(".-"));
+    assert_eq!(code('N'), Some("-."));
+    // A dot is 1 unit and a dash 3, 1 unit apart inside a letter, 3 between
+    // letters and 7 after the last one
+    assert_eq!(units("AN"), [(1, 1), (3, 3), (3, 1), (1, 7)]);
+}
+
+#[test]
+fn word_gaps() {
+    assert_eq!(units("E T"), [(1, 7), (3, 7)]);
+    // Any run of spaces is one word gap
+    assert_eq!(units("E   T"), units("E T"));
+    assert_eq!(units("  E T  "), units("E T"));
+}
+
+#[test]
+fn unknown_characters_are_skipped() {
+    assert_eq!(code('#'), None);
+    assert_eq!(code('é'), None);
+    assert_eq!(units("E#T"), units("ET"));
+    assert_eq!(units("E # T"), units("E T"));
+    assert_eq!(units("#~"), []);
+}
@@ -0,0 +1,141 @@
+//! Tests for the named beep patterns.
+
+use crate::pattern::{
+    ALARM, Beep, CHIRP, CONFIRM, ERROR, MIN_ALARM_PERIOD_MS, Pattern, Repeat, SOS,
+};
+
+fn beep(on_ms: u32, off_ms: u32) -> Beep {
+    Beep { on_ms, off_ms }
+}
+
+#[test]
+fn sos() {
+    assert_eq!(SOS.name(), "morse");
+    // 12 wpm, 100 ms units
+    let round = [
+        beep(100, 100),
+        beep(100, 100),
+        beep(100, 300),
+        beep(300, 100),
+        beep(300, 100),
+        beep(300, 300),
+        beep(100, 100),
+        beep(100, 100),
+        beep(100, 700),
+    ];
+    // Sent again and again, a word gap apart
+    let beeps: Vec<Beep> = SOS.timings().take(3 * round.len()).collect();
+    assert_eq!(beeps, [round, round, round].concat());
+}
+
+#[test]
+fn short_patterns_play_once() {
+    assert_eq!(CHIRP.name(), "chirp");
+    assert_eq!(CHIRP.timings().collect::<Vec<_>>(), [beep(40, 0)]);
+
+    assert_eq!(CONFIRM.name(), "confirm");
+    assert_eq!(
+        CONFIRM.timings().collect::<Vec<_>>(),
+        [beep(40, 60), beep(40, 0)]
+    );
+
+    assert_eq!(ERROR.name(), "error");
+    assert_eq!(
+        ERROR.timings().collect::<Vec<_>>(),
+        [beep(250, 120), beep(250, 0)]
+    );
+}
+
+#[test]
+fn alarm_goes_on_forever() {
+    assert_eq!(ALARM.name(), "alarm");
+    assert!(ALARM.timings().take(100).all(|b| b == beep(250, 250)));
+
+    let mostly_on = Pattern::Alarm {
+        period_ms: 1000,
+        duty: 90,
+    };
+    assert_eq!(mostly_on.timings().next(), Some(beep(900, 100)));
+    // More than 100% is on all the time
+    let always_on = Pattern::Alarm {
+        period_ms: 1000,
+        duty: 150,
+    };
+    assert_eq!(always_on.timings().next(), Some(beep(1000, 0)));
+}
+
+#[test]
+fn alarm_period_has_a_minimum() {
+    let no_period = Pattern::Alarm {
+        period_ms: 0,
+        duty: 50,
+    };
+    let half = MIN_ALARM_PERIOD_MS / 2;
+    assert!(no_period.timings().take(100).all(|b| b == beep(half, half)));
+
+    let silent = Pattern::Alarm {
+        period_ms: 0,
+        duty: 0,
+    };
+    assert_eq!(silent.timings().next(), Some(beep(0, MIN_ALARM_PERIOD_MS)));
+}
+
+#[test]
+fn repeats() {
+    let twice = Pattern::Beeps {
+        name: "twice",
+        beeps: &[Beep {
+            on_ms: 10,
+            off_ms: 20,
+        }],
+        repeat: Repeat::Times(2),
+    };
+    assert_eq!(
+        twice.timings().collect::<Vec<_>>(),
+        [beep(10, 20), beep(10, 20)]
+    );
+
+    let morse = Pattern::Morse {
+        text: "E",
+        wpm: 20,
+        repeat: Repeat::Times(3),
+    };
+    assert_eq!(morse.timings().count(), 3);
+}
+
+#[test]
+fn zero_times_plays_nothing() {
+    let never = Pattern::Beeps {
+        name: "never",
+        beeps: &[Beep {
+            on_ms: 10,
+            off_ms: 20,
+        }],
+        repeat: Repeat::Times(0),
+    };
+    assert_eq!(never.timings().next(), None);
+
+    let morse = Pattern::Morse {
+        text: "E",
+        wpm: 20,
+        repeat: Repeat::Times(0),
+    };
+    assert_eq!(morse.timings().next(), None);
+}
+
+#[test]
+fn empty_patterns_end() {
+    let silent = Pattern::Beeps {
+        name: "silent",
+        beeps: &[],
+        repeat: Repeat::Forever,
+    };
+    assert_eq!(silent.timings().next(), None);
+
+    let unsendable = Pattern::Morse {
+        text: "#",
+        wpm: 20,
+        repeat: Repeat::Forever,
+    };
+    assert_eq!(unsendable.timings().next(), None);
+}
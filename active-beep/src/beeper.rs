@@ -0,0 +1,57 @@
+//! Background task that plays beep patterns on an active buzzer.
+//!
+//! Call [`play`] from anywhere to start a pattern; it replaces whatever was
+//! beeping before. [`stop`] silences the buzzer.
+
+use embassy_futures::select::{Either, select};
+use embassy_rp::gpio::Output;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::Timer;
+
+use crate::pattern::Pattern;
+
+/// The next pattern to play, or `None` to go quiet
+static NEXT: Signal<CriticalSectionRawMutex, Option<Pattern>> = Signal::new();
+
+pub fn play(pattern: Pattern) {
+    NEXT.signal(Some(pattern));
+}
+
+pub fn stop() {
+    NEXT.signal(None);
+}
+
+async fn play_pattern(buzzer: &mut Output<'static>, pattern: Pattern) {
+    defmt::debug!("Playing {}", pattern.name());
+
+    for beep in pattern.timings() {
+        if beep.on_ms > 0 {
+            buzzer.set_high();
+            Timer::after_millis(beep.on_ms as u64).await;
+        }
+        buzzer.set_low();
+        Timer::after_millis(beep.off_ms as u64).await;
+    }
+}
+
+#[embassy_executor::task]
+pub async fn beeper_task(mut buzzer: Output<'static>) {
+    let mut next = NEXT.wait().await;
+    loop {
+        next = match next {
+            Some(pattern) => {
+                let played = select(play_pattern(&mut buzzer, pattern), NEXT.wait()).await;
+
+                // The pattern may have been cut off in the middle of a beep
+                buzzer.set_low();
+
+                match played {
+                    Either::First(()) => NEXT.wait().await,
+                    Either::Second(next) => next,
+                }
+            }
+            None => NEXT.wait().await,
+        };
+    }
+}
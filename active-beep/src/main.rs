@@ -1,6 +1,10 @@
 #![no_std]
 #![no_main]
 
+mod beeper;
+mod morse;
+mod pattern;
+
 use embassy_executor::Spawner;
 use embassy_rp as hal;
 use embassy_rp::block::ImageDef;
@@ -14,23 +18,44 @@ use defmt_rtt as _;
 //for the GPIO Output
 use embassy_rp::gpio::{Level, Output};
 
+use crate::pattern::{Pattern, Repeat};
+
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
 pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
 
 #[embassy_executor::main]
-async fn main(_spawner: Spawner) {
+async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
 
-    let mut buzzer = Output::new(p.PIN_15, Level::Low);
+    let buzzer = Output::new(p.PIN_15, Level::Low);
+    spawner.must_spawn(beeper::beeper_task(buzzer));
 
-    loop {
-        buzzer.set_high();
-        Timer::after_millis(500).await;
+    // Go through the patterns one after another
+    beeper::play(pattern::CHIRP);
+    Timer::after_secs(1).await;
+    beeper::play(pattern::CONFIRM);
+    Timer::after_secs(1).await;
+    beeper::play(pattern::ERROR);
+    Timer::after_secs(2).await;
+
+    beeper::play(Pattern::Morse {
+        text: "HELLO PICO",
+        wpm: 15,
+        repeat: Repeat::Times(2),
+    });
+    Timer::after_secs(14).await;
 
-        buzzer.set_low();
-        Timer::after_millis(500).await;
+    beeper::play(pattern::SOS);
+    Timer::after_secs(10).await;
+
+    beeper::play(pattern::ALARM);
+    Timer::after_secs(3).await;
+    beeper::stop();
+
+    loop {
+        Timer::after_millis(100).await;
     }
 }
 
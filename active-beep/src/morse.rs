@@ -0,0 +1,125 @@
+//! Turn text into Morse code beeps.
+//!
+//! Timing follows the usual rules, all counted in dot lengths ("units"):
+//! a dot is 1 unit, a dash 3, the gap inside a letter 1, between letters 3
+//! and between words 7. At `wpm` words per minute a unit is `1200 / wpm`
+//! milliseconds, based on the word "PARIS" being exactly 50 units long.
+
+use crate::pattern::Beep;
+
+/// Dots and dashes for a character, `None` for characters Morse has no code for
+pub fn code(c: char) -> Option<&'static str> {
+    let code = match c.to_ascii_uppercase() {
+        'A' => ".-",
+        'B' => "-...",
+        'C' => "-.-.",
+        'D' => "-..",
+        'E' => ".",
+        'F' => "..-.",
+        'G' => "--.",
+        'H' => "....",
+        'I' => "..",
+        'J' => ".---",
+        'K' => "-.-",
+        'L' => ".-..",
+        'M' => "--",
+        'N' => "-.",
+        'O' => "---",
+        'P' => ".--.",
+        'Q' => "--.-",
+        'R' => ".-.",
+        'S' => "...",
+        'T' => "-",
+        'U' => "..-",
+        'V' => "...-",
+        'W' => ".--",
+        'X' => "-..-",
+        'Y' => "-.--",
+        'Z' => "--..",
+        '0' => "-----",
+        '1' => ".----",
+        '2' => "..---",
+        '3' => "...--",
+        '4' => "....-",
+        '5' => ".....",
+        '6' => "-....",
+        '7' => "--...",
+        '8' => "---..",
+        '9' => "----.",
+        '.' => ".-.-.-",
+        ',' => "--..--",
+        '?' => "..--..",
+        '/' => "-..-.",
+        '=' => "-...-",
+        '-' => "-....-",
+        '@' => ".--.-.",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Length of one dot in milliseconds at `wpm` words per minute
+pub fn unit_ms(wpm: u8) -> u32 {
+    1200 / wpm.max(1) as u32
+}
+
+/// The beeps for a message, each one followed by the gap after it
+///
+/// Characters without a Morse code are skipped, and any run of spaces
+/// becomes a single word gap. The last beep is followed by a word gap, so
+/// a repeated message keeps its words apart.
+pub struct Morse<'a> {
+    chars: core::str::Chars<'a>,
+    /// Dots and dashes of the letter being sent
+    symbols: core::str::Chars<'static>,
+    unit: u32,
+}
+
+impl<'a> Morse<'a> {
+    pub fn new(text: &'a str, wpm: u8) -> Self {
+        Self {
+            chars: text.chars(),
+            symbols: "".chars(),
+            unit: unit_ms(wpm),
+        }
+    }
+
+    /// Gap after the last symbol of a letter, which depends on what comes next
+    fn gap_after_letter(&self) -> u32 {
+        let mut space = false;
+        for c in self.chars.clone() {
+            if c == ' ' {
+                space = true;
+            } else if code(c).is_some() {
+                return if space { 7 } else { 3 };
+            }
+        }
+        // End of the message
+        7
+    }
+}
+
+impl Iterator for Morse<'_> {
+    type Item = Beep;
+
+    fn next(&mut self) -> Option<Beep> {
+        let symbol = loop {
+            if let Some(symbol) = self.symbols.next() {
+                break symbol;
+            }
+            self.symbols = self.chars.by_ref().find_map(code)?.chars();
+        };
+
+        let on = if symbol == '-' { 3 } else { 1 };
+        let off = if self.symbols.as_str().is_empty() {
+            self.gap_after_letter()
+        } else {
+            1
+        };
+
+        Some(Beep {
+            on_ms: on * self.unit,
+            off_ms: off * self.unit,
+        })
+    }
+}
@@ -0,0 +1,195 @@
+//! Named beep patterns and the on/off timings they turn into.
+//!
+//! An active buzzer can only be switched on and off, so every pattern
+//! boils down to a list of [`Beep`]s: how long to sound, then how long to
+//! stay quiet before the next one.
+
+use crate::morse::Morse;
+
+/// Sound for `on_ms`, then stay silent for `off_ms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub struct Beep {
+    pub on_ms: u32,
+    pub off_ms: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub enum Repeat {
+    Once,
+    /// `Times(0)` plays nothing
+    Times(u8),
+    Forever,
+}
+
+/// Shortest alarm period, shorter ones are played at this one so an alarm
+/// never turns into beeps of no length at all
+pub const MIN_ALARM_PERIOD_MS: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// A fixed list of beeps
+    Beeps {
+        name: &'static str,
+        beeps: &'static [Beep],
+        repeat: Repeat,
+    },
+    /// Beep until stopped, on for `duty` percent of every `period_ms`, at
+    /// least `MIN_ALARM_PERIOD_MS`
+    Alarm { period_ms: u32, duty: u8 },
+    /// Send a message in Morse code at `wpm` words per minute
+    Morse {
+        text: &'static str,
+        wpm: u8,
+        repeat: Repeat,
+    },
+}
+
+/// A single short chirp, for a key press
+pub const CHIRP: Pattern = Pattern::Beeps {
+    name: "chirp",
+    beeps: &[Beep {
+        on_ms: 40,
+        off_ms: 0,
+    }],
+    repeat: Repeat::Once,
+};
+
+/// Two quick chirps, for "done" or "saved"
+pub const CONFIRM: Pattern = Pattern::Beeps {
+    name: "confirm",
+    beeps: &[
+        Beep {
+            on_ms: 40,
+            off_ms: 60,
+        },
+        Beep {
+            on_ms: 40,
+            off_ms: 0,
+        },
+    ],
+    repeat: Repeat::Once,
+};
+
+/// Two long beeps, for "that didn't work"
+pub const ERROR: Pattern = Pattern::Beeps {
+    name: "error",
+    beeps: &[
+        Beep {
+            on_ms: 250,
+            off_ms: 120,
+        },
+        Beep {
+            on_ms: 250,
+            off_ms: 0,
+        },
+    ],
+    repeat: Repeat::Once,
+};
+
+/// ... --- ... until stopped
+pub const SOS: Pattern = Pattern::Morse {
+    text: "SOS",
+    wpm: 12,
+    repeat: Repeat::Forever,
+};
+
+/// A steady on/off alarm until stopped
+pub const ALARM: Pattern = Pattern::Alarm {
+    period_ms: 500,
+    duty: 50,
+};
+
+impl Pattern {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pattern::Beeps { name, .. } => name,
+            Pattern::Alarm { .. } => "alarm",
+            Pattern::Morse { .. } => "morse",
+        }
+    }
+
+    /// Every beep of the pattern in order, with repeats
+    pub fn timings(&self) -> Timings {
+        Timings {
+            pattern: *self,
+            played: 0,
+            source: self.source(),
+        }
+    }
+
+    /// The beeps of a single round of the pattern
+    fn source(&self) -> Source {
+        match *self {
+            Pattern::Beeps { beeps, .. } => Source::Beeps(beeps.iter()),
+            Pattern::Alarm { period_ms, duty } => {
+                let period_ms = period_ms.max(MIN_ALARM_PERIOD_MS);
+                let on_ms = period_ms * duty.min(100) as u32 / 100;
+                Source::Alarm(Beep {
+                    on_ms,
+                    off_ms: period_ms - on_ms,
+                })
+            }
+            Pattern::Morse { text, wpm, .. } => Source::Morse(Morse::new(text, wpm)),
+        }
+    }
+
+    fn repeat(&self) -> Repeat {
+        match *self {
+            Pattern::Beeps { repeat, .. } | Pattern::Morse { repeat, .. } => repeat,
+            Pattern::Alarm { .. } => Repeat::Forever,
+        }
+    }
+}
+
+enum Source {
+    Beeps(core::slice::Iter<'static, Beep>),
+    Alarm(Beep),
+    Morse(Morse<'static>),
+}
+
+impl Source {
+    fn next(&mut self) -> Option<Beep> {
+        match self {
+            Source::Beeps(beeps) => beeps.next().copied(),
+            Source::Alarm(beep) => Some(*beep),
+            Source::Morse(morse) => morse.next(),
+        }
+    }
+}
+
+/// The on/off timings of a pattern, see [`Pattern::timings`]
+pub struct Timings {
+    pattern: Pattern,
+    /// Rounds of the pattern finished so far
+    played: u8,
+    source: Source,
+}
+
+impl Iterator for Timings {
+    type Item = Beep;
+
+    fn next(&mut self) -> Option<Beep> {
+        if self.pattern.repeat() == Repeat::Times(0) {
+            return None;
+        }
+        if let Some(beep) = self.source.next() {
+            return Some(beep);
+        }
+
+        self.played = self.played.saturating_add(1);
+        let more = match self.pattern.repeat() {
+            Repeat::Once => false,
+            Repeat::Times(times) => self.played < times,
+            Repeat::Forever => true,
+        };
+        if !more {
+            return None;
+        }
+
+        // An empty pattern ends here instead of starting over forever
+        self.source = self.pattern.source();
+        self.source.next()
+    }
+}
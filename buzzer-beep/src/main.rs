@@ -1,9 +1,14 @@
 #![no_std]
 #![no_main]
 
+// Shared with buzzer-song, buzzer-song-tests checks it on the host
+#[path = "../../buzzer-song/src/pwm.rs"]
+mod pwm;
+
 use embassy_executor::Spawner;
 use embassy_rp as hal;
 use embassy_rp::block::ImageDef;
+use embassy_rp::clocks::clk_sys_freq;
 use embassy_rp::pwm::{Config as PwmConfig, Pwm, SetDutyCycle};
 use embassy_time::Timer;
 
@@ -17,30 +22,23 @@ use defmt_rtt as _;
 #[used]
 pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
 
-const fn get_top(freq: f64, div_int: u8) -> u16 {
-    assert!(div_int != 0, "Divider must not be 0");
-
-    let result = 150_000_000. / (freq * div_int as f64);
-
-    assert!(result >= 1.0, "Frequency too high");
-    assert!(
-        result <= 65535.0,
-        "Frequency too low: TOP exceeds 65534 max"
-    );
-
-    result as u16 - 1
-}
-
-const PWM_DIV_INT: u8 = 64;
-const PWM_TOP: u16 = get_top(440., PWM_DIV_INT);
+const BEEP_FREQUENCY: f64 = 440.0;
 
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
 
+    let settings = pwm::solve(clk_sys_freq(), BEEP_FREQUENCY).expect("440 Hz is in PWM range");
+    defmt::info!(
+        "{} Hz -> {} Hz ({} cents)",
+        BEEP_FREQUENCY,
+        settings.frequency,
+        settings.error_cents
+    );
+
     let mut pwm_config = PwmConfig::default();
-    pwm_config.top = PWM_TOP;
-    pwm_config.divider = PWM_DIV_INT.into();
+    pwm_config.top = settings.top;
+    pwm_config.divider = fixed::FixedU16::from_bits(settings.divider_bits());
 
     let mut buzzer = Pwm::new_output_b(p.PWM_SLICE7, p.PIN_15, pwm_config);
 
@@ -0,0 +1,93 @@
+//! Tests for the ADSR envelope the player steps the duty cycle along.
+
+use crate::envelope::Envelope;
+use crate::got::ENVELOPE;
+
+/// Released well after the sustain is reached
+const HOLD: u64 = 500;
+const STEP: u64 = 5;
+
+#[test]
+fn got_envelope_is_the_one_tested() {
+    assert_eq!(
+        ENVELOPE,
+        Envelope {
+            attack_ms: 15,
+            decay_ms: 80,
+            sustain: 70,
+            release_ms: 60,
+        }
+    );
+}
+
+#[test]
+fn attack() {
+    assert_eq!(ENVELOPE.level(0, HOLD), 0);
+    assert_eq!(ENVELOPE.level(7, HOLD), 46);
+    assert_eq!(ENVELOPE.level(14, HOLD), 93);
+    assert_eq!(ENVELOPE.level(15, HOLD), 100);
+}
+
+#[test]
+fn decay() {
+    assert_eq!(ENVELOPE.level(15, HOLD), 100);
+    assert_eq!(ENVELOPE.level(55, HOLD), 85);
+    assert_eq!(ENVELOPE.level(94, HOLD), 71);
+    assert_eq!(ENVELOPE.level(95, HOLD), 70);
+}
+
+#[test]
+fn sustain() {
+    assert_eq!(ENVELOPE.level(95, HOLD), 70);
+    assert_eq!(ENVELOPE.level(HOLD - 1, HOLD), 70);
+    // Nothing moves until the release
+    assert_eq!(ENVELOPE.next_change(95, HOLD, STEP), Some(HOLD));
+}
+
+#[test]
+fn release() {
+    assert_eq!(ENVELOPE.level(HOLD, HOLD), 70);
+    assert_eq!(ENVELOPE.level(HOLD + 30, HOLD), 35);
+    assert_eq!(ENVELOPE.level(HOLD + 59, HOLD), 1);
+    assert_eq!(ENVELOPE.level(HOLD + 60, HOLD), 0);
+    assert_eq!(ENVELOPE.level(HOLD + 1000, HOLD), 0);
+
+    assert_eq!(ENVELOPE.next_change(HOLD, HOLD, STEP), Some(HOLD + 5));
+    assert_eq!(ENVELOPE.next_change(HOLD + 58, HOLD, STEP), Some(HOLD + 60));
+    assert_eq!(ENVELOPE.next_change(HOLD + 60, HOLD, STEP), None);
+}
+
+#[test]
+fn steps_stop_at_every_phase_boundary() {
+    assert_eq!(ENVELOPE.next_change(0, HOLD, STEP), Some(5));
+    assert_eq!(ENVELOPE.next_change(13, HOLD, STEP), Some(18));
+    assert_eq!(ENVELOPE.next_change(93, HOLD, STEP), Some(95));
+}
+
+#[test]
+fn note_shorter_than_attack_and_decay() {
+    // Released during the attack, the fade starts from where it got to
+    let hold = 10;
+    assert_eq!(ENVELOPE.level(9, hold), 60);
+    assert_eq!(ENVELOPE.level(hold, hold), 66);
+    assert_eq!(ENVELOPE.level(hold + 30, hold), 33);
+    assert_eq!(ENVELOPE.level(hold + 60, hold), 0);
+    assert_eq!(ENVELOPE.next_change(8, hold, STEP), Some(hold));
+    assert_eq!(ENVELOPE.next_change(hold, hold, STEP), Some(hold + 5));
+
+    // Released during the decay
+    let hold = 55;
+    assert_eq!(ENVELOPE.level(hold, hold), 85);
+    assert_eq!(ENVELOPE.level(hold + 30, hold), 42);
+    assert_eq!(ENVELOPE.next_change(53, hold, STEP), Some(hold));
+}
+
+#[test]
+fn no_envelope() {
+    let none = Envelope::NONE;
+    assert_eq!(none.level(0, HOLD), 100);
+    assert_eq!(none.level(HOLD - 1, HOLD), 100);
+    assert_eq!(none.level(HOLD, HOLD), 0);
+    assert_eq!(none.next_change(0, HOLD, STEP), Some(HOLD));
+    assert_eq!(none.next_change(HOLD, HOLD, STEP), None);
+}
@@ -0,0 +1,38 @@
+//! Host tests for the buzzer-song firmware.
+//!
+//! The modules under test only use `core` and `libm`, so they are pulled in
+//! from the firmware with `#[path]` and run here with `cargo test`.
+
+// The firmware modules under test, at the paths they have in their crate
+#[cfg(test)]
+#[path = "../../buzzer-song/src/envelope.rs"]
+#[allow(dead_code)]
+mod envelope;
+#[cfg(test)]
+mod envelope_tests;
+#[cfg(test)]
+#[path = "../../buzzer-song/src/got.rs"]
+#[allow(dead_code)]
+mod got;
+#[cfg(test)]
+#[path = "../../buzzer-song/src/music.rs"]
+#[allow(dead_code)]
+mod music;
+#[cfg(test)]
+mod music_tests;
+#[cfg(test)]
+#[path = "../../buzzer-song/src/pwm.rs"]
+mod pwm;
+#[cfg(test)]
+mod pwm_tests;
+#[cfg(test)]
+#[path = "../../buzzer-song/src/rtttl.rs"]
+mod rtttl;
+#[cfg(test)]
+mod rtttl_tests;
+#[cfg(test)]
+#[path = "../../buzzer-song/src/scheduler.rs"]
+#[allow(dead_code)]
+mod scheduler;
+#[cfg(test)]
+mod scheduler_tests;
@@ -0,0 +1,74 @@
+//! Tests for the song settings and note lengths.
+
+use crate::music::{NOTE_A4, REST, Song, TICKS_PER_WHOLE, triplet};
+
+/// A whole note takes two seconds
+const TEMPO: u16 = 120;
+
+fn quarter_ms(song: Song) -> u64 {
+    song.ticks_to_millis(Song::note_ticks(4))
+}
+
+#[test]
+fn note_lengths() {
+    let song = Song::new(TEMPO);
+    assert_eq!(song.ticks_to_millis(TICKS_PER_WHOLE), 2000);
+    assert_eq!(quarter_ms(song), 500);
+    assert_eq!(song.ticks_to_millis(Song::note_ticks(-4)), 750);
+    assert_eq!(song.ticks_to_millis(Song::note_ticks(16)), 125);
+    assert_eq!(song.millis_to_ticks(500), Song::note_ticks(4));
+}
+
+#[test]
+fn speed() {
+    let song = Song::new(TEMPO);
+    assert_eq!(quarter_ms(song.with_speed(200)), 250);
+    assert_eq!(quarter_ms(song.with_speed(50)), 1000);
+    assert_eq!(quarter_ms(song.with_speed(125)), 400);
+    // The speed scales whatever the tempo is, in either order
+    assert_eq!(quarter_ms(song.with_speed(200).with_tempo(60)), 500);
+    assert_eq!(quarter_ms(song.with_tempo(60).with_speed(200)), 500);
+    // Zero would never get anywhere, it plays as slow as it can instead
+    assert_eq!(quarter_ms(song.with_speed(0)), 50_000);
+}
+
+#[test]
+fn transpose() {
+    let song = Song::new(TEMPO);
+    assert_eq!(song.pitch(NOTE_A4), NOTE_A4);
+    assert_eq!(song.with_transpose(12).pitch(NOTE_A4), 880.0);
+    assert_eq!(song.with_transpose(-12).pitch(NOTE_A4), 220.0);
+    assert!((song.with_transpose(3).pitch(NOTE_A4) - 523.251).abs() < 0.001);
+    assert_eq!(song.with_transpose(7).pitch(REST), REST);
+}
+
+#[test]
+fn articulation() {
+    let song = Song::new(TEMPO);
+    assert_eq!(song.articulation(), 90);
+    assert_eq!(song.with_articulation(50).articulation(), 50);
+    assert_eq!(song.with_articulation(150).articulation(), 100);
+    assert_eq!(song.with_articulation(-5).articulation(), 0);
+}
+
+#[test]
+fn triplets() {
+    assert_eq!(triplet(8), 12);
+    assert_eq!(triplet(2), 3);
+    assert_eq!(triplet(-8), -12);
+
+    // Three triplet eighths take a quarter
+    let song = Song::new(TEMPO);
+    assert_eq!(3 * Song::note_ticks(triplet(8)), Song::note_ticks(4));
+    assert_eq!(song.ticks_to_millis(Song::note_ticks(triplet(8))), 166);
+    assert_eq!(song.ticks_to_millis(2 * Song::note_ticks(triplet(8))), 333);
+    assert_eq!(song.ticks_to_millis(3 * Song::note_ticks(triplet(8))), 500);
+    // A dotted triplet eighth is half as long again
+    assert_eq!(song.ticks_to_millis(Song::note_ticks(triplet(-8))), 250);
+}
+
+#[test]
+#[should_panic(expected = "only even dividers have a triplet")]
+fn whole_note_triplet() {
+    triplet(1);
+}
@@ -0,0 +1,63 @@
+//! The PWM divider solver shared by buzzer-song, buzzer-beep and cdc-player.
+
+use crate::music::{NOTE_B0, NOTE_DS8, NOTES};
+use crate::pwm::{PwmError, solve};
+
+/// System clock of the RP2350 after `embassy_rp::init`
+const SYS_CLK: u32 = 150_000_000;
+/// Farthest any note of the table may land from its frequency. The worst are
+/// a few hundredths of a cent off, far below what anyone can hear.
+const MAX_ERROR_CENTS: f64 = 0.05;
+
+#[test]
+fn every_note_is_in_tune() {
+    assert_eq!(NOTES[0], NOTE_B0);
+    assert_eq!(NOTES[NOTES.len() - 1], NOTE_DS8);
+
+    for note in NOTES {
+        let settings = solve(SYS_CLK, note).unwrap();
+        assert!(
+            settings.error_cents.abs() <= MAX_ERROR_CENTS,
+            "{} Hz is {} cents off",
+            note,
+            settings.error_cents
+        );
+
+        // The settings produce the frequency they claim
+        let divider = settings.div_int as f64 + settings.div_frac as f64 / 16.0;
+        let frequency = SYS_CLK as f64 / (divider * (settings.top as f64 + 1.0));
+        assert!((frequency - settings.frequency).abs() < 1e-9);
+        assert!(settings.div_int >= 1);
+        assert_eq!(
+            settings.divider_bits(),
+            (settings.div_int as u16) << 4 | settings.div_frac as u16
+        );
+    }
+}
+
+#[test]
+fn out_of_range() {
+    // Slower than the largest divider with the largest TOP
+    assert_eq!(solve(SYS_CLK, 5.0), Err(PwmError::FrequencyTooLow));
+    assert_eq!(solve(SYS_CLK, 0.0), Err(PwmError::FrequencyTooLow));
+    assert_eq!(solve(SYS_CLK, -440.0), Err(PwmError::FrequencyTooLow));
+    // Shorter than two counts of the system clock
+    assert_eq!(
+        solve(SYS_CLK, SYS_CLK as f64),
+        Err(PwmError::FrequencyTooHigh)
+    );
+    assert!(solve(SYS_CLK, SYS_CLK as f64 / 2.0).is_ok());
+}
+
+#[test]
+fn not_a_number() {
+    assert_eq!(solve(SYS_CLK, f64::NAN), Err(PwmError::FrequencyTooLow));
+    assert_eq!(
+        solve(SYS_CLK, f64::NEG_INFINITY),
+        Err(PwmError::FrequencyTooLow)
+    );
+    assert_eq!(
+        solve(SYS_CLK, f64::INFINITY),
+        Err(PwmError::FrequencyTooHigh)
+    );
+}
@@ -0,0 +1,102 @@
+//! The RTTTL parser of buzzer-song.
+
+use crate::got;
+use crate::music::{NOTE_A4, NOTE_AS3, NOTE_C4, NOTE_CS7, NOTE_E5, REST};
+use crate::rtttl::{Rtttl, RtttlError};
+
+fn notes(text: &str) -> Vec<(f64, i16)> {
+    Rtttl::parse(text)
+        .unwrap()
+        .notes()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn got_ringtone_is_the_opening_of_the_melody() {
+    let ringtone = Rtttl::parse(got::RTTTL).unwrap();
+    assert_eq!(ringtone.name(), "got");
+    assert_eq!(ringtone.tempo(), got::TEMPO);
+    assert_eq!(notes(got::RTTTL), got::MELODY[..50]);
+}
+
+#[test]
+fn notes_use_the_defaults_unless_given() {
+    assert_eq!(
+        notes("test:d=4,o=5,b=120:a,8c4,2p,e.,a#3.,16c#7"),
+        [
+            (880.0, 4),
+            (NOTE_C4, 8),
+            (REST, 2),
+            (NOTE_E5, -4),
+            (NOTE_AS3, -4),
+            (NOTE_CS7, 16),
+        ]
+    );
+}
+
+#[test]
+fn missing_defaults_fall_back_to_the_standard_ones() {
+    // Duration 4, octave 6 and 63 bpm
+    let ringtone = Rtttl::parse("test::a").unwrap();
+    assert_eq!(ringtone.tempo(), 63);
+    assert_eq!(notes("test::a"), [(1760.0, 4)]);
+    assert_eq!(notes("test:o=4:a"), [(NOTE_A4, 4)]);
+}
+
+#[test]
+fn bad_durations() {
+    assert_eq!(
+        Rtttl::parse("test:d=3:a").err(),
+        Some(RtttlError::InvalidDuration)
+    );
+    assert_eq!(
+        Rtttl::parse("test:d=4:64a").err(),
+        Some(RtttlError::InvalidDuration)
+    );
+}
+
+#[test]
+fn bad_octaves() {
+    assert_eq!(
+        Rtttl::parse("test:o=9:a").err(),
+        Some(RtttlError::InvalidOctave)
+    );
+    assert_eq!(
+        Rtttl::parse("test:o=5:a0").err(),
+        Some(RtttlError::InvalidOctave)
+    );
+    // Octave 8 only goes up to D#8 in the note table
+    assert_eq!(
+        Rtttl::parse("test:o=5:e8").err(),
+        Some(RtttlError::InvalidOctave)
+    );
+}
+
+#[test]
+fn bad_tempos() {
+    for tempo in ["24", "901", "fast"] {
+        let text = format!("test:b={}:a", tempo);
+        assert_eq!(Rtttl::parse(&text).err(), Some(RtttlError::InvalidTempo));
+    }
+}
+
+#[test]
+fn bad_sections_and_notes() {
+    assert_eq!(
+        Rtttl::parse("test:d=4").err(),
+        Some(RtttlError::MissingSection)
+    );
+    assert_eq!(
+        Rtttl::parse("test:x=4:a").err(),
+        Some(RtttlError::InvalidDefault)
+    );
+    assert_eq!(
+        Rtttl::parse("test::a,x").err(),
+        Some(RtttlError::InvalidNote)
+    );
+    assert_eq!(
+        Rtttl::parse("test::a#5#").err(),
+        Some(RtttlError::InvalidNote)
+    );
+}
@@ -0,0 +1,172 @@
+//! Tests for the shared timeline of the voices of a score.
+
+use std::collections::BTreeMap;
+
+use crate::got;
+use crate::music::{
+    ARTICULATION, LEGATO, NOTE_A4, NOTE_C5, REST, STACCATO, Song, TIE, VOLUME, is_marker, triplet,
+};
+use crate::scheduler::{NoteEvent, Timeline};
+
+/// `got` is in 3/4, a bar is a dotted half note
+const BAR: u32 = 384 * 3 / 4;
+
+/// Start of the note beginning each bar of `voice`, by bar number
+///
+/// `got` has no ties, so every note of the voice is one event.
+fn bar_starts(voice: &[(f64, i16)]) -> BTreeMap<u32, u64> {
+    let notes = voice
+        .iter()
+        .filter(|&&(frequency, _)| !is_marker(frequency));
+    let mut starts = BTreeMap::new();
+    let mut tick = 0;
+    for (event, &(_, divider)) in Timeline::new(got::SCORE.song, voice).zip(notes) {
+        if tick % BAR == 0 {
+            starts.insert(tick / BAR, event.start_ms);
+        }
+        tick += Song::note_ticks(divider);
+    }
+    starts
+}
+
+#[test]
+fn voices_meet_at_every_bar() {
+    let [melody, bass] = got::SCORE.voices else {
+        panic!("got is written for two voices");
+    };
+    let melody = bar_starts(melody);
+    let bass = bar_starts(bass);
+
+    let mut shared = 0;
+    for (bar, start_ms) in &bass {
+        if let Some(melody_ms) = melody.get(bar) {
+            assert_eq!(*melody_ms, *start_ms, "bar {bar}");
+            shared += 1;
+        }
+    }
+    // The bass starts a note on most bars, and the melody with it
+    assert!(shared >= bass.len() / 2, "only {shared} shared bars");
+}
+
+#[test]
+fn voices_end_together() {
+    let song = got::SCORE.song;
+    let ends: Vec<u64> = got::SCORE
+        .voices
+        .iter()
+        .map(|voice| Timeline::new(song, voice).last().unwrap().end_ms)
+        .collect();
+    assert!(ends.iter().all(|&end| end == ends[0]), "{ends:?}");
+    assert_eq!(ends[0], song.ticks_to_millis(got::SCORE.length()));
+}
+
+fn timeline(song: Song, notes: &[(f64, i16)]) -> Vec<NoteEvent> {
+    Timeline::new(song, notes).collect()
+}
+
+#[test]
+fn articulation_leaves_a_gap() {
+    let song = Song::new(120);
+    let notes = [
+        (NOTE_A4, 4),
+        (ARTICULATION, LEGATO),
+        (NOTE_C5, 4),
+        (ARTICULATION, STACCATO),
+        (NOTE_A4, 4),
+    ];
+    let releases: Vec<u64> = timeline(song, &notes)
+        .iter()
+        .map(NoteEvent::release_ms)
+        .collect();
+    assert_eq!(releases, [450, 1000, 1250]);
+
+    let staccato = song.with_articulation(STACCATO);
+    assert_eq!(timeline(staccato, &notes[..1])[0].release_ms(), 250);
+}
+
+#[test]
+fn tied_notes_sound_as_one() {
+    let song = Song::new(120);
+    let notes = [(NOTE_A4, 4), (TIE, 0), (NOTE_A4, 8), (NOTE_C5, 4)];
+    let events = timeline(song, &notes);
+    assert_eq!(events.len(), 2);
+    assert_eq!((events[0].start_ms, events[0].end_ms), (0, 750));
+    assert_eq!((events[1].start_ms, events[1].end_ms), (750, 1250));
+}
+
+#[test]
+fn markers_inside_a_tie_take_no_time() {
+    let song = Song::new(120);
+    let notes = [
+        (NOTE_A4, 4),
+        (TIE, 0),
+        (VOLUME, 50),
+        (ARTICULATION, STACCATO),
+        (NOTE_A4, 4),
+        (NOTE_C5, 4),
+    ];
+    let events = timeline(song, &notes);
+    assert_eq!(events.len(), 2);
+    assert_eq!((events[0].start_ms, events[0].end_ms), (0, 1000));
+    assert_eq!((events[0].volume, events[0].articulation), (100, 90));
+    // The markers apply from the note after the tie
+    assert_eq!((events[1].start_ms, events[1].end_ms), (1000, 1500));
+    assert_eq!((events[1].volume, events[1].articulation), (50, 50));
+}
+
+#[test]
+fn markers_before_a_tie_take_no_time() {
+    let song = Song::new(120);
+    let notes = [
+        (NOTE_A4, 4),
+        (VOLUME, 50),
+        (ARTICULATION, LEGATO),
+        (TIE, 0),
+        (NOTE_A4, 4),
+        (NOTE_C5, 4),
+    ];
+    let events = timeline(song, &notes);
+    assert_eq!(events.len(), 2);
+    assert_eq!((events[0].start_ms, events[0].end_ms), (0, 1000));
+    assert_eq!((events[0].volume, events[0].articulation), (100, 90));
+    // The markers still change the notes after the tie
+    assert_eq!((events[1].start_ms, events[1].end_ms), (1000, 1500));
+    assert_eq!((events[1].volume, events[1].articulation), (50, 100));
+}
+
+#[test]
+fn markers_without_a_tie_only_change_the_next_note() {
+    let song = Song::new(120);
+    let notes = [(NOTE_A4, 4), (VOLUME, 50), (NOTE_C5, 4)];
+    let events = timeline(song, &notes);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].end_ms, 500);
+    assert_eq!((events[0].volume, events[1].volume), (100, 50));
+}
+
+#[test]
+fn triplets_land_on_the_beat() {
+    let song = Song::new(120);
+    let notes = [
+        (NOTE_A4, triplet(8)),
+        (NOTE_C5, triplet(8)),
+        (NOTE_A4, triplet(8)),
+        (NOTE_C5, 4),
+    ];
+    let starts: Vec<u64> = timeline(song, &notes).iter().map(|e| e.start_ms).collect();
+    assert_eq!(starts, [0, 166, 333, 500]);
+}
+
+#[test]
+fn transpose_and_speed_apply_to_the_timeline() {
+    let song = Song::new(120).with_transpose(12).with_speed(200);
+    let events = timeline(song, &[(NOTE_A4, 4), (REST, 4), (NOTE_A4, 2)]);
+    let played: Vec<(f64, u64, u64)> = events
+        .iter()
+        .map(|e| (e.frequency, e.start_ms, e.end_ms))
+        .collect();
+    assert_eq!(
+        played,
+        [(880.0, 0, 250), (REST, 250, 500), (880.0, 500, 1000)]
+    );
+}
@@ -0,0 +1,93 @@
+//! Attack, decay, sustain and release (ADSR) envelope for buzzer notes.
+//!
+//! A buzzer has no real volume control, but a lower PWM duty cycle makes it
+//! quieter. The player steps the duty cycle along this envelope while a note
+//! plays, so notes fade in and out instead of clicking on and off.
+//!
+//! ```text
+//! level
+//! 100 |   /\
+//!     |  /  \______________
+//! sus | /                  \
+//!   0 |/                    \____
+//!     +--------------------------> time
+//!      attack decay  sustain  release
+//!                           ^ note released
+//! ```
+
+/// Shape of the loudness of every note, levels are in percent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope {
+    /// Milliseconds to rise from silence to full level
+    pub attack_ms: u16,
+    /// Milliseconds to fall from full level to the sustain level
+    pub decay_ms: u16,
+    /// Level held from the end of the decay until the note is released
+    pub sustain: u8,
+    /// Milliseconds to fade out to silence after the note is released
+    pub release_ms: u16,
+}
+
+impl Envelope {
+    /// Full level as soon as the note starts and silence as soon as it is released
+    pub const NONE: Envelope = Envelope {
+        attack_ms: 0,
+        decay_ms: 0,
+        sustain: 100,
+        release_ms: 0,
+    };
+
+    /// Level in percent at `elapsed` ms into a note that is released at `hold` ms
+    pub fn level(&self, elapsed: u64, hold: u64) -> u8 {
+        if elapsed < hold {
+            return self.held_level(elapsed);
+        }
+
+        // Fade out from wherever the note was when it got released, which may
+        // still be in the attack or decay for short notes
+        let released_at = self.held_level(hold) as u64;
+        let release = self.release_ms as u64;
+        let since_release = elapsed - hold;
+        if since_release >= release {
+            0
+        } else {
+            (released_at * (release - since_release) / release) as u8
+        }
+    }
+
+    /// The next time after `elapsed` at which the level should be updated
+    ///
+    /// While the level is moving this is `step` ms later, during the sustain
+    /// it jumps straight to the release, and once the release has finished
+    /// it returns `None`.
+    pub fn next_change(&self, elapsed: u64, hold: u64, step: u64) -> Option<u64> {
+        let attack_decay = self.attack_ms as u64 + self.decay_ms as u64;
+        let silent_at = hold + self.release_ms as u64;
+
+        if elapsed < hold.min(attack_decay) {
+            Some((elapsed + step).min(attack_decay).min(hold))
+        } else if elapsed < hold {
+            Some(hold)
+        } else if elapsed < silent_at {
+            Some((elapsed + step).min(silent_at))
+        } else {
+            None
+        }
+    }
+
+    /// Level before the note is released
+    fn held_level(&self, elapsed: u64) -> u8 {
+        let attack = self.attack_ms as u64;
+        let decay = self.decay_ms as u64;
+        let sustain = self.sustain.min(100) as u64;
+
+        if elapsed < attack {
+            (100 * elapsed / attack) as u8
+        } else if elapsed < attack + decay {
+            let into_decay = elapsed - attack;
+            (100 - (100 - sustain) * into_decay / decay) as u8
+        } else {
+            sustain as u8
+        }
+    }
+}
@@ -1,7 +1,29 @@
+use crate::envelope::Envelope;
 use crate::music::*;
 
 pub const TEMPO: u16 = 85;
 
+/// Melody and bass line, played together on two buzzers
+pub const SCORE: Score<'static> = Score {
+    song: Song::new(TEMPO).with_envelope(ENVELOPE),
+    voices: &[&MELODY, &BASS],
+};
+
+/// Soft start and a short fade, so repeated notes don't run into each other
+pub const ENVELOPE: Envelope = Envelope {
+    attack_ms: 15,
+    decay_ms: 80,
+    sustain: 70,
+    release_ms: 60,
+};
+
+/// Opening of the theme as an RTTTL ringtone
+pub const RTTTL: &str = "got:d=8,o=4,b=85:\
+    g,c,16d#,16f,g,c,16d#,16f,g,c,16d#,16f,g,c,16d#,16f,\
+    g,c,16e,16f,g,c,16e,16f,g,c,16e,16f,g,c,16e,16f,\
+    4g.,4c.,16d#,16f,4g,4c,16d#,16f,1d.,\
+    4f.,4a#3.,16d#,16d,4f,4a#3.,16d#,16d,1c.";
+
 pub const MELODY: [(f64, i16); 92] = [
     // Game of Thrones Theme
     (NOTE_G4, 8),
@@ -98,3 +120,40 @@ pub const MELODY: [(f64, i16); 92] = [
     (NOTE_F4, -4),
     (NOTE_G4, -1),
 ];
+
+/// Bass line under `MELODY`, one root note per bar or phrase
+pub const BASS: [(f64, i16); 30] = [
+    // Keep the bass behind the melody, each note running into the next
+    (VOLUME, 60),
+    (ARTICULATION, LEGATO),
+    // Intro
+    (NOTE_C3, -2),
+    (NOTE_C3, -2),
+    (NOTE_C3, -2),
+    (NOTE_C3, -2),
+    (NOTE_C3, -2),
+    (NOTE_C3, -2),
+    (NOTE_G2, -1),
+    (NOTE_AS2, -2),
+    (NOTE_AS2, -2),
+    (NOTE_G2, 8),
+    (NOTE_C3, -1),
+    // Repeat
+    (NOTE_C3, -2),
+    (NOTE_C3, -2),
+    (NOTE_G2, -1),
+    (NOTE_AS2, -2),
+    (NOTE_AS2, -2),
+    (NOTE_G2, 8),
+    (NOTE_C3, -1),
+    (NOTE_C3, -2),
+    (NOTE_C3, -2),
+    (NOTE_G2, -2),
+    (NOTE_AS2, -2),
+    (NOTE_AS2, -2),
+    (NOTE_C3, -1),
+    (NOTE_GS2, -1),
+    (NOTE_C3, -1),
+    (NOTE_GS2, -1),
+    (NOTE_C3, -1),
+];
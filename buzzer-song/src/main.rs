@@ -1,8 +1,13 @@
 #![no_std]
 #![no_main]
 
+mod envelope;
 mod got;
 mod music;
+mod player;
+mod pwm;
+mod rtttl;
+mod scheduler;
 
 use embassy_executor::Spawner;
 use embassy_rp as hal;
@@ -15,64 +20,129 @@ use panic_probe as _;
 use defmt_rtt as _;
 
 // For PWM
-use embassy_rp::pwm::{Config as PwmConfig, Pwm, SetDutyCycle};
+use embassy_rp::pwm::{Config as PwmConfig, Pwm};
 
-use crate::music::Song;
+use heapless::Vec;
+use static_cell::StaticCell;
+
+use crate::music::{NOTE_C5, NOTE_C6, NOTE_E5, NOTE_G5, STACCATO, Score, Song, TIE, triplet};
+use crate::player::Command;
+use crate::rtttl::{Rtttl, RtttlError};
 
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
 pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
 
-const fn get_top(freq: f64, div_int: u8) -> u16 {
-    assert!(div_int != 0, "Divider must not be 0");
+/// A rising chime in triplets, the top note held across the bar with a tie
+static CHIME: Score<'static> = Score {
+    song: Song::new(100).with_envelope(got::ENVELOPE),
+    voices: &[&[
+        (NOTE_C5, triplet(8)),
+        (NOTE_E5, triplet(8)),
+        (NOTE_G5, triplet(8)),
+        (NOTE_C6, 4),
+        (TIE, 0),
+        (NOTE_C6, 2),
+    ]],
+};
+
+/// The theme again, an octave up, a quarter faster and short and detached
+static GOT_VARIATION: Score<'static> = Score {
+    song: got::SCORE
+        .song
+        .with_transpose(12)
+        .with_speed(125)
+        .with_articulation(STACCATO),
+    voices: got::SCORE.voices,
+};
+
+/// Most notes kept from an RTTTL ringtone
+const RINGTONE_NOTES: usize = 128;
+
+/// Parse an RTTTL ringtone into a single voice score the player can hold on to
+fn ringtone_score(text: &str) -> Result<&'static Score<'static>, RtttlError> {
+    static NOTES: StaticCell<Vec<(f64, i16), RINGTONE_NOTES>> = StaticCell::new();
+    static VOICES: StaticCell<[&[(f64, i16)]; 1]> = StaticCell::new();
+    static SCORE: StaticCell<Score<'static>> = StaticCell::new();
+
+    let ringtone = Rtttl::parse(text)?;
+    defmt::info!("Loaded ringtone {}", ringtone.name());
+
+    let notes = NOTES.init(Vec::new());
+    for note in ringtone.notes() {
+        if notes.push(note?).is_err() {
+            defmt::warn!("Ringtone cut short after {} notes", RINGTONE_NOTES);
+            break;
+        }
+    }
 
-    let result = 150_000_000. / (freq * div_int as f64);
+    let voices = VOICES.init([notes.as_slice()]);
+    Ok(SCORE.init(Score {
+        song: Song::new(ringtone.tempo()).with_envelope(got::ENVELOPE),
+        voices,
+    }))
+}
 
-    assert!(result >= 1.0, "Frequency too high");
-    assert!(
-        result <= 65535.0,
-        "Frequency too low: TOP exceeds 65534 max"
-    );
+/// Follow the player from the outside, the way a display or USB host would
+#[embassy_executor::task]
+async fn position_task() {
+    let mut position = player::POSITION
+        .receiver()
+        .expect("a free position receiver");
 
-    result as u16 - 1
+    loop {
+        let position = position.changed().await;
+        defmt::debug!(
+            "{}: {}/{} ticks at {} bpm",
+            position.state,
+            position.tick,
+            position.length,
+            position.tempo
+        );
+    }
 }
 
-const PWM_DIV_INT: u8 = 64;
-const PWM_TOP: u16 = get_top(440., PWM_DIV_INT);
-
 #[embassy_executor::main]
-async fn main(_spawner: Spawner) {
+async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
 
-    let song = Song::new(got::TEMPO);
+    let pwm_config = PwmConfig::default();
+    let melody_buzzer = Pwm::new_output_b(p.PWM_SLICE7, p.PIN_15, pwm_config.clone());
+    let bass_buzzer = Pwm::new_output_b(p.PWM_SLICE6, p.PIN_13, pwm_config);
 
-    let mut pwm_config = PwmConfig::default();
-    pwm_config.top = PWM_TOP;
-    pwm_config.divider = PWM_DIV_INT.into();
+    spawner.must_spawn(player::voice_task(0, melody_buzzer));
+    spawner.must_spawn(player::voice_task(1, bass_buzzer));
+    spawner.must_spawn(player::player_task());
+    spawner.must_spawn(position_task());
 
-    let mut buzzer = Pwm::new_output_b(p.PWM_SLICE7, p.PIN_15, pwm_config.clone());
+    // A chime, the opening as a ringtone, then the whole score
+    player::COMMANDS.send(Command::Play(&CHIME)).await;
+    match ringtone_score(got::RTTTL) {
+        Ok(ringtone) => player::COMMANDS.send(Command::Enqueue(ringtone)).await,
+        Err(e) => defmt::error!("Invalid RTTTL: {}", e),
+    }
+    player::COMMANDS.send(Command::Enqueue(&got::SCORE)).await;
 
-    // One time play the song
-    for (note, duration_type) in got::MELODY {
-        let top = get_top(note, PWM_DIV_INT);
-        pwm_config.top = top;
-        buzzer.set_config(&pwm_config);
+    // The music plays in the background, so main is free to do other work.
+    // Here it just tries out the other commands.
+    Timer::after_secs(8).await;
+    player::COMMANDS.send(Command::Next).await;
 
-        let note_duration = song.calc_note_duration(duration_type);
-        let pause_duration = note_duration / 10; // 10% of note_duration
+    Timer::after_secs(10).await;
+    player::COMMANDS.send(Command::Pause).await;
+    Timer::after_secs(2).await;
+    player::COMMANDS.send(Command::Resume).await;
 
-        buzzer
-            .set_duty_cycle_percent(50)
-            .expect("50 is valid duty percentage"); // Set duty cycle to 50% to play the note
+    Timer::after_secs(10).await;
+    player::COMMANDS.send(Command::SetTempo(110)).await;
+    player::COMMANDS.send(Command::SetVolume(30)).await;
 
-        Timer::after_millis(note_duration - pause_duration).await; // Play 90%
+    Timer::after_secs(10).await;
+    player::COMMANDS.send(Command::Play(&GOT_VARIATION)).await;
 
-        buzzer
-            .set_duty_cycle_percent(0)
-            .expect("50 is valid duty percentage"); // Stop tone
-        Timer::after_millis(pause_duration).await; // Pause for 10%
-    }
+    Timer::after_secs(10).await;
+    player::COMMANDS.send(Command::Stop).await;
 
     loop {
         Timer::after_millis(100).await;
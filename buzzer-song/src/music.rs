@@ -1,4 +1,4 @@
-#![allow(dead_code)]
+use crate::envelope::Envelope;
 
 // Note frequencies in Hertz as f64
 pub const NOTE_B0: f64 = 31.0;
@@ -91,23 +91,189 @@ pub const NOTE_CS8: f64 = 4435.0;
 pub const NOTE_D8: f64 = 4699.0;
 pub const NOTE_DS8: f64 = 4978.0;
 pub const REST: f64 = 0.0; // No sound, for pauses
+pub const VOLUME: f64 = -1.0; // Not a note, sets the volume (0 to 100) of the notes after it
+pub const ARTICULATION: f64 = -2.0; // Not a note, sets how much of the notes after it sounds
+pub const TIE: f64 = -3.0; // Not a note, adds the length of the next note to the one before
 
+/// Entries like `VOLUME` change how the notes after them are played and take no time
+pub fn is_marker(frequency: f64) -> bool {
+    frequency < 0.0
+}
+
+/// MIDI note number of `NOTE_B0`, the first entry in [`NOTES`]
+pub const FIRST_NOTE: u8 = 23;
+
+/// Every note frequency from `NOTE_B0` to `NOTE_DS8`, one semitone apart
+pub const NOTES: [f64; 89] = [
+    NOTE_B0, NOTE_C1, NOTE_CS1, NOTE_D1, NOTE_DS1, NOTE_E1, NOTE_F1, NOTE_FS1, NOTE_G1, NOTE_GS1,
+    NOTE_A1, NOTE_AS1, NOTE_B1, NOTE_C2, NOTE_CS2, NOTE_D2, NOTE_DS2, NOTE_E2, NOTE_F2, NOTE_FS2,
+    NOTE_G2, NOTE_GS2, NOTE_A2, NOTE_AS2, NOTE_B2, NOTE_C3, NOTE_CS3, NOTE_D3, NOTE_DS3, NOTE_E3,
+    NOTE_F3, NOTE_FS3, NOTE_G3, NOTE_GS3, NOTE_A3, NOTE_AS3, NOTE_B3, NOTE_C4, NOTE_CS4, NOTE_D4,
+    NOTE_DS4, NOTE_E4, NOTE_F4, NOTE_FS4, NOTE_G4, NOTE_GS4, NOTE_A4, NOTE_AS4, NOTE_B4, NOTE_C5,
+    NOTE_CS5, NOTE_D5, NOTE_DS5, NOTE_E5, NOTE_F5, NOTE_FS5, NOTE_G5, NOTE_GS5, NOTE_A5, NOTE_AS5,
+    NOTE_B5, NOTE_C6, NOTE_CS6, NOTE_D6, NOTE_DS6, NOTE_E6, NOTE_F6, NOTE_FS6, NOTE_G6, NOTE_GS6,
+    NOTE_A6, NOTE_AS6, NOTE_B6, NOTE_C7, NOTE_CS7, NOTE_D7, NOTE_DS7, NOTE_E7, NOTE_F7, NOTE_FS7,
+    NOTE_G7, NOTE_GS7, NOTE_A7, NOTE_AS7, NOTE_B7, NOTE_C8, NOTE_CS8, NOTE_D8, NOTE_DS8,
+];
+
+/// Look up the frequency of a MIDI note number (60 is middle C, `NOTE_C4`)
+pub fn midi_note(number: u8) -> Option<f64> {
+    NOTES.get(number.checked_sub(FIRST_NOTE)? as usize).copied()
+}
+
+/// Length of a whole note in scheduler ticks
+///
+/// 384 is 128 * 3, so every dotted note down to a 1/64 and every triplet
+/// down to a 1/32 is a whole number of ticks.
+pub const TICKS_PER_WHOLE: u32 = 384;
+
+/// Share of each note that sounds before the gap to the next note, in percent
+pub const LEGATO: i16 = 100;
+pub const NORMAL: i16 = 90;
+pub const STACCATO: i16 = 50;
+
+/// Length of one note of a triplet, three of which take the time of two `divider` notes
+///
+/// Dotted dividers stay dotted, `triplet(-8)` is a dotted triplet eighth.
+/// Whole note triplets have no divider, so `divider` must be even.
+pub const fn triplet(divider: i16) -> i16 {
+    assert!(divider % 2 == 0, "only even dividers have a triplet");
+    divider * 3 / 2
+}
+
+#[derive(Clone, Copy)]
 pub struct Song {
-    whole_note: u64,
+    tempo: u16,
+    /// Tempo multiplier in percent, 200 plays twice as fast
+    speed: u16,
+    envelope: Envelope,
+    /// Semitones every note is shifted by
+    transpose: i8,
+    /// Share of each note that sounds, until an `ARTICULATION` marker changes it
+    articulation: u8,
 }
 
 impl Song {
-    pub fn new(tempo: u16) -> Self {
-        let whole_note = (60_000 * 4) / tempo as u64;
-        Self { whole_note }
+    pub const fn new(tempo: u16) -> Self {
+        Self {
+            tempo,
+            speed: 100,
+            envelope: Envelope::NONE,
+            transpose: 0,
+            articulation: NORMAL as u8,
+        }
     }
 
-    pub fn calc_note_duration(&self, divider: i16) -> u64 {
-        if divider > 0 {
-            self.whole_note / divider as u64
-        } else {
-            let duration = self.whole_note / divider.unsigned_abs() as u64;
-            (duration as f64 * 1.5) as u64
+    /// Shape every note of the song with `envelope`
+    pub const fn with_envelope(self, envelope: Envelope) -> Self {
+        Self { envelope, ..self }
+    }
+
+    /// The same song played at another tempo
+    pub const fn with_tempo(self, tempo: u16) -> Self {
+        Self { tempo, ..self }
+    }
+
+    /// Play faster or slower than the written tempo, in percent
+    pub const fn with_speed(self, speed: u16) -> Self {
+        let speed = if speed == 0 { 1 } else { speed };
+        Self { speed, ..self }
+    }
+
+    /// Shift every note up (or down, for negative values) by whole semitones
+    pub const fn with_transpose(self, semitones: i8) -> Self {
+        Self {
+            transpose: semitones,
+            ..self
         }
     }
+
+    /// Share of each note that sounds, like `LEGATO` or `STACCATO`
+    pub const fn with_articulation(self, percent: i16) -> Self {
+        Self {
+            articulation: clamp_percent(percent),
+            ..self
+        }
+    }
+
+    pub fn tempo(&self) -> u16 {
+        self.tempo
+    }
+
+    pub fn envelope(&self) -> Envelope {
+        self.envelope
+    }
+
+    pub fn articulation(&self) -> u8 {
+        self.articulation
+    }
+
+    /// The frequency to play for a written note, after transposing
+    ///
+    /// Equal temperament puts every semitone a factor of 2^(1/12) apart, so
+    /// this stays in tune for notes between the `NOTE_*` table entries too.
+    pub fn pitch(&self, frequency: f64) -> f64 {
+        if frequency == REST || self.transpose == 0 {
+            return frequency;
+        }
+        frequency * libm::exp2(self.transpose as f64 / 12.0)
+    }
+
+    /// Length of a note in ticks, a negative divider is a dotted note
+    pub fn note_ticks(divider: i16) -> u32 {
+        let ticks = TICKS_PER_WHOLE / divider.unsigned_abs().max(1) as u32;
+        if divider > 0 { ticks } else { ticks * 3 / 2 }
+    }
+
+    /// Convert a position in ticks into milliseconds since the start of the song
+    ///
+    /// Positions are converted from the start every time instead of adding up
+    /// rounded note lengths, so voices that reach the same tick always agree on
+    /// the same millisecond.
+    pub fn ticks_to_millis(&self, ticks: u32) -> u64 {
+        ticks as u64 * 60_000 * 4 * 100 / (self.scaled_tempo() * TICKS_PER_WHOLE as u64)
+    }
+
+    /// Convert milliseconds since the start of the song back into ticks
+    pub fn millis_to_ticks(&self, millis: u64) -> u32 {
+        (millis * self.scaled_tempo() * TICKS_PER_WHOLE as u64 / (60_000 * 4 * 100)) as u32
+    }
+
+    /// Tempo times speed, in 1/100 beats per minute
+    fn scaled_tempo(&self) -> u64 {
+        self.tempo as u64 * self.speed as u64
+    }
+}
+
+const fn clamp_percent(percent: i16) -> u8 {
+    if percent < 0 {
+        0
+    } else if percent > 100 {
+        100
+    } else {
+        percent as u8
+    }
+}
+
+/// A tune with one track per voice, all played with the same song settings
+pub struct Score<'a> {
+    pub song: Song,
+    pub voices: &'a [&'a [(f64, i16)]],
+}
+
+impl Score<'_> {
+    /// Length of the longest voice in ticks
+    pub fn length(&self) -> u32 {
+        self.voices
+            .iter()
+            .map(|voice| {
+                voice
+                    .iter()
+                    .filter(|&&(frequency, _)| !is_marker(frequency))
+                    .map(|&(_, divider)| Song::note_ticks(divider))
+                    .sum()
+            })
+            .max()
+            .unwrap_or(0)
+    }
 }
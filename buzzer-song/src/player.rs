@@ -0,0 +1,379 @@
+//! Background music player, controlled by sending it commands.
+//!
+//! `player_task` is the conductor: it owns the playlist, keeps track of the
+//! position in the current song and tells every `voice_task` when to start
+//! and stop. Any other part of the firmware (a button, a USB command, an
+//! RFID tag) can control the music by sending a [`Command`] to [`COMMANDS`],
+//! and can follow along by watching [`POSITION`].
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use embassy_futures::select::{Either, select};
+use embassy_rp::clocks::clk_sys_freq;
+use embassy_rp::pwm::{Config as PwmConfig, Pwm, SetDutyCycle};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Instant, Timer};
+use fixed::FixedU16;
+use heapless::Deque;
+
+use crate::music::{REST, Score, Song};
+use crate::pwm;
+use crate::scheduler::Timeline;
+
+/// Number of voices that can play at the same time, one PWM slice each
+pub const VOICES: usize = 2;
+
+/// Songs that can wait in the playlist behind the current one
+const PLAYLIST_LEN: usize = 4;
+
+/// How often the position is published while a song is playing
+const POSITION_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often the duty cycle is updated while a note fades in or out
+const ENVELOPE_STEP_MS: u64 = 5;
+
+/// Global volume times note volume times envelope level, all out of 100
+const FULL_LEVEL: u32 = 100 * 100 * 100;
+
+/// Head start given to the voices so they all begin on the same instant
+const START_DELAY: Duration = Duration::from_millis(20);
+
+#[derive(Clone, Copy)]
+pub enum Command {
+    /// Stop the current song and play this one instead
+    Play(&'static Score<'static>),
+    /// Add a song to the playlist, or play it right away if nothing is playing
+    Enqueue(&'static Score<'static>),
+    /// Skip to the next song in the playlist
+    Next,
+    Pause,
+    Resume,
+    /// Stop playing and clear the playlist
+    Stop,
+    /// Change the tempo of the current song, in beats per minute
+    SetTempo(u16),
+    /// Loudness from 0 (silent) to 100
+    SetVolume(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PlayerState {
+    Stopped,
+    Playing,
+    Paused,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Position {
+    pub state: PlayerState,
+    /// How far into the current song the player is, in ticks
+    pub tick: u32,
+    /// Length of the current song in ticks
+    pub length: u32,
+    /// Tempo the current song is played at
+    pub tempo: u16,
+}
+
+/// Send commands here to control the player
+pub static COMMANDS: Channel<CriticalSectionRawMutex, Command, 4> = Channel::new();
+
+/// The latest position of the player
+pub static POSITION: Watch<CriticalSectionRawMutex, Position, 2> = Watch::new();
+
+static VOLUME: AtomicU8 = AtomicU8::new(100);
+
+enum VoiceCommand {
+    /// Play `track` starting from `from_tick`, which falls on `start`
+    Start {
+        song: Song,
+        track: &'static [(f64, i16)],
+        from_tick: u32,
+        start: Instant,
+    },
+    Stop,
+}
+
+static VOICE_COMMANDS: [Signal<CriticalSectionRawMutex, VoiceCommand>; VOICES] =
+    [const { Signal::new() }; VOICES];
+
+/// Point the PWM slice at a new frequency, picking the divider and TOP at runtime
+fn set_frequency(buzzer: &mut Pwm<'_>, pwm_config: &mut PwmConfig, freq: f64) {
+    let settings = match pwm::solve(clk_sys_freq(), freq) {
+        Ok(settings) => settings,
+        Err(e) => {
+            defmt::error!("Can't play {} Hz: {}", freq, e);
+            return;
+        }
+    };
+    defmt::debug!(
+        "{} Hz -> {} Hz ({} cents)",
+        freq,
+        settings.frequency,
+        settings.error_cents
+    );
+
+    pwm_config.top = settings.top;
+    pwm_config.divider = FixedU16::from_bits(settings.divider_bits());
+    buzzer.set_config(pwm_config);
+}
+
+/// Duty cycle of a tone at `level` percent of the loudest
+///
+/// 50% duty is the loudest a buzzer gets, so the scale tops out there.
+fn set_level(buzzer: &mut Pwm<'_>, level: u32) {
+    let max_duty = buzzer.max_duty_cycle() as u64;
+    let duty = max_duty * level.min(FULL_LEVEL) as u64 / (2 * FULL_LEVEL as u64);
+    buzzer
+        .set_duty_cycle(duty as u16)
+        .expect("duty is at most 50%");
+}
+
+async fn play_track(
+    buzzer: &mut Pwm<'static>,
+    song: Song,
+    track: &'static [(f64, i16)],
+    from_tick: u32,
+    start: Instant,
+) {
+    let mut pwm_config = PwmConfig::default();
+    let envelope = song.envelope();
+
+    // Note times count from the start of the song, `start` is where we resume
+    let offset = song.ticks_to_millis(from_tick);
+    let at = |millis: u64| start + Duration::from_millis(millis - offset);
+
+    let mut end_ms = offset;
+    for note in Timeline::new(song, track).skip_to(from_tick) {
+        Timer::at(at(note.start_ms)).await;
+        end_ms = note.end_ms;
+        if note.frequency == REST {
+            set_level(buzzer, 0);
+            continue;
+        }
+
+        set_frequency(buzzer, &mut pwm_config, note.frequency);
+
+        // Step the duty cycle along the envelope until the next note is due,
+        // cutting the release short if it runs into it
+        let length = note.end_ms - note.start_ms;
+        let hold = note.release_ms() - note.start_ms;
+        let mut elapsed = 0;
+        loop {
+            let volume = VOLUME.load(Ordering::Relaxed) as u32 * note.volume as u32;
+            set_level(buzzer, volume * envelope.level(elapsed, hold) as u32);
+
+            match envelope.next_change(elapsed, hold, ENVELOPE_STEP_MS) {
+                Some(next) if next < length => elapsed = next,
+                _ => break,
+            }
+            Timer::at(at(note.start_ms + elapsed)).await;
+        }
+    }
+
+    // Let the last release finish before the voice goes quiet
+    Timer::at(at(end_ms)).await;
+}
+
+/// Play one voice of whatever the player is playing on its own buzzer
+#[embassy_executor::task(pool_size = VOICES)]
+pub async fn voice_task(voice: usize, mut buzzer: Pwm<'static>) {
+    let commands = &VOICE_COMMANDS[voice];
+
+    let mut command = commands.wait().await;
+    loop {
+        command = match command {
+            VoiceCommand::Start {
+                song,
+                track,
+                from_tick,
+                start,
+            } => {
+                let played = select(
+                    play_track(&mut buzzer, song, track, from_tick, start),
+                    commands.wait(),
+                )
+                .await;
+
+                // The track may have been cut off in the middle of a note
+                buzzer
+                    .set_duty_cycle_percent(0)
+                    .expect("0 is valid duty percentage");
+
+                match played {
+                    Either::First(()) => commands.wait().await,
+                    Either::Second(next) => next,
+                }
+            }
+            VoiceCommand::Stop => commands.wait().await,
+        };
+    }
+}
+
+struct Player {
+    playlist: Deque<&'static Score<'static>, PLAYLIST_LEN>,
+    score: Option<&'static Score<'static>>,
+    state: PlayerState,
+    tempo: u16,
+    /// Tick the voices were last started from, or the paused position
+    tick: u32,
+    /// When the voices were last started
+    started_at: Instant,
+}
+
+impl Player {
+    fn new() -> Self {
+        Self {
+            playlist: Deque::new(),
+            score: None,
+            state: PlayerState::Stopped,
+            tempo: 0,
+            tick: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    fn length(&self) -> u32 {
+        self.score.map_or(0, Score::length)
+    }
+
+    /// The current score's settings at the tempo chosen with `SetTempo`
+    fn song(&self) -> Option<Song> {
+        self.score.map(|score| score.song.with_tempo(self.tempo))
+    }
+
+    fn current_tick(&self) -> u32 {
+        let Some(song) = self.song().filter(|_| self.state == PlayerState::Playing) else {
+            return self.tick;
+        };
+
+        let elapsed = Instant::now()
+            .saturating_duration_since(self.started_at)
+            .as_millis();
+        song.millis_to_ticks(song.ticks_to_millis(self.tick) + elapsed)
+            .min(self.length())
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            state: self.state,
+            tick: self.current_tick(),
+            length: self.length(),
+            tempo: self.tempo,
+        }
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Play(score) => self.start(score),
+            Command::Enqueue(score) => {
+                if self.state == PlayerState::Stopped {
+                    self.start(score);
+                } else if self.playlist.push_back(score).is_err() {
+                    defmt::warn!("Playlist is full");
+                }
+            }
+            Command::Next => self.next(),
+            Command::Pause => {
+                if self.state == PlayerState::Playing {
+                    self.tick = self.current_tick();
+                    self.stop_voices();
+                    self.state = PlayerState::Paused;
+                }
+            }
+            Command::Resume => {
+                if self.state == PlayerState::Paused {
+                    self.play_from(self.tick);
+                }
+            }
+            Command::Stop => {
+                self.playlist.clear();
+                self.stop();
+            }
+            Command::SetTempo(tempo) => {
+                let tick = self.current_tick();
+                self.tempo = tempo.max(1);
+                if self.state == PlayerState::Playing {
+                    self.play_from(tick);
+                }
+            }
+            Command::SetVolume(volume) => VOLUME.store(volume.min(100), Ordering::Relaxed),
+        }
+    }
+
+    fn start(&mut self, score: &'static Score<'static>) {
+        self.score = Some(score);
+        self.tempo = score.song.tempo();
+        self.play_from(0);
+    }
+
+    fn play_from(&mut self, tick: u32) {
+        let (Some(score), Some(song)) = (self.score, self.song()) else {
+            return;
+        };
+
+        let start = Instant::now() + START_DELAY;
+        for (voice, commands) in VOICE_COMMANDS.iter().enumerate() {
+            match score.voices.get(voice) {
+                Some(track) => commands.signal(VoiceCommand::Start {
+                    song,
+                    track,
+                    from_tick: tick,
+                    start,
+                }),
+                None => commands.signal(VoiceCommand::Stop),
+            }
+        }
+
+        self.tick = tick;
+        self.started_at = start;
+        self.state = PlayerState::Playing;
+    }
+
+    fn next(&mut self) {
+        match self.playlist.pop_front() {
+            Some(score) => self.start(score),
+            None => self.stop(),
+        }
+    }
+
+    fn stop(&mut self) {
+        self.stop_voices();
+        self.score = None;
+        self.tick = 0;
+        self.state = PlayerState::Stopped;
+    }
+
+    fn stop_voices(&self) {
+        for commands in &VOICE_COMMANDS {
+            commands.signal(VoiceCommand::Stop);
+        }
+    }
+}
+
+/// Run the player, taking commands from [`COMMANDS`]
+#[embassy_executor::task]
+pub async fn player_task() {
+    let mut player = Player::new();
+    let position = POSITION.sender();
+    position.send(player.position());
+
+    loop {
+        if player.state == PlayerState::Playing {
+            match select(COMMANDS.receive(), Timer::after(POSITION_INTERVAL)).await {
+                Either::First(command) => player.handle(command),
+                Either::Second(()) => {
+                    if player.current_tick() >= player.length() {
+                        player.next();
+                    }
+                }
+            }
+        } else {
+            player.handle(COMMANDS.receive().await);
+        }
+
+        position.send(player.position());
+    }
+}
@@ -0,0 +1,94 @@
+//! Pick the PWM clock divider and TOP for a buzzer frequency at runtime.
+//!
+//! The PWM output frequency is `sys_clk / (divider * (top + 1))`, where the
+//! divider is an 8.4 fixed point number (1.0 to 255 + 15/16) and `top` is a
+//! 16 bit counter wrap value. A small divider leaves more room for `top`,
+//! which makes the rounding error of the period smaller, so the solver
+//! starts from the smallest divider that fits and checks the next few
+//! fractional steps for the closest match.
+
+/// The divider has 4 fractional bits, so it moves in steps of 1/16
+const DIV_STEPS_PER_UNIT: u32 = 16;
+/// Smallest divider (1.0) in 1/16 steps
+const MIN_DIV_STEPS: u32 = DIV_STEPS_PER_UNIT;
+/// Largest divider (255 + 15/16) in 1/16 steps
+const MAX_DIV_STEPS: u32 = 255 * DIV_STEPS_PER_UNIT + 15;
+/// The counter runs from 0 to `top`, so a period is at most 65536 counts
+const MAX_PERIOD: f64 = 65536.0;
+/// A period of two counts is the shortest that can still be half on, half off
+const MIN_PERIOD: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub enum PwmError {
+    /// Even the largest divider with the largest TOP is too fast
+    FrequencyTooLow,
+    /// The period would be shorter than two counts of the system clock
+    FrequencyTooHigh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub struct PwmSettings {
+    /// Integer part of the clock divider
+    pub div_int: u8,
+    /// Fractional part of the clock divider, in 1/16 steps
+    pub div_frac: u8,
+    /// Counter wrap value, the period is `top + 1` counts
+    pub top: u16,
+    /// Frequency the PWM slice actually produces with these settings
+    pub frequency: f64,
+    /// Distance from the requested frequency in cents (1/100 of a semitone)
+    pub error_cents: f64,
+}
+
+impl PwmSettings {
+    /// The divider as raw 8.4 fixed point bits, as stored in `pwm::Config::divider`
+    pub fn divider_bits(&self) -> u16 {
+        ((self.div_int as u16) << 4) | self.div_frac as u16
+    }
+}
+
+/// Find the divider and TOP that come closest to `freq` with the given system clock
+pub fn solve(sys_clk: u32, freq: f64) -> Result<PwmSettings, PwmError> {
+    if freq.is_nan() || freq <= 0.0 {
+        return Err(PwmError::FrequencyTooLow);
+    }
+
+    // The system clock counted in 1/16 divider steps, so that
+    // `steps * period == ticks` gives exactly the requested frequency
+    let clock = sys_clk as f64 * DIV_STEPS_PER_UNIT as f64;
+    let ticks = clock / freq;
+
+    let first_steps = libm::ceil(ticks / MAX_PERIOD).max(MIN_DIV_STEPS as f64);
+    if first_steps > MAX_DIV_STEPS as f64 {
+        return Err(PwmError::FrequencyTooLow);
+    }
+    let first_steps = first_steps as u32;
+    let last_steps = (first_steps + DIV_STEPS_PER_UNIT).min(MAX_DIV_STEPS);
+
+    let mut best: Option<(u32, f64)> = None;
+    for steps in first_steps..=last_steps {
+        let period = libm::round(ticks / steps as f64);
+        if !(MIN_PERIOD..=MAX_PERIOD).contains(&period) {
+            continue;
+        }
+
+        let achieved = clock / (steps as f64 * period);
+        let error = (achieved - freq).abs();
+        if best.is_none_or(|(_, best_error)| error < best_error) {
+            best = Some((steps, period));
+        }
+    }
+
+    let (steps, period) = best.ok_or(PwmError::FrequencyTooHigh)?;
+    let frequency = clock / (steps as f64 * period);
+
+    Ok(PwmSettings {
+        div_int: (steps / DIV_STEPS_PER_UNIT) as u8,
+        div_frac: (steps % DIV_STEPS_PER_UNIT) as u8,
+        top: (period - 1.0) as u16,
+        frequency,
+        error_cents: 1200.0 * libm::log2(frequency / freq),
+    })
+}
@@ -0,0 +1,196 @@
+//! Parser for RTTTL (Ring Tone Text Transfer Language) melodies.
+//!
+//! An RTTTL string has three sections separated by colons:
+//!
+//! ```text
+//! got:d=8,o=5,b=85:g,c,16d#,16f
+//! ```
+//!
+//! the name, the defaults (duration, octave and beats per minute) and the
+//! comma separated notes. Each note is `[duration]note[#][.][octave][.]`,
+//! where `p` is a pause.
+//!
+//! The notes are turned into the same `(frequency, divider)` pairs used by
+//! `got::MELODY`, so they can be placed on a `scheduler::Timeline`.
+
+use crate::music::{REST, midi_note};
+
+const DEFAULT_DURATION: u8 = 4;
+const DEFAULT_OCTAVE: u8 = 6;
+const DEFAULT_TEMPO: u16 = 63;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub enum RtttlError {
+    /// The name, defaults or notes section is missing
+    MissingSection,
+    /// Unknown key or malformed value in the defaults section
+    InvalidDefault,
+    /// Duration is not one of 1, 2, 4, 8, 16 or 32
+    InvalidDuration,
+    /// Octave is outside the range covered by the note table
+    InvalidOctave,
+    /// Beats per minute is outside 25..=900
+    InvalidTempo,
+    /// Note letter is not one of a-g or p
+    InvalidNote,
+}
+
+pub struct Rtttl<'a> {
+    name: &'a str,
+    duration: u8,
+    octave: u8,
+    tempo: u16,
+    notes: &'a str,
+}
+
+impl<'a> Rtttl<'a> {
+    /// Parse an RTTTL string, checking every note up front
+    pub fn parse(text: &'a str) -> Result<Self, RtttlError> {
+        let mut sections = text.splitn(3, ':');
+        let name = sections.next().ok_or(RtttlError::MissingSection)?.trim();
+        let defaults = sections.next().ok_or(RtttlError::MissingSection)?;
+        let notes = sections.next().ok_or(RtttlError::MissingSection)?;
+
+        let mut rtttl = Self {
+            name,
+            duration: DEFAULT_DURATION,
+            octave: DEFAULT_OCTAVE,
+            tempo: DEFAULT_TEMPO,
+            notes,
+        };
+
+        for default in defaults.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (key, value) = default.split_once('=').ok_or(RtttlError::InvalidDefault)?;
+            let value = value.trim();
+            match key.trim() {
+                "d" | "D" => {
+                    rtttl.duration = parse_duration(value)?.ok_or(RtttlError::InvalidDuration)?
+                }
+                "o" | "O" => {
+                    rtttl.octave = value.parse().map_err(|_| RtttlError::InvalidOctave)?;
+                    check_octave(rtttl.octave)?;
+                }
+                "b" | "B" => rtttl.tempo = parse_tempo(value)?,
+                _ => return Err(RtttlError::InvalidDefault),
+            }
+        }
+
+        rtttl.notes().try_for_each(|note| note.map(|_| ()))?;
+
+        Ok(rtttl)
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Beats (quarter notes) per minute, as expected by `Song::new`
+    pub fn tempo(&self) -> u16 {
+        self.tempo
+    }
+
+    /// Iterate over the notes as `(frequency, divider)` pairs
+    ///
+    /// A negative divider marks a dotted note, matching `Song::note_ticks`.
+    pub fn notes(&self) -> Notes<'a> {
+        Notes {
+            tokens: self.notes.split(','),
+            duration: self.duration,
+            octave: self.octave,
+        }
+    }
+}
+
+pub struct Notes<'a> {
+    tokens: core::str::Split<'a, char>,
+    duration: u8,
+    octave: u8,
+}
+
+impl Iterator for Notes<'_> {
+    type Item = Result<(f64, i16), RtttlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self
+            .tokens
+            .by_ref()
+            .map(str::trim)
+            .find(|t| !t.is_empty())?;
+        Some(parse_note(token, self.duration, self.octave))
+    }
+}
+
+fn parse_note(
+    token: &str,
+    default_duration: u8,
+    default_octave: u8,
+) -> Result<(f64, i16), RtttlError> {
+    let digits = token.bytes().take_while(u8::is_ascii_digit).count();
+    let (duration, rest) = token.split_at(digits);
+    let duration = parse_duration(duration)?.unwrap_or(default_duration);
+
+    let mut chars = rest.bytes().peekable();
+    let letter = chars.next().ok_or(RtttlError::InvalidNote)?;
+    let semitone = match letter.to_ascii_lowercase() {
+        b'c' => Some(0),
+        b'd' => Some(2),
+        b'e' => Some(4),
+        b'f' => Some(5),
+        b'g' => Some(7),
+        b'a' => Some(9),
+        b'b' | b'h' => Some(11),
+        b'p' => None,
+        _ => return Err(RtttlError::InvalidNote),
+    };
+
+    let sharp = chars.next_if_eq(&b'#').is_some();
+    let mut dotted = chars.next_if_eq(&b'.').is_some();
+
+    let octave = match chars.next_if(u8::is_ascii_digit) {
+        Some(digit) => digit - b'0',
+        None => default_octave,
+    };
+    check_octave(octave)?;
+
+    dotted |= chars.next_if_eq(&b'.').is_some();
+    if chars.next().is_some() {
+        return Err(RtttlError::InvalidNote);
+    }
+
+    let frequency = match semitone {
+        Some(semitone) => {
+            let number = (octave + 1) * 12 + semitone + sharp as u8;
+            midi_note(number).ok_or(RtttlError::InvalidOctave)?
+        }
+        None => REST,
+    };
+
+    let divider = duration as i16;
+    Ok((frequency, if dotted { -divider } else { divider }))
+}
+
+/// Parse an optional note duration; an empty string means "use the default"
+fn parse_duration(value: &str) -> Result<Option<u8>, RtttlError> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match value.parse() {
+        Ok(duration @ (1 | 2 | 4 | 8 | 16 | 32)) => Ok(Some(duration)),
+        _ => Err(RtttlError::InvalidDuration),
+    }
+}
+
+fn parse_tempo(value: &str) -> Result<u16, RtttlError> {
+    match value.parse() {
+        Ok(tempo @ 25..=900) => Ok(tempo),
+        _ => Err(RtttlError::InvalidTempo),
+    }
+}
+
+fn check_octave(octave: u8) -> Result<(), RtttlError> {
+    match octave {
+        1..=8 => Ok(()),
+        _ => Err(RtttlError::InvalidOctave),
+    }
+}
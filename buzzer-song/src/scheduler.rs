@@ -0,0 +1,128 @@
+//! Place the notes of each voice on the shared timeline of a score.
+//!
+//! Every voice is played by its own task, so the tasks must agree on when
+//! each note starts. Instead of sleeping for one note length after another,
+//! which lets rounding errors pile up differently in every voice, note
+//! positions are counted in ticks and turned into milliseconds from the
+//! common start of the song.
+
+use crate::music::{ARTICULATION, Song, TIE, VOLUME, is_marker};
+
+/// One note of a voice, placed on the timeline of the song
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteEvent {
+    pub frequency: f64,
+    /// Loudness of the note from 0 to 100, set by the last `VOLUME` marker
+    pub volume: u8,
+    /// Share of the note that sounds in percent, the rest is a gap before the next note
+    pub articulation: u8,
+    /// Milliseconds from the start of the song until the note starts
+    pub start_ms: u64,
+    /// Milliseconds from the start of the song until the next note starts
+    pub end_ms: u64,
+}
+
+impl NoteEvent {
+    /// When to stop the tone, leaving the gap set by the articulation silent
+    pub fn release_ms(&self) -> u64 {
+        self.start_ms + (self.end_ms - self.start_ms) * self.articulation as u64 / 100
+    }
+}
+
+pub struct Timeline<'a> {
+    song: Song,
+    notes: core::slice::Iter<'a, (f64, i16)>,
+    tick: u32,
+    volume: u8,
+    articulation: u8,
+}
+
+impl<'a> Timeline<'a> {
+    pub fn new(song: Song, notes: &'a [(f64, i16)]) -> Self {
+        Self {
+            song,
+            notes: notes.iter(),
+            tick: 0,
+            volume: 100,
+            articulation: song.articulation(),
+        }
+    }
+
+    /// Skip ahead to the first note starting at or after `tick`
+    ///
+    /// Used to resume a song part way through. A note that is already
+    /// sounding at `tick` is skipped rather than cut in half.
+    pub fn skip_to(mut self, tick: u32) -> Self {
+        // Going through `next` keeps the volume markers on the way
+        while self.tick < tick && self.next().is_some() {}
+        self
+    }
+
+    /// The next note or rest, applying the markers on the way
+    fn next_note(&mut self) -> Option<(f64, i16)> {
+        loop {
+            let &(frequency, divider) = self.notes.next()?;
+            if !is_marker(frequency) {
+                return Some((frequency, divider));
+            }
+            self.apply_marker(frequency, divider);
+        }
+    }
+
+    fn apply_marker(&mut self, marker: f64, value: i16) {
+        if marker == VOLUME {
+            self.volume = value.clamp(0, 100) as u8;
+        } else if marker == ARTICULATION {
+            self.articulation = value.clamp(0, 100) as u8;
+        }
+    }
+
+    /// Move past a `TIE` coming up before the next note, applying the
+    /// markers in front of it, or leave everything as it is if there is none
+    fn take_tie(&mut self) -> bool {
+        let tied = self
+            .notes
+            .as_slice()
+            .iter()
+            .find(|&&(f, _)| f == TIE || !is_marker(f))
+            .is_some_and(|&(f, _)| f == TIE);
+        if tied {
+            while let Some(&(frequency, divider)) = self.notes.next()
+                && frequency != TIE
+            {
+                self.apply_marker(frequency, divider);
+            }
+        }
+        tied
+    }
+}
+
+impl Iterator for Timeline<'_> {
+    type Item = NoteEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (frequency, divider) = self.next_note()?;
+        let volume = self.volume;
+        let articulation = self.articulation;
+
+        let start = self.tick;
+        self.tick += Song::note_ticks(divider);
+
+        // Tied notes sound as one, so later notes only add their length. A
+        // marker before or after the tie changes the notes after it.
+        while self.take_tie() {
+            match self.next_note() {
+                Some((_, divider)) => self.tick += Song::note_ticks(divider),
+                None => break,
+            }
+        }
+
+        Some(NoteEvent {
+            frequency: self.song.pitch(frequency),
+            volume,
+            articulation,
+            start_ms: self.song.ticks_to_millis(start),
+            end_ms: self.song.ticks_to_millis(self.tick),
+        })
+    }
+}
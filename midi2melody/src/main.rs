@@ -0,0 +1,227 @@
+//! Convert a track of a Standard MIDI File into a melody table for buzzer-song.
+//!
+//! The output is a Rust module in the same shape as `buzzer-song/src/got.rs`:
+//! a `TEMPO` and an array of `(frequency, divider)` pairs using the `NOTE_*`
+//! constants from `music.rs`. With `--millis` it writes `(frequency,
+//! milliseconds)` pairs instead, which keep tempo changes and timing that
+//! doesn't fit on the note grid.
+
+mod melody;
+#[cfg(test)]
+mod melody_tests;
+
+use std::fmt::Write as _;
+use std::process::ExitCode;
+
+use melody::{Melody, Selection, TICKS_PER_WHOLE};
+
+const USAGE: &str = "\
+Usage: midi2melody <file.mid> [options]
+
+Options:
+  --track <n>     Track to convert, counting from 0 (default: first with notes)
+  --channel <n>   MIDI channel from 1 to 16 (default: all except drums on 10)
+  --grid <n>      Snap notes to this note length, 32 is a 1/32 note (default: 32)
+  --name <name>   Name of the melody array (default: MELODY)
+  --millis        Write (frequency, milliseconds) pairs instead of dividers
+  -o <file>       Write the module to a file instead of stdout";
+
+struct Options {
+    input: String,
+    output: Option<String>,
+    selection: Selection,
+    grid: u32,
+    name: String,
+    millis: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut input = None;
+    let mut options = Options {
+        input: String::new(),
+        output: None,
+        selection: Selection {
+            track: None,
+            channel: None,
+        },
+        grid: 32,
+        name: "MELODY".into(),
+        millis: false,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or(format!("{flag} needs a value"));
+        match arg.as_str() {
+            "--track" => {
+                let track = value("--track")?;
+                let track = track.parse().map_err(|_| format!("bad track {track}"))?;
+                options.selection.track = Some(track);
+            }
+            "--channel" => {
+                let channel = value("--channel")?;
+                match channel.parse::<u8>() {
+                    Ok(number @ 1..=16) => options.selection.channel = Some(number - 1),
+                    _ => return Err(format!("bad channel {channel}, expected 1 to 16")),
+                }
+            }
+            "--grid" => {
+                let grid = value("--grid")?;
+                options.grid = grid.parse().map_err(|_| format!("bad grid {grid}"))?;
+            }
+            "--name" => options.name = value("--name")?,
+            "--millis" => options.millis = true,
+            "-o" => options.output = Some(value("-o")?),
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+            _ if input.is_none() => input = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}\n\n{USAGE}")),
+        }
+    }
+
+    // The grid has to be a whole number of ticks that note lengths can fill
+    let step = TICKS_PER_WHOLE.checked_div(options.grid).unwrap_or(0);
+    if step < 4 || step * options.grid != TICKS_PER_WHOLE || !step.is_multiple_of(2) {
+        return Err(format!(
+            "bad grid {}, use 1, 2, 3, 4, 6, 8, 12, 16, 24, 32, 48, 64 or 96",
+            options.grid
+        ));
+    }
+
+    options.input = input.ok_or(USAGE)?;
+    Ok(options)
+}
+
+/// The frequency constant for a segment, `REST` when nothing sounds
+fn frequency(key: Option<u8>) -> String {
+    key.map_or("REST".into(), melody::note_name)
+}
+
+/// Notes as `(frequency, divider)` pairs, snapped to the grid and tied where needed
+fn divider_entries(melody: &Melody, grid: u32) -> Result<Vec<(String, i16)>, String> {
+    let lengths = melody::note_lengths();
+    let mut entries = Vec::new();
+
+    for segment in &melody.segments {
+        let start = melody.quantize(segment.start, grid);
+        let end = melody.quantize(segment.end, grid);
+        if end <= start {
+            // Shorter than the grid, the note before or after covers it
+            continue;
+        }
+
+        let dividers = melody::split_length(end - start, &lengths)
+            .ok_or(format!("can't write {} ticks as notes", end - start))?;
+        let name = frequency(segment.key);
+        for (i, divider) in dividers.into_iter().enumerate() {
+            // Rests can simply follow each other, notes need a tie to sound as one
+            if i > 0 && segment.key.is_some() {
+                entries.push(("TIE".into(), 0));
+            }
+            entries.push((name.clone(), divider));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Notes as `(frequency, milliseconds)` pairs, following every tempo change
+fn millis_entries(melody: &Melody) -> Vec<(String, u64)> {
+    melody
+        .segments
+        .iter()
+        .filter_map(|segment| {
+            let start = melody.tick_to_millis(segment.start).round() as u64;
+            let end = melody.tick_to_millis(segment.end).round() as u64;
+            (end > start).then(|| (frequency(segment.key), end - start))
+        })
+        .collect()
+}
+
+fn render(options: &Options, melody: &Melody) -> Result<String, String> {
+    let mut out = String::new();
+    let source = std::path::Path::new(&options.input)
+        .file_name()
+        .map_or(options.input.clone(), |name| name.to_string_lossy().into());
+
+    writeln!(
+        out,
+        "// Generated by midi2melody from {source}, track {}",
+        melody.track
+    )
+    .unwrap();
+    writeln!(out, "use crate::music::*;").unwrap();
+    writeln!(out).unwrap();
+
+    if options.millis {
+        let entries = millis_entries(melody);
+        writeln!(out, "/// Notes as (frequency, milliseconds)").unwrap();
+        writeln!(
+            out,
+            "pub const {}: [(f64, u64); {}] = [",
+            options.name,
+            entries.len()
+        )
+        .unwrap();
+        for (name, millis) in entries {
+            writeln!(out, "    ({name}, {millis}),").unwrap();
+        }
+    } else {
+        let entries = divider_entries(melody, options.grid)?;
+        writeln!(out, "pub const TEMPO: u16 = {};", melody.bpm()).unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "pub const {}: [(f64, i16); {}] = [",
+            options.name,
+            entries.len()
+        )
+        .unwrap();
+        for (name, divider) in entries {
+            writeln!(out, "    ({name}, {divider}),").unwrap();
+        }
+    }
+    writeln!(out, "];").unwrap();
+
+    Ok(out)
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args(std::env::args().skip(1))?;
+
+    let bytes =
+        std::fs::read(&options.input).map_err(|e| format!("can't read {}: {e}", options.input))?;
+    let smf = midly::Smf::parse(&bytes).map_err(|e| format!("invalid MIDI file: {e}"))?;
+    let melody = melody::extract(&smf, &options.selection)?;
+
+    if melody.shifted > 0 {
+        eprintln!(
+            "warning: moved {} notes by octaves to fit the note table",
+            melody.shifted
+        );
+    }
+    if melody.tempos.len() > 1 && !options.millis {
+        eprintln!(
+            "warning: the tempo changes {} times, only the first tempo is kept (try --millis)",
+            melody.tempos.len() - 1
+        );
+    }
+
+    let module = render(&options, &melody)?;
+    match &options.output {
+        Some(path) => std::fs::write(path, module).map_err(|e| format!("can't write {path}: {e}")),
+        None => {
+            print!("{module}");
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,334 @@
+//! Turn the notes of one MIDI track into a single line of notes and rests.
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+/// Length of a whole note in buzzer-song ticks, see `music::TICKS_PER_WHOLE`
+pub const TICKS_PER_WHOLE: u32 = 384;
+
+/// MIDI note number of `NOTE_B0`, the lowest note in the buzzer-song table
+const FIRST_NOTE: u8 = 23;
+/// MIDI note number of `NOTE_DS8`, the highest note in the table
+const LAST_NOTE: u8 = 111;
+
+/// MIDI channel 10, which General MIDI uses for drums
+const DRUM_CHANNEL: u8 = 9;
+
+/// Tempo used by MIDI files without a tempo event, 120 bpm
+const DEFAULT_MICROS_PER_QUARTER: u32 = 500_000;
+
+/// A note (or a rest, for `None`) between two points in MIDI ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub key: Option<u8>,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Which notes of the file to use
+pub struct Selection {
+    /// Index of the track, or `None` for the first one that has notes
+    pub track: Option<usize>,
+    /// Channel from 0 to 15, or `None` for every channel except drums
+    pub channel: Option<u8>,
+}
+
+/// The monophonic line of one track, still in MIDI ticks
+pub struct Melody {
+    pub track: usize,
+    pub ticks_per_quarter: u32,
+    /// Tempo changes as (tick, microseconds per quarter note), in order
+    pub tempos: Vec<(u64, u32)>,
+    pub segments: Vec<Segment>,
+    /// Notes moved by whole octaves to fit the buzzer-song note table
+    pub shifted: usize,
+}
+
+pub fn extract(smf: &Smf, selection: &Selection) -> Result<Melody, String> {
+    let ticks_per_quarter = match smf.header.timing {
+        Timing::Metrical(ticks) => ticks.as_int() as u32,
+        Timing::Timecode(..) => return Err("SMPTE timecode files are not supported".into()),
+    };
+
+    let tempos = tempo_map(smf);
+
+    let track = match selection.track {
+        Some(track) if track < smf.tracks.len() => track,
+        Some(track) => {
+            return Err(format!(
+                "track {track} does not exist, the file has {} tracks",
+                smf.tracks.len()
+            ));
+        }
+        None => (0..smf.tracks.len())
+            .find(|&track| !note_events(smf, track, selection.channel).is_empty())
+            .ok_or("no track has any notes")?,
+    };
+
+    let events = note_events(smf, track, selection.channel);
+    if events.is_empty() {
+        return Err(format!("track {track} has no notes on the chosen channel"));
+    }
+
+    let mut segments = skyline(&events);
+    let shifted = fit_to_table(&mut segments);
+
+    Ok(Melody {
+        track,
+        ticks_per_quarter,
+        tempos,
+        segments,
+        shifted,
+    })
+}
+
+/// Note on and off events of a track as (tick, key, on), in time order
+fn note_events(smf: &Smf, track: usize, channel: Option<u8>) -> Vec<(u64, u8, bool)> {
+    let mut tick = 0;
+    let mut events = Vec::new();
+
+    for event in &smf.tracks[track] {
+        tick += event.delta.as_int() as u64;
+
+        let TrackEventKind::Midi {
+            channel: event_channel,
+            message,
+        } = event.kind
+        else {
+            continue;
+        };
+        let event_channel = event_channel.as_int();
+        let wanted = match channel {
+            Some(channel) => event_channel == channel,
+            None => event_channel != DRUM_CHANNEL,
+        };
+        if !wanted {
+            continue;
+        }
+
+        match message {
+            // A note on with velocity 0 is the usual shorthand for a note off
+            MidiMessage::NoteOn { key, vel } => events.push((tick, key.as_int(), vel > 0)),
+            MidiMessage::NoteOff { key, .. } => events.push((tick, key.as_int(), false)),
+            _ => {}
+        }
+    }
+
+    events
+}
+
+/// Tempo changes from every track, most files keep them in the first one
+fn tempo_map(smf: &Smf) -> Vec<(u64, u32)> {
+    let mut tempos = Vec::new();
+    for track in &smf.tracks {
+        let mut tick = 0;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            if let TrackEventKind::Meta(MetaMessage::Tempo(tempo)) = event.kind {
+                tempos.push((tick, tempo.as_int()));
+            }
+        }
+    }
+
+    tempos.sort_by_key(|&(tick, _)| tick);
+    if tempos.first().is_none_or(|&(tick, _)| tick > 0) {
+        tempos.insert(0, (0, DEFAULT_MICROS_PER_QUARTER));
+    }
+    tempos
+}
+
+/// Keep only the highest note sounding at any time
+///
+/// A buzzer plays one note at a time, and the melody is usually on top of
+/// the chords, so when notes overlap the highest one wins.
+fn skyline(events: &[(u64, u8, bool)]) -> Vec<Segment> {
+    let mut held = [0u32; 128];
+    let mut segments = Vec::new();
+    let mut current = Segment {
+        key: None,
+        start: 0,
+        end: 0,
+    };
+
+    let mut i = 0;
+    while i < events.len() {
+        let tick = events[i].0;
+
+        // Apply every event on this tick before deciding what sounds
+        let mut retriggered = false;
+        while let Some(&(event_tick, key, on)) = events.get(i) {
+            if event_tick != tick {
+                break;
+            }
+            let count = &mut held[key as usize];
+            if on {
+                *count += 1;
+                retriggered |= current.key == Some(key);
+            } else {
+                *count = count.saturating_sub(1);
+            }
+            i += 1;
+        }
+
+        let top = (0..128u8).rev().find(|&key| held[key as usize] > 0);
+        if top != current.key || (retriggered && top.is_some()) {
+            if tick > current.start {
+                current.end = tick;
+                segments.push(current);
+            }
+            current = Segment {
+                key: top,
+                start: tick,
+                end: tick,
+            };
+        }
+    }
+
+    // Every note has been released by the last event, so `current` is a rest
+    segments
+}
+
+/// Move notes outside the note table by whole octaves until they fit
+fn fit_to_table(segments: &mut [Segment]) -> usize {
+    let mut shifted = 0;
+    for key in segments
+        .iter_mut()
+        .filter_map(|segment| segment.key.as_mut())
+    {
+        if !(FIRST_NOTE..=LAST_NOTE).contains(key) {
+            while *key < FIRST_NOTE {
+                *key += 12;
+            }
+            while *key > LAST_NOTE {
+                *key -= 12;
+            }
+            shifted += 1;
+        }
+    }
+    shifted
+}
+
+impl Melody {
+    /// Tempo at the start of the track in beats per minute
+    pub fn bpm(&self) -> u16 {
+        let micros = self.tempos[0].1 as f64;
+        (60_000_000.0 / micros).round() as u16
+    }
+
+    /// Milliseconds from the start of the file until `tick`, following tempo changes
+    pub fn tick_to_millis(&self, tick: u64) -> f64 {
+        let mut millis = 0.0;
+        for (i, &(start, micros)) in self.tempos.iter().enumerate() {
+            if start >= tick {
+                break;
+            }
+            let end = self
+                .tempos
+                .get(i + 1)
+                .map_or(tick, |&(next, _)| next.min(tick));
+            millis += (end - start) as f64 * micros as f64 / self.ticks_per_quarter as f64 / 1000.0;
+        }
+        millis
+    }
+
+    /// Convert a MIDI tick into buzzer-song ticks, snapped to a `grid` note
+    pub fn quantize(&self, tick: u64, grid: u32) -> u32 {
+        let step = (TICKS_PER_WHOLE / grid) as f64;
+        let song_ticks = tick as f64 * (TICKS_PER_WHOLE / 4) as f64 / self.ticks_per_quarter as f64;
+        ((song_ticks / step).round() * step) as u32
+    }
+}
+
+/// A note length buzzer-song understands
+#[derive(Debug, Clone, Copy)]
+pub struct NoteLength {
+    pub divider: i16,
+    pub ticks: u32,
+    /// Plain notes are preferred over dotted ones, and those over triplets
+    cost: u32,
+}
+
+/// Plain notes, dotted notes (negative dividers) and triplets, down to the
+/// smallest note that is still a whole number of ticks
+pub fn note_lengths() -> Vec<NoteLength> {
+    let mut lengths = Vec::new();
+    for divider in [1i16, 2, 4, 8, 16, 32, 64] {
+        let ticks = TICKS_PER_WHOLE / divider as u32;
+        lengths.push(NoteLength {
+            divider,
+            ticks,
+            cost: 10,
+        });
+        lengths.push(NoteLength {
+            divider: -divider,
+            ticks: ticks * 3 / 2,
+            cost: 11,
+        });
+        lengths.push(NoteLength {
+            divider: divider * 3 / 2,
+            ticks: ticks * 2 / 3,
+            cost: 12,
+        });
+    }
+    lengths.retain(|length| length.divider != 1);
+    lengths.push(NoteLength {
+        divider: 1,
+        ticks: TICKS_PER_WHOLE,
+        cost: 10,
+    });
+    lengths
+}
+
+/// Split a length in buzzer-song ticks into the fewest notes that add up to it
+///
+/// Returns `None` if nothing adds up to exactly `ticks`. The shortest notes
+/// are 4, 6 and 9 ticks long, so that only happens for 1, 2, 3, 5, 7 and 11
+/// ticks.
+pub fn split_length(ticks: u32, lengths: &[NoteLength]) -> Option<Vec<i16>> {
+    let ticks = ticks as usize;
+
+    // best[t] is the cheapest way to fill t ticks and the last length used
+    let mut best: Vec<Option<(u32, usize)>> = vec![None; ticks + 1];
+    best[0] = Some((0, usize::MAX));
+    for t in 1..=ticks {
+        for (i, length) in lengths.iter().enumerate() {
+            let Some(rest) = t.checked_sub(length.ticks as usize) else {
+                continue;
+            };
+            if let Some((cost, _)) = best[rest] {
+                let cost = cost + length.cost;
+                if best[t].is_none_or(|(best_cost, _)| cost < best_cost) {
+                    best[t] = Some((cost, i));
+                }
+            }
+        }
+    }
+
+    best[ticks]?;
+    let mut dividers = Vec::new();
+    let mut t = ticks;
+    while t > 0 {
+        let (_, i) = best[t]?;
+        dividers.push(lengths[i].divider);
+        t -= lengths[i].ticks as usize;
+    }
+
+    // Long notes first reads the way a tied note is written
+    dividers.sort_by_key(|&divider| std::cmp::Reverse(note_ticks(divider, lengths)));
+    Some(dividers)
+}
+
+fn note_ticks(divider: i16, lengths: &[NoteLength]) -> u32 {
+    lengths
+        .iter()
+        .find(|length| length.divider == divider)
+        .map_or(0, |length| length.ticks)
+}
+
+/// The `NOTE_*` constant name for a MIDI note number, 60 is `NOTE_C4`
+pub fn note_name(key: u8) -> String {
+    const NAMES: [&str; 12] = [
+        "C", "CS", "D", "DS", "E", "F", "FS", "G", "GS", "A", "AS", "B",
+    ];
+    let octave = key as i32 / 12 - 1;
+    format!("NOTE_{}{}", NAMES[key as usize % 12], octave)
+}
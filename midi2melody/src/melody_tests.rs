@@ -0,0 +1,176 @@
+//! Tests against `fixtures/melody.mid`, a two track file at 100 bpm with 96
+//! ticks per quarter, so MIDI ticks and buzzer-song ticks are the same.
+//!
+//! Track 0 only sets the tempo. Track 1 plays, in order:
+//!
+//! - a C major chord for a quarter, with G4 on top
+//! - A4 for a quarter, with C5 over the third sixteenth of it
+//! - B4 for a quarter, starting a tick late and ending a tick early
+//! - D5 for a whole note and an eighth
+//! - an eighth rest, then E5 for a quarter, with a drum hit on channel 10
+//! - F5 for a dotted quarter
+
+use crate::melody::{self, Melody, Segment, Selection, note_lengths, split_length};
+use crate::{divider_entries, millis_entries};
+
+const FIXTURE: &[u8] = include_bytes!("../fixtures/melody.mid");
+
+const ANY: Selection = Selection {
+    track: None,
+    channel: None,
+};
+
+fn extract(selection: &Selection) -> Result<Melody, String> {
+    let smf = midly::Smf::parse(FIXTURE).unwrap();
+    melody::extract(&smf, selection)
+}
+
+fn segment(key: Option<u8>, start: u64, end: u64) -> Segment {
+    Segment { key, start, end }
+}
+
+#[test]
+fn skyline_keeps_the_top_note() {
+    let melody = extract(&ANY).unwrap();
+    assert_eq!(melody.track, 1);
+    assert_eq!(melody.bpm(), 100);
+    assert_eq!(melody.shifted, 0);
+    assert_eq!(
+        melody.segments,
+        [
+            segment(Some(67), 0, 96),
+            segment(Some(69), 96, 144),
+            segment(Some(72), 144, 168),
+            // Back to the note still held under the C5
+            segment(Some(69), 168, 192),
+            segment(None, 192, 193),
+            segment(Some(71), 193, 287),
+            segment(None, 287, 288),
+            segment(Some(74), 288, 720),
+            segment(None, 720, 768),
+            // The drum hit is on top, but channel 10 is left out
+            segment(Some(76), 768, 864),
+            segment(Some(77), 864, 1008),
+        ]
+    );
+}
+
+#[test]
+fn channel_and_track_selection() {
+    let drums = extract(&Selection {
+        track: None,
+        channel: Some(9),
+    })
+    .unwrap();
+    // Silent until the drum hit
+    assert_eq!(
+        drums.segments,
+        [segment(None, 0, 768), segment(Some(81), 768, 800)]
+    );
+
+    let tempo_track = Selection {
+        track: Some(0),
+        channel: None,
+    };
+    assert!(extract(&tempo_track).is_err());
+    let missing = Selection {
+        track: Some(2),
+        channel: None,
+    };
+    assert!(extract(&missing).is_err());
+}
+
+#[test]
+fn quantization_snaps_to_the_grid() {
+    let melody = extract(&ANY).unwrap();
+    // 1/32 notes are 12 ticks
+    assert_eq!(melody.quantize(193, 32), 192);
+    assert_eq!(melody.quantize(287, 32), 288);
+    assert_eq!(melody.quantize(198, 32), 204);
+    // 1/8 notes are 48 ticks, halfway rounds up
+    assert_eq!(melody.quantize(168, 8), 192);
+    assert_eq!(melody.quantize(167, 8), 144);
+}
+
+#[test]
+fn lengths_split_into_the_fewest_notes() {
+    let lengths = note_lengths();
+    assert_eq!(split_length(384, &lengths), Some(vec![1]));
+    assert_eq!(split_length(432, &lengths), Some(vec![1, 8]));
+    assert_eq!(split_length(768, &lengths), Some(vec![1, 1]));
+    // Dotted before plain, and plain before triplets
+    assert_eq!(split_length(144, &lengths), Some(vec![-4]));
+    assert_eq!(split_length(120, &lengths), Some(vec![4, 16]));
+    assert_eq!(split_length(32, &lengths), Some(vec![12]));
+    assert_eq!(split_length(256, &lengths), Some(vec![2, 6]));
+    // Shorter than anything, or between the 4, 6 and 9 tick notes
+    for ticks in [1, 2, 3, 5, 7, 11] {
+        assert_eq!(split_length(ticks, &lengths), None, "{ticks} ticks");
+    }
+    assert_eq!(split_length(97, &lengths), Some(vec![6, 16, -64]));
+}
+
+fn entries(grid: u32) -> Vec<(String, i16)> {
+    divider_entries(&extract(&ANY).unwrap(), grid).unwrap()
+}
+
+fn expected(entries: &[(&str, i16)]) -> Vec<(String, i16)> {
+    entries
+        .iter()
+        .map(|&(name, divider)| (name.to_string(), divider))
+        .collect()
+}
+
+#[test]
+fn converts_to_dividers() {
+    assert_eq!(
+        entries(32),
+        expected(&[
+            ("NOTE_G4", 4),
+            ("NOTE_A4", 8),
+            ("NOTE_C5", 16),
+            ("NOTE_A4", 16),
+            // The tick of rest on either side is shorter than the grid
+            ("NOTE_B4", 4),
+            ("NOTE_D5", 1),
+            ("TIE", 0),
+            ("NOTE_D5", 8),
+            ("REST", 8),
+            ("NOTE_E5", 4),
+            ("NOTE_F5", -4),
+        ])
+    );
+}
+
+#[test]
+fn coarse_grid_drops_short_notes() {
+    assert_eq!(
+        entries(8),
+        expected(&[
+            ("NOTE_G4", 4),
+            ("NOTE_A4", 8),
+            // Stretched to the grid, leaving nothing of the A4 after it
+            ("NOTE_C5", 8),
+            ("NOTE_B4", 4),
+            ("NOTE_D5", 1),
+            ("TIE", 0),
+            ("NOTE_D5", 8),
+            ("REST", 8),
+            ("NOTE_E5", 4),
+            ("NOTE_F5", -4),
+        ])
+    );
+}
+
+#[test]
+fn converts_to_millis() {
+    let melody = extract(&ANY).unwrap();
+    let entries = millis_entries(&melody);
+    // A quarter at 100 bpm is 600 ms
+    assert_eq!(entries[0], ("NOTE_G4".to_string(), 600));
+    // Without a grid, even the tick of rest before the B4 is kept
+    assert_eq!(entries[4], ("REST".to_string(), 6));
+    assert_eq!(entries[5], ("NOTE_B4".to_string(), 588));
+    let total: u64 = entries.iter().map(|&(_, millis)| millis).sum();
+    assert_eq!(total, 6300);
+}
@@ -0,0 +1,92 @@
+//! Tests for the allow-list of the access-control firmware.
+
+use crate::allow_list::{AllowList, CAPACITY, ListError, RECORD_SIZE, TagUid};
+
+fn uid(bytes: &[u8]) -> TagUid {
+    TagUid::new(bytes).unwrap()
+}
+
+#[test]
+fn uid_sizes() {
+    for len in [4, 7, 10] {
+        let bytes = [0x42; 10];
+        assert_eq!(uid(&bytes[..len]).as_bytes(), &bytes[..len]);
+    }
+    for len in [0, 3, 5, 11] {
+        assert_eq!(TagUid::new(&[1; 11][..len]), Err(ListError::InvalidUid));
+    }
+    // A 4 byte UID isn't the start of a 7 byte one
+    assert_ne!(uid(&[1, 2, 3, 4]), uid(&[1, 2, 3, 4, 0, 0, 0]));
+}
+
+#[test]
+fn add_and_remove() {
+    let single = uid(&[0x13, 0x37, 0x73, 0x31]);
+    let double = uid(&[0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+    let mut list = AllowList::new();
+    assert!(list.is_empty());
+
+    assert_eq!(list.add(single), Ok(true));
+    assert_eq!(list.add(double), Ok(true));
+    assert_eq!(list.add(single), Ok(false));
+    assert_eq!(list.len(), 2);
+    assert!(list.contains(&double));
+
+    assert!(list.remove(&single));
+    assert!(!list.remove(&single));
+    assert!(!list.contains(&single));
+    assert_eq!(list.iter().collect::<Vec<_>>(), vec![&double]);
+}
+
+#[test]
+fn list_is_bounded() {
+    let mut list = AllowList::new();
+    for i in 0..CAPACITY as u8 {
+        assert_eq!(list.add(uid(&[i, 0, 0, 0])), Ok(true));
+    }
+    assert_eq!(list.add(uid(&[0xFF, 0, 0, 0])), Err(ListError::Full));
+    // Already there, so it still fits
+    assert_eq!(list.add(uid(&[0, 0, 0, 0])), Ok(false));
+
+    assert!(list.remove(&uid(&[5, 0, 0, 0])));
+    assert_eq!(list.add(uid(&[0xFF, 0, 0, 0])), Ok(true));
+}
+
+#[test]
+fn record_round_trip() {
+    let mut list = AllowList::new();
+    list.add(uid(&[0x13, 0x37, 0x73, 0x31])).unwrap();
+    list.add(uid(&[0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66]))
+        .unwrap();
+    list.add(uid(&[0x04, 1, 2, 3, 4, 5, 6, 7, 8, 9])).unwrap();
+
+    let record = list.to_record();
+    assert_eq!(&record[..5], b"UIDL\x03");
+    assert_eq!(AllowList::from_record(&record), Ok(list));
+    assert_eq!(
+        AllowList::from_record(&AllowList::new().to_record()),
+        Ok(AllowList::new())
+    );
+}
+
+#[test]
+fn bad_records_are_rejected() {
+    // Erased flash
+    assert_eq!(
+        AllowList::from_record(&[0xFF; RECORD_SIZE]),
+        Err(ListError::BadRecord)
+    );
+
+    let mut list = AllowList::new();
+    list.add(uid(&[1, 2, 3, 4])).unwrap();
+    let record = list.to_record();
+    for i in [0, 4, 6, RECORD_SIZE - 1] {
+        let mut broken = record;
+        broken[i] ^= 0x01;
+        assert_eq!(
+            AllowList::from_record(&broken),
+            Err(ListError::BadRecord),
+            "byte {i}"
+        );
+    }
+}
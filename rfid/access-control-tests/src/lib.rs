@@ -0,0 +1,11 @@
+//! Host tests for the access-control firmware.
+//!
+//! The allow-list only uses `core`, so it is pulled in from
+//! the firmware with `#[path]` and run here with `cargo test`.
+
+// The firmware module under test, at the path it has in its crate
+#[cfg(test)]
+#[path = "../../access-control/src/allow_list.rs"]
+mod allow_list;
+#[cfg(test)]
+mod allow_list_tests;
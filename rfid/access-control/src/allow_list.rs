@@ -0,0 +1,170 @@
+//! The list of tags allowed in, and how it is stored in flash.
+//!
+//! The record kept in flash is:
+//!
+//! ```text
+//! | magic "UIDL" | count | 32 x (length, 10 UID bytes) | CRC-16 LE |
+//! ```
+//!
+//! Erased flash reads back as 0xFF, so a board that never saved a list fails
+//! the magic check and starts with an empty one.
+//!
+//! This file is tested on the host by access-control-tests, so it only uses
+//! `core`.
+
+/// Most tags the list holds
+pub const CAPACITY: usize = 32;
+
+const MAGIC: [u8; 4] = *b"UIDL";
+const ENTRY_SIZE: usize = 11;
+/// Size of the record saved to flash
+pub const RECORD_SIZE: usize = MAGIC.len() + 1 + CAPACITY * ENTRY_SIZE + 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListError {
+    /// UIDs are 4, 7 or 10 bytes long
+    InvalidUid,
+    /// The list already holds `CAPACITY` tags
+    Full,
+    /// The record isn't a saved list, or it was corrupted
+    BadRecord,
+}
+
+/// A single, double or triple size UID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TagUid {
+    len: u8,
+    bytes: [u8; 10],
+}
+
+impl TagUid {
+    pub fn new(uid: &[u8]) -> Result<Self, ListError> {
+        if !matches!(uid.len(), 4 | 7 | 10) {
+            return Err(ListError::InvalidUid);
+        }
+        let mut bytes = [0u8; 10];
+        bytes[..uid.len()].copy_from_slice(uid);
+        Ok(TagUid {
+            len: uid.len() as u8,
+            bytes,
+        })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
+    }
+}
+
+/// CRC-16/CCITT-FALSE, the same as the memory-dump-usb frames
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowList {
+    uids: [TagUid; CAPACITY],
+    len: usize,
+}
+
+impl Default for AllowList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AllowList {
+    pub const fn new() -> Self {
+        AllowList {
+            uids: [TagUid {
+                len: 0,
+                bytes: [0; 10],
+            }; CAPACITY],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &TagUid> {
+        self.uids[..self.len].iter()
+    }
+
+    pub fn contains(&self, uid: &TagUid) -> bool {
+        self.iter().any(|u| u == uid)
+    }
+
+    /// Add a tag, returning false if it was already on the list
+    pub fn add(&mut self, uid: TagUid) -> Result<bool, ListError> {
+        if self.contains(&uid) {
+            return Ok(false);
+        }
+        if self.len == CAPACITY {
+            return Err(ListError::Full);
+        }
+        self.uids[self.len] = uid;
+        self.len += 1;
+        Ok(true)
+    }
+
+    /// Remove a tag, returning false if it wasn't on the list
+    pub fn remove(&mut self, uid: &TagUid) -> bool {
+        let Some(i) = self.iter().position(|u| u == uid) else {
+            return false;
+        };
+        self.uids.copy_within(i + 1..self.len, i);
+        self.len -= 1;
+        true
+    }
+
+    /// The record to save to flash
+    pub fn to_record(&self) -> [u8; RECORD_SIZE] {
+        let mut record = [0u8; RECORD_SIZE];
+        record[..4].copy_from_slice(&MAGIC);
+        record[4] = self.len as u8;
+        for (entry, uid) in record[5..].chunks_exact_mut(ENTRY_SIZE).zip(self.iter()) {
+            entry[0] = uid.len;
+            entry[1..].copy_from_slice(&uid.bytes);
+        }
+        let crc = crc16(&record[..RECORD_SIZE - 2]);
+        record[RECORD_SIZE - 2..].copy_from_slice(&crc.to_le_bytes());
+        record
+    }
+
+    /// Load a list saved with `to_record`
+    pub fn from_record(record: &[u8; RECORD_SIZE]) -> Result<Self, ListError> {
+        let crc = u16::from_le_bytes([record[RECORD_SIZE - 2], record[RECORD_SIZE - 1]]);
+        if record[..4] != MAGIC || crc != crc16(&record[..RECORD_SIZE - 2]) {
+            return Err(ListError::BadRecord);
+        }
+
+        let count = record[4] as usize;
+        if count > CAPACITY {
+            return Err(ListError::BadRecord);
+        }
+        let mut list = AllowList::new();
+        for entry in record[5..].chunks_exact(ENTRY_SIZE).take(count) {
+            let uid = entry
+                .get(1..1 + entry[0] as usize)
+                .ok_or(ListError::BadRecord)?;
+            list.add(TagUid::new(uid).map_err(|_| ListError::BadRecord)?)?;
+        }
+        Ok(list)
+    }
+}
@@ -0,0 +1,164 @@
+#![no_std]
+#![no_main]
+
+// Shared with tap-logger, which grants the tags enrolled here
+mod allow_list;
+mod storage;
+
+use embassy_executor::Spawner;
+use embassy_rp::block::ImageDef;
+use embassy_time::{Duration, Instant, Timer};
+
+//Panic Handler
+use panic_probe as _;
+
+// Defmt Logging
+use defmt_rtt as _;
+
+// For SPI
+use embassy_rp::spi::Spi;
+use embassy_rp::{self as hal, spi};
+use embassy_time::Delay;
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+// For CS Pin, the LED and the enrolment button
+use embassy_rp::gpio::{Input, Level, Output, Pull};
+
+// For the allow-list
+use embassy_rp::flash::Flash;
+
+// Driver for the MFRC522
+use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
+
+use crate::allow_list::{AllowList, ListError, TagUid};
+use crate::storage::ListFlash;
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+// Replace the UID Bytes with the tag you want to use as the master card
+const MASTER_UID: &[u8] = &[0x13, 0x37, 0x73, 0x31];
+
+/// Enrolment ends after this long without a tag
+const ENROL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Add the tag to the list, or remove it if it is already there
+fn enrol(uid: TagUid, list: &mut AllowList, flash: &mut ListFlash) -> Result<(), &'static str> {
+    if list.remove(&uid) {
+        defmt::info!("Removed {=[u8]:02X}", uid.as_bytes());
+    } else {
+        list.add(uid).map_err(|e| match e {
+            ListError::Full => "The allow-list is full",
+            _ => "Can't add the tag",
+        })?;
+        defmt::info!("Added {=[u8]:02X}", uid.as_bytes());
+    }
+    storage::save(flash, list)?;
+    defmt::info!("Saved the allow-list, {} tags", list.len());
+    Ok(())
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let miso = p.PIN_0;
+    let cs_pin = Output::new(p.PIN_1, Level::High);
+    let clk = p.PIN_2;
+    let mosi = p.PIN_3;
+
+    let mut config = spi::Config::default();
+    config.frequency = 1_000_000;
+
+    let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
+
+    let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
+
+    let itf = SpiInterface::new(spi);
+    let mut rfid = Mfrc522::new(itf)
+        .init()
+        .expect("failed to initialize the RFID reader");
+
+    let mut led = Output::new(p.PIN_25, Level::Low);
+    let button = Input::new(p.PIN_15, Pull::Up);
+
+    let mut flash: ListFlash = Flash::new_blocking(p.FLASH);
+    let mut list = storage::load(&mut flash);
+    defmt::info!("Loaded the allow-list, {} tags", list.len());
+    for uid in list.iter() {
+        defmt::info!("  {=[u8]:02X}", uid.as_bytes());
+    }
+    if list.is_empty() {
+        defmt::info!("Tap the master card or press the button to enrol tags");
+    }
+
+    // When enrolment mode ends, if it is on
+    let mut enrolling: Option<Instant> = None;
+
+    loop {
+        if enrolling.is_none() && button.is_low() {
+            defmt::info!("Enrolment started, tap tags to add or remove them");
+            enrolling = Some(Instant::now() + ENROL_TIMEOUT);
+        }
+        if enrolling.is_some_and(|end| Instant::now() > end) {
+            defmt::info!("Enrolment ended");
+            enrolling = None;
+        }
+
+        // The LED blinks during enrolment
+        if enrolling.is_some() {
+            led.toggle();
+        } else {
+            led.set_low();
+        }
+
+        if let Ok(atqa) = rfid.reqa()
+            && let Ok(uid) = rfid.select(&atqa)
+        {
+            // Halted, the tag won't answer again until it leaves the field
+            let _ = rfid.hlta();
+            let time = Instant::now().as_secs();
+
+            let Ok(uid) = TagUid::new(uid.as_bytes()) else {
+                defmt::warn!("[{}s] Tag with an invalid UID", time);
+                continue;
+            };
+
+            if uid.as_bytes() == MASTER_UID {
+                if enrolling.take().is_some() {
+                    defmt::info!("[{}s] Enrolment ended by the master card", time);
+                } else {
+                    defmt::info!("[{}s] Enrolment started by the master card", time);
+                    enrolling = Some(Instant::now() + ENROL_TIMEOUT);
+                }
+            } else if enrolling.is_some() {
+                if let Err(e) = enrol(uid, &mut list, &mut flash) {
+                    defmt::error!("[{}s] Enrolment failed: {:?}", time, e);
+                }
+                enrolling = Some(Instant::now() + ENROL_TIMEOUT);
+            } else if list.contains(&uid) {
+                defmt::info!("[{}s] Granted {=[u8]:02X}", time, uid.as_bytes());
+                led.set_high();
+                Timer::after_millis(500).await;
+            } else {
+                defmt::warn!("[{}s] Denied {=[u8]:02X}", time, uid.as_bytes());
+            }
+        }
+        Timer::after_millis(100).await;
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recommended to have these minimal entries.
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
+    embassy_rp::binary_info::rp_program_name!(c"access-control"),
+    embassy_rp::binary_info::rp_program_description!(c"RFID access control with an allow-list"),
+    embassy_rp::binary_info::rp_cargo_version!(),
+    embassy_rp::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,40 @@
+//! Keep the allow-list in the last sector of the on-board flash.
+//!
+//! memory.x gives the program only the first 2 MiB of the Pico 2's 4 MiB, so
+//! the last sector is never overwritten by flashing a new build.
+
+use embassy_rp::flash::{Blocking, ERASE_SIZE, Flash};
+use embassy_rp::peripherals::FLASH;
+
+use crate::allow_list::{AllowList, RECORD_SIZE};
+
+pub const FLASH_SIZE: usize = 4 * 1024 * 1024;
+const LIST_OFFSET: u32 = (FLASH_SIZE - ERASE_SIZE) as u32;
+
+pub type ListFlash = Flash<'static, FLASH, Blocking, FLASH_SIZE>;
+
+/// The saved list, or an empty one if nothing valid was saved
+pub fn load(flash: &mut ListFlash) -> AllowList {
+    let mut record = [0u8; RECORD_SIZE];
+    if flash.blocking_read(LIST_OFFSET, &mut record).is_err() {
+        defmt::warn!("Can't read the allow-list from flash");
+        return AllowList::new();
+    }
+
+    match AllowList::from_record(&record) {
+        Ok(list) => list,
+        Err(_) => {
+            defmt::info!("No allow-list saved, starting with an empty one");
+            AllowList::new()
+        }
+    }
+}
+
+pub fn save(flash: &mut ListFlash, list: &AllowList) -> Result<(), &'static str> {
+    flash
+        .blocking_erase(LIST_OFFSET, LIST_OFFSET + ERASE_SIZE as u32)
+        .map_err(|_| "Flash erase failed")?;
+    flash
+        .blocking_write(LIST_OFFSET, &list.to_record())
+        .map_err(|_| "Flash write failed")
+}
@@ -0,0 +1,81 @@
+//! Select the card that answered a request, keeping its ATQA and SAK.
+//!
+//! The same ISO 14443-3 steps as `activate` in memory-dump-usb, over the
+//! async driver.
+
+use embedded_hal_async::spi::SpiDevice;
+use mfrc522::{GenericUid, Uid};
+
+use crate::crc::crc_a;
+use crate::error::DriverError;
+use crate::reader::AsyncMfrc522;
+
+/// Anticollision and select commands for cascade levels 1 to 3
+const SELECT: [u8; 3] = [0x93, 0x95, 0x97];
+/// Cascade tag that starts the first part of a UID longer than 4 bytes
+const CASCADE_TAG: u8 = 0x88;
+/// Set in the SAK when the UID continues in the next cascade level
+const SAK_UID_INCOMPLETE: u8 = 0x04;
+
+/// A selected card and how it answered
+pub struct Card {
+    pub uid: Uid,
+    pub atqa: [u8; 2],
+    pub sak: u8,
+}
+
+/// Select the card that answered with `atqa`
+///
+/// If several cards answered, anticollision picks one of them.
+pub async fn select<E, SPI>(
+    rfid: &mut AsyncMfrc522<SPI>,
+    atqa: [u8; 2],
+) -> Result<Card, DriverError>
+where
+    SPI: SpiDevice<Error = E>,
+{
+    let mut uid = [0u8; 10];
+    let mut len = 0;
+    for select in SELECT {
+        // Anticollision: the card answers with 4 UID bytes and their XOR (BCC)
+        let part = rfid.anticollision(select).await?;
+        if part[0] ^ part[1] ^ part[2] ^ part[3] != part[4] {
+            return Err(DriverError::Bcc);
+        }
+
+        let mut tx = [0u8; 9];
+        tx[0] = select;
+        tx[1] = 0x70;
+        tx[2..7].copy_from_slice(&part);
+        let crc = crc_a(&tx[..7]);
+        tx[7..].copy_from_slice(&crc);
+
+        let rx = rfid.transceive::<3>(&tx, 0, 0).await?;
+        if rx.valid_bytes != 3 {
+            return Err(DriverError::IncompleteFrame);
+        }
+        if crc_a(&rx.buffer[..1]) != rx.buffer[1..] {
+            return Err(DriverError::Crc);
+        }
+        let sak = rx.buffer[0];
+
+        if sak & SAK_UID_INCOMPLETE != 0 && part[0] == CASCADE_TAG {
+            uid[len..len + 3].copy_from_slice(&part[1..4]);
+            len += 3;
+            continue;
+        }
+
+        uid[len..len + 4].copy_from_slice(&part[..4]);
+        len += 4;
+
+        let uid = match len {
+            4 => Uid::Single(GenericUid::new(uid[..4].try_into().unwrap(), sak)),
+            7 => Uid::Double(GenericUid::new(uid[..7].try_into().unwrap(), sak)),
+            _ => Uid::Triple(GenericUid::new(uid, sak)),
+        };
+        return Ok(Card { uid, atqa, sak });
+    }
+
+    // Still incomplete after the third cascade level
+    Err(DriverError::Protocol)
+}
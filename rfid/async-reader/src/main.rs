@@ -0,0 +1,143 @@
+#![no_std]
+#![no_main]
+
+mod card;
+// Shared with memory-dump-usb, not everything is needed here
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+// Shared with card-inventory, which resets the field between inventories
+#[allow(dead_code)]
+mod reader;
+
+use embassy_executor::Spawner;
+use embassy_rp::block::ImageDef;
+use embassy_time::Timer;
+
+//Panic Handler
+use panic_probe as _;
+
+// Defmt Logging
+use defmt_rtt as _;
+
+// For SPI
+use embassy_rp::spi::Spi;
+use embassy_rp::{self as hal, spi};
+use embassy_time::Delay;
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+// For CS Pin, the IRQ pin and the LED
+use embassy_rp::gpio::{Input, Level, Output, Pull};
+
+use crate::card_type::atqa_value;
+use crate::reader::AsyncMfrc522;
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+/// Keeps blinking while the reader waits for cards, the executor is never stalled
+#[embassy_executor::task]
+async fn heartbeat_task(mut led: Output<'static>) -> ! {
+    loop {
+        led.toggle();
+        Timer::after_millis(250).await;
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    spawner.must_spawn(heartbeat_task(Output::new(p.PIN_25, Level::Low)));
+
+    let miso = p.PIN_0;
+    let cs_pin = Output::new(p.PIN_1, Level::High);
+    let clk = p.PIN_2;
+    let mosi = p.PIN_3;
+    // The MFRC522 drives IRQ open drain, active low
+    let irq = Input::new(p.PIN_4, Pull::Up);
+
+    let mut config = spi::Config::default();
+    config.frequency = 1_000_000;
+
+    let spi_bus = Spi::new(p.SPI0, clk, mosi, miso, p.DMA_CH0, p.DMA_CH1, config);
+
+    let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
+
+    Timer::after_millis(100).await;
+
+    let mut rfid = AsyncMfrc522::new(spi, irq)
+        .await
+        .expect("failed to initialize the RFID reader");
+    match rfid.version().await {
+        Ok(version) => defmt::info!("Initialized RFID reader, version {=u8:#x}", version),
+        Err(_) => defmt::warn!("Can't read the RFID reader version"),
+    }
+
+    loop {
+        let Ok(atqa) = rfid.wait_for_card().await else {
+            defmt::error!("Lost the RFID reader");
+            Timer::after_millis(1000).await;
+            continue;
+        };
+
+        match card::select(&mut rfid, atqa).await {
+            Ok(card) => {
+                defmt::info!(
+                    "UID: {=[u8]:02x}, ATQA: {=u16:04x}, SAK: {=u8:02x}",
+                    card.uid.as_bytes(),
+                    atqa_value(card.atqa),
+                    card.sak
+                );
+                read_block(&card, &mut rfid).await;
+            }
+            Err(e) => defmt::warn!("Error selecting the card: {}", e),
+        }
+
+        let _ = rfid.hlta().await;
+        let _ = rfid.stop_crypto1().await;
+        Timer::after_millis(500).await;
+    }
+}
+
+/// Print block 4, opened with the default key
+async fn read_block<E, SPI>(card: &card::Card, rfid: &mut AsyncMfrc522<SPI>)
+where
+    SPI: embedded_hal_async::spi::SpiDevice<Error = E>,
+{
+    const AUTH_KEY: [u8; 6] = [0xFF; 6];
+    const BLOCK: u8 = 4;
+
+    if rfid
+        .mf_authenticate(&card.uid, BLOCK, &AUTH_KEY)
+        .await
+        .is_err()
+    {
+        defmt::warn!("Auth failed for block {}", BLOCK);
+        return;
+    }
+    match rfid.mf_read(BLOCK).await {
+        Ok(data) => defmt::info!("Block {}: {=[u8]:02x}", BLOCK, data),
+        Err(_) => defmt::warn!("Read failed for block {}", BLOCK),
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recommended to have these minimal entries.
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
+    embassy_rp::binary_info::rp_program_name!(c"async-reader"),
+    embassy_rp::binary_info::rp_program_description!(c"MFRC522 over DMA SPI and its IRQ pin"),
+    embassy_rp::binary_info::rp_cargo_version!(),
+    embassy_rp::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
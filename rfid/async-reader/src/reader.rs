@@ -0,0 +1,420 @@
+//! Async driver for the MFRC522, over DMA SPI and its IRQ pin.
+//!
+//! The mfrc522 crate only talks blocking SPI and busy-polls the chip's
+//! interrupt register until a card answers, so the executor can't run
+//! anything else in the meantime. This does the same register work with
+//! `embedded-hal-async`, and waits for the IRQ pin to go low instead of
+//! polling. It reuses the crate's `Error`, `FifoData` and `Uid` types, so
+//! code written against it looks the same.
+//!
+//! The chip has no interrupt for a card entering the field. `wait_for_card`
+//! sends REQA and awaits the IRQ for the answer or the chip's 25 ms timeout,
+//! sleeping between tries.
+
+use embassy_rp::gpio::Input;
+use embassy_time::{Duration, Timer, with_timeout};
+use embedded_hal_async::spi::{Operation, SpiDevice};
+use mfrc522::{Error, FifoData, MifareKey, Uid};
+
+use crate::crc::crc_a;
+
+// Registers used here, with their datasheet addresses
+const COMMAND_REG: u8 = 0x01;
+const COM_IEN_REG: u8 = 0x02;
+const COM_IRQ_REG: u8 = 0x04;
+const ERROR_REG: u8 = 0x06;
+const STATUS2_REG: u8 = 0x08;
+const FIFO_DATA_REG: u8 = 0x09;
+const FIFO_LEVEL_REG: u8 = 0x0A;
+const CONTROL_REG: u8 = 0x0C;
+const BIT_FRAMING_REG: u8 = 0x0D;
+const COLL_REG: u8 = 0x0E;
+const MODE_REG: u8 = 0x11;
+const TX_MODE_REG: u8 = 0x12;
+const RX_MODE_REG: u8 = 0x13;
+const TX_CONTROL_REG: u8 = 0x14;
+const TX_ASK_REG: u8 = 0x15;
+const MOD_WIDTH_REG: u8 = 0x24;
+const T_MODE_REG: u8 = 0x2A;
+const T_PRESCALER_REG: u8 = 0x2B;
+const T_RELOAD_REG_HIGH: u8 = 0x2C;
+const T_RELOAD_REG_LOW: u8 = 0x2D;
+const VERSION_REG: u8 = 0x37;
+
+// Commands
+const IDLE: u8 = 0x00;
+const TRANSCEIVE: u8 = 0x0C;
+const MF_AUTHENT: u8 = 0x0E;
+const SOFT_RESET: u8 = 0x0F;
+
+// ComIrqReg and ComIEnReg bits
+const TIMER_IRQ: u8 = 1 << 0;
+const ERR_IRQ: u8 = 1 << 1;
+const IDLE_IRQ: u8 = 1 << 4;
+const RX_IRQ: u8 = 1 << 5;
+/// In ComIEnReg: drive the IRQ pin low while an enabled interrupt is set
+const IRQ_INV: u8 = 1 << 7;
+
+// ErrorReg bits
+const PROTOCOL_ERR: u8 = 1 << 0;
+const PARITY_ERR: u8 = 1 << 1;
+const CRC_ERR: u8 = 1 << 2;
+const COLL_ERR: u8 = 1 << 3;
+const BUFFER_OVFL: u8 = 1 << 4;
+
+const POWER_DOWN: u8 = 1 << 4;
+const FLUSH_BUFFER: u8 = 1 << 7;
+const FORCE_100_ASK: u8 = 1 << 6;
+const MF_CRYPTO1_ON: u8 = 1 << 3;
+/// In CollReg: keep receiving after a collision, cleared so the FIFO stops at it
+const VALUES_AFTER_COLL: u8 = 1 << 7;
+const COLL_POS_NOT_VALID: u8 = 1 << 5;
+
+/// Request command, sent as a 7 bit short frame
+const REQA: u8 = 0x26;
+const MF_AUTH_KEY_A: u8 = 0x60;
+const MF_READ: u8 = 0x30;
+
+/// How long to sleep between REQAs while no card is in the field
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long the field stays off to reset the cards, ISO 14443 asks for at least 5 ms
+const FIELD_OFF: Duration = Duration::from_millis(10);
+/// The chip times out after 25 ms, a missing IRQ means it isn't wired
+const IRQ_TIMEOUT: Duration = Duration::from_millis(100);
+
+pub struct AsyncMfrc522<SPI> {
+    spi: SPI,
+    irq: Input<'static>,
+}
+
+impl<E, SPI> AsyncMfrc522<SPI>
+where
+    SPI: SpiDevice<Error = E>,
+{
+    /// Reset and set up the MFRC522, the same way the mfrc522 crate does
+    ///
+    /// `irq` is the pin wired to the module's IRQ, with a pull-up since the
+    /// chip drives it open drain.
+    pub async fn new(spi: SPI, irq: Input<'static>) -> Result<Self, Error<E>> {
+        let mut rfid = AsyncMfrc522 { spi, irq };
+
+        rfid.write(COMMAND_REG, SOFT_RESET).await?;
+        while rfid.read(COMMAND_REG).await? & POWER_DOWN != 0 {
+            Timer::after_millis(1).await;
+        }
+
+        rfid.write(TX_MODE_REG, 0x00).await?;
+        rfid.write(RX_MODE_REG, 0x00).await?;
+        rfid.write(MOD_WIDTH_REG, 0x26).await?;
+        // Timer starts after every transmission and stops the wait after 25 ms
+        rfid.write(T_MODE_REG, 0x80).await?;
+        rfid.write(T_PRESCALER_REG, 0xA9).await?;
+        rfid.write(T_RELOAD_REG_HIGH, 0x03).await?;
+        rfid.write(T_RELOAD_REG_LOW, 0xE8).await?;
+        rfid.write(TX_ASK_REG, FORCE_100_ASK).await?;
+        rfid.write(MODE_REG, 0x3D).await?;
+        // The interrupts that end a command, routed to the IRQ pin
+        rfid.write(
+            COM_IEN_REG,
+            IRQ_INV | RX_IRQ | IDLE_IRQ | ERR_IRQ | TIMER_IRQ,
+        )
+        .await?;
+        // Antenna on
+        let tx_control = rfid.read(TX_CONTROL_REG).await?;
+        rfid.write(TX_CONTROL_REG, tx_control | 0b11).await?;
+
+        Ok(rfid)
+    }
+
+    /// The version reported by the MFRC522, 0x91 or 0x92
+    pub async fn version(&mut self) -> Result<u8, Error<E>> {
+        self.read(VERSION_REG).await
+    }
+
+    /// Send REQA until a card answers, returning its ATQA
+    pub async fn wait_for_card(&mut self) -> Result<[u8; 2], Error<E>> {
+        loop {
+            match self.reqa().await {
+                Ok(atqa) => return Ok(atqa),
+                Err(Error::Comm(e)) => return Err(Error::Comm(e)),
+                Err(_) => Timer::after(POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    /// Ask the idle cards in the field for their ATQA
+    pub async fn reqa(&mut self) -> Result<[u8; 2], Error<E>> {
+        self.request(REQA).await
+    }
+
+    /// Switch the field off and on again, which resets every card in it
+    ///
+    /// A halted card stays quiet until the field goes off, so it isn't
+    /// counted twice while the other cards are selected. Without power they
+    /// all start over idle, so the next round of REQAs reaches every one of
+    /// them again.
+    pub async fn reset_field(&mut self) -> Result<(), Error<E>> {
+        let tx_control = self.read(TX_CONTROL_REG).await?;
+        self.write(TX_CONTROL_REG, tx_control & !0b11).await?;
+        Timer::after(FIELD_OFF).await;
+        self.write(TX_CONTROL_REG, tx_control | 0b11).await?;
+        // Time for the cards to power up before the first request
+        Timer::after(FIELD_OFF).await;
+        Ok(())
+    }
+
+    async fn request(&mut self, command: u8) -> Result<[u8; 2], Error<E>> {
+        let rx = self.transceive::<2>(&[command], 7, 0).await?;
+        if rx.valid_bytes != 2 || rx.valid_bits != 0 {
+            return Err(Error::IncompleteFrame);
+        }
+        Ok(rx.buffer)
+    }
+
+    /// Put the card in the HALT state
+    pub async fn hlta(&mut self) -> Result<(), Error<E>> {
+        let mut tx = [0x50, 0, 0, 0];
+        let crc = crc_a(&tx[..2]);
+        tx[2..].copy_from_slice(&crc);
+
+        // Silence means the card accepted it
+        match self.transceive::<0>(&tx, 0, 0).await {
+            Err(Error::Timeout) => Ok(()),
+            Ok(_) => Err(Error::Nak),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Switch off the MIFARE Crypto1 unit after talking to an authenticated card
+    pub async fn stop_crypto1(&mut self) -> Result<(), Error<E>> {
+        let status = self.read(STATUS2_REG).await?;
+        self.write(STATUS2_REG, status & !MF_CRYPTO1_ON).await
+    }
+
+    /// Authenticate a block with key A
+    pub async fn mf_authenticate(
+        &mut self,
+        uid: &Uid,
+        block: u8,
+        key: &MifareKey,
+    ) -> Result<(), Error<E>> {
+        let mut tx = [0u8; 12];
+        tx[0] = MF_AUTH_KEY_A;
+        tx[1] = block;
+        tx[2..8].copy_from_slice(key);
+        tx[8..].copy_from_slice(&uid.as_bytes()[..4]);
+
+        self.start(&tx, MF_AUTHENT).await?;
+        self.wait_irq(IDLE_IRQ | ERR_IRQ).await?;
+        self.check_error_register().await?;
+
+        if self.read(STATUS2_REG).await? & MF_CRYPTO1_ON == 0 {
+            return Err(Error::Protocol);
+        }
+        Ok(())
+    }
+
+    /// Read a block, after authenticating it
+    pub async fn mf_read(&mut self, block: u8) -> Result<[u8; 16], Error<E>> {
+        let mut tx = [MF_READ, block, 0, 0];
+        let crc = crc_a(&tx[..2]);
+        tx[2..].copy_from_slice(&crc);
+
+        let rx = self.transceive::<18>(&tx, 0, 0).await?;
+        if rx.valid_bytes != 18 || crc_a(&rx.buffer[..16]) != rx.buffer[16..] {
+            return Err(Error::Crc);
+        }
+        Ok(rx.buffer[..16].try_into().unwrap())
+    }
+
+    /// Send a frame and wait for the answer, as `Mfrc522::transceive` does
+    pub async fn transceive<const RX: usize>(
+        &mut self,
+        tx: &[u8],
+        tx_last_bits: u8,
+        rx_align_bits: u8,
+    ) -> Result<FifoData<RX>, Error<E>> {
+        self.start(tx, TRANSCEIVE).await?;
+        // Start sending
+        self.write(
+            BIT_FRAMING_REG,
+            (1 << 7) | ((rx_align_bits & 0b0111) << 4) | (tx_last_bits & 0b0111),
+        )
+        .await?;
+
+        self.wait_irq(RX_IRQ | IDLE_IRQ | ERR_IRQ).await?;
+        self.check_error_register().await?;
+        self.fifo_data().await
+    }
+
+    /// Get the 4 UID bytes and BCC of one cascade level, `select` being its SEL command
+    ///
+    /// When several cards answer, their UIDs collide at the first bit that
+    /// differs. That bit is set to 1 and sent back, so only the cards with a
+    /// 1 there keep answering, until one card is left.
+    pub async fn anticollision(&mut self, select: u8) -> Result<[u8; 5], Error<E>> {
+        let coll = self.read(COLL_REG).await?;
+        self.write(COLL_REG, coll & !VALUES_AFTER_COLL).await?;
+
+        // SEL, NVB, then the UID bits known so far
+        let mut tx = [0u8; 7];
+        tx[0] = select;
+        let mut known_bits: u8 = 0;
+        // Every collision adds at least one known bit out of 32
+        for _ in 0..32 {
+            let last_bits = known_bits % 8;
+            let tx_bytes = 2 + known_bits / 8;
+            let end = tx_bytes as usize + usize::from(last_bits > 0);
+            tx[1] = (tx_bytes << 4) | last_bits;
+
+            // The answer starts where the known bits end
+            self.start(&tx[..end], TRANSCEIVE).await?;
+            self.write(BIT_FRAMING_REG, (1 << 7) | (last_bits << 4) | last_bits)
+                .await?;
+            self.wait_irq(RX_IRQ | IDLE_IRQ | ERR_IRQ).await?;
+
+            let err = self.read(ERROR_REG).await?;
+            if err & BUFFER_OVFL != 0 {
+                return Err(Error::BufferOverflow);
+            } else if err & PARITY_ERR != 0 {
+                return Err(Error::Parity);
+            } else if err & PROTOCOL_ERR != 0 {
+                return Err(Error::Protocol);
+            }
+
+            let rx = self.fifo_data::<5>().await?;
+            if rx.valid_bytes > 0 {
+                let first = 2 + known_bits as usize / 8;
+                let mask = 0xFF << last_bits;
+                tx[first] = (tx[first] & !mask) | (rx.buffer[0] & mask);
+                let rest = (rx.valid_bytes - 1).min(tx.len() - first - 1);
+                tx[first + 1..first + 1 + rest].copy_from_slice(&rx.buffer[1..=rest]);
+            }
+            if err & COLL_ERR == 0 {
+                return Ok(tx[2..].try_into().unwrap());
+            }
+
+            let coll = self.read(COLL_REG).await?;
+            if coll & COLL_POS_NOT_VALID != 0 {
+                return Err(Error::Collision);
+            }
+            let position = match coll & 0x1F {
+                0 => 32,
+                position => position,
+            };
+            if position <= known_bits {
+                return Err(Error::Collision);
+            }
+            known_bits = position;
+            let bit = known_bits - 1;
+            tx[2 + bit as usize / 8] |= 1 << (bit % 8);
+        }
+        Err(Error::Collision)
+    }
+
+    /// Load the FIFO and start a command
+    async fn start(&mut self, tx: &[u8], command: u8) -> Result<(), Error<E>> {
+        self.write(COMMAND_REG, IDLE).await?;
+        // Clear the interrupt flags, which also releases the IRQ pin
+        self.write(COM_IRQ_REG, 0x7F).await?;
+        self.write(FIFO_LEVEL_REG, FLUSH_BUFFER).await?;
+        self.write(BIT_FRAMING_REG, 0).await?;
+        self.write_many(FIFO_DATA_REG, tx).await?;
+        self.write(COMMAND_REG, command).await
+    }
+
+    /// Sleep until the IRQ pin says the command is done
+    async fn wait_irq(&mut self, done: u8) -> Result<(), Error<E>> {
+        if with_timeout(IRQ_TIMEOUT, self.irq.wait_for_low())
+            .await
+            .is_err()
+        {
+            return Err(Error::Timeout);
+        }
+
+        let irq = self.read(COM_IRQ_REG).await?;
+        if irq & done != 0 {
+            Ok(())
+        } else {
+            Err(Error::Timeout)
+        }
+    }
+
+    async fn check_error_register(&mut self) -> Result<(), Error<E>> {
+        let err = self.read(ERROR_REG).await?;
+
+        if err & PROTOCOL_ERR != 0 {
+            Err(Error::Protocol)
+        } else if err & PARITY_ERR != 0 {
+            Err(Error::Parity)
+        } else if err & CRC_ERR != 0 {
+            Err(Error::Crc)
+        } else if err & COLL_ERR != 0 {
+            Err(Error::Collision)
+        } else if err & BUFFER_OVFL != 0 {
+            Err(Error::BufferOverflow)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn fifo_data<const RX: usize>(&mut self) -> Result<FifoData<RX>, Error<E>> {
+        let mut buffer = [0u8; RX];
+        let mut valid_bits = 0;
+
+        let valid_bytes = self.read(FIFO_LEVEL_REG).await? as usize;
+        if valid_bytes > RX {
+            return Err(Error::NoRoom);
+        }
+        if valid_bytes > 0 {
+            self.read_many(FIFO_DATA_REG, &mut buffer[..valid_bytes])
+                .await?;
+            valid_bits = (self.read(CONTROL_REG).await? & 0x07) as usize;
+        }
+
+        Ok(FifoData {
+            buffer,
+            valid_bytes,
+            valid_bits,
+        })
+    }
+
+    async fn read(&mut self, reg: u8) -> Result<u8, Error<E>> {
+        let mut buffer = [(reg << 1) | 0x80, 0];
+        self.spi
+            .transfer_in_place(&mut buffer)
+            .await
+            .map_err(Error::Comm)?;
+        Ok(buffer[1])
+    }
+
+    async fn read_many(&mut self, reg: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        // Every byte sent asks for the next one, the last one ends the read
+        let address = (reg << 1) | 0x80;
+        buffer.fill(address);
+        if let Some(last) = buffer.last_mut() {
+            *last = 0;
+        }
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[address]),
+                Operation::TransferInPlace(buffer),
+            ])
+            .await
+            .map_err(Error::Comm)
+    }
+
+    async fn write(&mut self, reg: u8, value: u8) -> Result<(), Error<E>> {
+        self.spi
+            .write(&[reg << 1, value])
+            .await
+            .map_err(Error::Comm)
+    }
+
+    async fn write_many(&mut self, reg: u8, bytes: &[u8]) -> Result<(), Error<E>> {
+        self.spi
+            .transaction(&mut [Operation::Write(&[reg << 1]), Operation::Write(bytes)])
+            .await
+            .map_err(Error::Comm)
+    }
+}
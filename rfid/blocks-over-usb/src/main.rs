@@ -1,6 +1,18 @@
 #![no_std]
 #![no_main]
 
+// Shared with memory-dump-usb, which sends whole cards the same way
+#[path = "../../memory-dump-usb/src/card.rs"]
+mod card;
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/frame.rs"]
+#[allow(dead_code)]
+mod frame;
+
 use embassy_executor::Spawner;
 use embassy_rp as hal;
 use embassy_rp::block::ImageDef;
@@ -9,8 +21,15 @@ use embassy_time::Timer;
 //Panic Handler
 use panic_probe as _;
 
+// Defmt Logging, over the debug probe
+use defmt_rtt as _;
+
 // For USB
 use embassy_rp::{peripherals::USB, usb};
+use embassy_usb::UsbDevice;
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embassy_usb::driver::EndpointError;
+use static_cell::StaticCell;
 
 // For SPI
 use embassy_rp::spi;
@@ -24,10 +43,13 @@ use embassy_rp::gpio::{Level, Output};
 // Driver for the MFRC522
 use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
 
-// to prepare buffer with data before writing into USB serial
+// to prepare the metadata record before sending it
 use core::fmt::Write;
 use heapless::String;
 
+use crate::card::Card;
+use crate::card_type::CardType;
+
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
@@ -37,48 +59,176 @@ embassy_rp::bind_interrupts!(struct Irqs {
     USBCTRL_IRQ => usb::InterruptHandler<USB>;
 });
 
-#[embassy_executor::task]
-async fn logger_task(usb: embassy_rp::Peri<'static, embassy_rp::peripherals::USB>) {
-    let driver = embassy_rp::usb::Driver::new(usb, Irqs);
+type MyUsbDriver = usb::Driver<'static, USB>;
+type MyUsbDevice = UsbDevice<'static, MyUsbDriver>;
 
-    embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
+#[embassy_executor::task]
+async fn usb_task(mut usb: MyUsbDevice) -> ! {
+    usb.run().await
 }
 
-fn print_hex(data: &[u8]) {
-    let mut buff: String<64> = String::new();
-    for &d in data.iter() {
-        write!(buff, "{:02x} ", d).expect("failed to write byte into buffer");
-    }
-    log::info!("{}", buff);
-}
+/// The sector that is read, the first one holds the manufacturer block
+const SECTOR: u8 = 0;
+const AUTH_KEY: [u8; 6] = [0xFF; 6];
 
+/// Read the 4 blocks of `sector` into `data`, returning the `frame::STATUS_*` of the sector
 fn read_sector<E, COMM>(
     uid: &mfrc522::Uid,
     sector: u8,
     rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
+    data: &mut [u8; 64],
+) -> u8
 where
     COMM: mfrc522::comm::Interface<Error = E>,
 {
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
     let block_offset = sector * 4;
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
+    if rfid.mf_authenticate(uid, block_offset, &AUTH_KEY).is_err() {
+        return frame::STATUS_LOCKED;
+    }
 
-    for abs_block in block_offset..block_offset + 4 {
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
-        print_hex(&data);
+    for (abs_block, block) in (block_offset..).zip(data.chunks_exact_mut(16)) {
+        match rfid.mf_read(abs_block) {
+            Ok(read) => block.copy_from_slice(&read),
+            Err(_) => {
+                data.fill(0);
+                return frame::STATUS_READ_FAILED;
+            }
+        }
+    }
+    frame::STATUS_OK
+}
+
+fn write_hex<const N: usize>(out: &mut String<N>, data: &[u8]) -> core::fmt::Result {
+    for &d in data.iter() {
+        write!(out, "{:02x}", d)?;
     }
     Ok(())
 }
 
+/// Describe the card and how the sector was opened, as JSON
+///
+/// The same record as memory-dump-usb sends, with a single sector.
+fn write_meta<const N: usize>(card: &Card, status: u8, out: &mut String<N>) -> core::fmt::Result {
+    out.push_str("{\"uid\":\"").map_err(|_| core::fmt::Error)?;
+    write_hex(out, card.uid.as_bytes())?;
+    out.push_str("\",\"atqa\":\"")
+        .map_err(|_| core::fmt::Error)?;
+    write!(
+        out,
+        "{:04x}\",\"sak\":\"{:02x}\",\"type\":\"{}\",\"sectors\":[",
+        card_type::atqa_value(card.atqa),
+        card.sak,
+        CardType::identify(card.atqa, card.sak).name()
+    )?;
+    match status {
+        frame::STATUS_LOCKED => out
+            .push_str("{\"auth\":null,\"key\":null,\"status\":\"locked\"}")
+            .map_err(|_| core::fmt::Error)?,
+        _ => {
+            out.push_str("{\"auth\":\"A\",\"key\":\"")
+                .map_err(|_| core::fmt::Error)?;
+            write_hex(out, &AUTH_KEY)?;
+            let status = match status {
+                frame::STATUS_OK => "ok",
+                _ => "read_failed",
+            };
+            write!(out, "\",\"status\":\"{}\"}}", status)?;
+        }
+    }
+    out.push_str("]}").map_err(|_| core::fmt::Error)
+}
+
+/// Send one frame, split into USB packets
+async fn send_frame(
+    class: &mut CdcAcmClass<'static, MyUsbDriver>,
+    kind: u8,
+    payload: &[u8],
+) -> Result<(), EndpointError> {
+    let mut buff = [0u8; 256];
+    let len = frame::encode(kind, payload, &mut buff).map_err(|_| EndpointError::BufferOverflow)?;
+
+    let max_packet = class.max_packet_size() as usize;
+    for packet in buff[..len].chunks(max_packet) {
+        class.write_packet(packet).await?;
+    }
+    // A full last packet would leave the host waiting for more
+    if len.is_multiple_of(max_packet) {
+        class.write_packet(&[]).await?;
+    }
+    Ok(())
+}
+
+/// Read the sector and send it as a one sector dump that dump-saver can save
+async fn send_sector<E, COMM>(
+    class: &mut CdcAcmClass<'static, MyUsbDriver>,
+    card: &Card,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), EndpointError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut payload = [0u8; 2 + 64];
+    let data = (&mut payload[2..]).try_into().expect("4 blocks");
+    let status = read_sector(&card.uid, SECTOR, rfid, data);
+    payload[0] = SECTOR;
+    payload[1] = status;
+    if status != frame::STATUS_OK {
+        defmt::warn!("Sector {}: status {}", SECTOR, status);
+    }
+
+    let mut meta: String<256> = String::new();
+    if write_meta(card, status, &mut meta).is_err() {
+        defmt::error!("Metadata record doesn't fit");
+        return send_frame(class, frame::KIND_ERROR, b"Metadata too long").await;
+    }
+    send_frame(class, frame::KIND_META, meta.as_bytes()).await?;
+    send_frame(class, frame::KIND_SECTOR, &payload).await?;
+    send_frame(class, frame::KIND_END, &[SECTOR + 1]).await
+}
+
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
 
-    spawner.must_spawn(logger_task(p.USB));
-    Timer::after_secs(3).await;
+    let driver = usb::Driver::new(p.USB, Irqs);
+    // Create embassy-usb Config
+    let config = {
+        let mut config = embassy_usb::Config::new(0xc0de, 0xcafe);
+        config.manufacturer = Some("implRust");
+        config.product = Some("Ferris Block Reader");
+        config.serial_number = Some("12345678");
+        config.max_power = 100;
+        config.max_packet_size_0 = 64;
+        config
+    };
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut builder = {
+        static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+        static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+        static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+
+        embassy_usb::Builder::new(
+            driver,
+            config,
+            CONFIG_DESCRIPTOR.init([0; 256]),
+            BOS_DESCRIPTOR.init([0; 256]),
+            &mut [], // no msos descriptors
+            CONTROL_BUF.init([0; 64]),
+        )
+    };
+
+    // Create classes on the builder.
+    let mut class = {
+        static STATE: StaticCell<State> = StaticCell::new();
+        let state = STATE.init(State::new());
+        CdcAcmClass::new(&mut builder, state, 64)
+    };
+
+    // Build the builder.
+    let usb = builder.build();
+    spawner.must_spawn(usb_task(usb));
 
     let miso = p.PIN_0;
     let cs_pin = Output::new(p.PIN_1, Level::High);
@@ -86,42 +236,38 @@ async fn main(spawner: Spawner) {
     let mosi = p.PIN_3;
 
     let mut config = spi::Config::default();
-    config.frequency = 1000_000;
+    config.frequency = 1_000_000;
 
     let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
     let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
     let itf = SpiInterface::new(spi);
 
-    log::info!("Initializing MFRC522...");
-
-    let mut rfid = match Mfrc522::new(itf).init() {
-        Ok(rfid) => {
-            log::info!("MFRC522 initialized successfully");
-            rfid
-        }
-        Err(e) => {
-            log::error!("Failed to initialize MFRC522: {:?}", e);
-            loop {
-                Timer::after_secs(1).await;
-            }
-        }
-    };
+    Timer::after_millis(100).await;
 
-    log::info!("Waiting for RFID");
+    let mut rfid = Mfrc522::new(itf)
+        .init()
+        .expect("failed to initialize the RFID reader");
+    defmt::info!("Initialized RFID reader");
 
     loop {
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                if let Err(e) = read_sector(&uid, 0, &mut rfid) {
-                    log::error!("Error reading sector: {:?}", e);
-                }
+        class.wait_connection().await;
+        defmt::info!("Host connected, waiting for RFID");
+
+        loop {
+            if let Ok(card) = card::activate(&mut rfid, false) {
+                let sent = send_sector(&mut class, &card, &mut rfid).await;
                 let _ = rfid.hlta();
                 let _ = rfid.stop_crypto1();
+
+                if sent.is_err() {
+                    break;
+                }
                 Timer::after_millis(100).await;
             }
-        }
 
-        Timer::after_millis(100).await;
+            Timer::after_millis(100).await;
+        }
+        defmt::info!("Host disconnected");
     }
 }
 
@@ -0,0 +1,62 @@
+//! Find every card in the field.
+//!
+//! All the cards answer a request together. Anticollision picks one of them,
+//! which is selected and then halted so it stays quiet for the next REQA,
+//! and the REQAs go on until nobody answers. Every inventory starts by
+//! switching the field off and on, so the cards halted by the last one are
+//! idle again and counted too.
+
+use embedded_hal_async::spi::SpiDevice;
+use heapless::Vec;
+use mfrc522::Error;
+
+use crate::card::{self, Card};
+use crate::reader::AsyncMfrc522;
+
+/// Most cards an inventory holds
+pub const MAX_CARDS: usize = 8;
+/// Give up after this many failed selects in a row
+const MAX_FAILURES: u8 = 3;
+
+pub type Cards = Vec<Card, MAX_CARDS>;
+
+/// Fill `cards` with every card in the field
+///
+/// Cards of different types answer with different ATQAs, which collide too.
+/// Those cards are still there to select, their ATQA is kept as zeros.
+pub async fn inventory<E, SPI>(
+    rfid: &mut AsyncMfrc522<SPI>,
+    cards: &mut Cards,
+) -> Result<(), Error<E>>
+where
+    SPI: SpiDevice<Error = E>,
+{
+    cards.clear();
+    rfid.reset_field().await?;
+    let mut failures = 0;
+
+    while !cards.is_full() && failures < MAX_FAILURES {
+        let atqa = match rfid.reqa().await {
+            Ok(atqa) => atqa,
+            Err(Error::Collision) => [0; 2],
+            // Nobody left to answer
+            Err(Error::Timeout) => break,
+            Err(Error::Comm(e)) => return Err(Error::Comm(e)),
+            Err(_) => {
+                failures += 1;
+                continue;
+            }
+        };
+
+        match card::select(rfid, atqa).await {
+            Ok(card) => {
+                let _ = rfid.hlta().await;
+                failures = 0;
+                // Can't fail, the loop stops when the list is full
+                let _ = cards.push(card);
+            }
+            Err(_) => failures += 1,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,158 @@
+#![no_std]
+#![no_main]
+
+// Shared with async-reader
+#[path = "../../async-reader/src/card.rs"]
+mod card;
+// Shared with memory-dump-usb, not everything is needed here
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+mod inventory;
+// Shared with async-reader, not everything is needed here
+#[path = "../../async-reader/src/reader.rs"]
+#[allow(dead_code)]
+mod reader;
+
+use embassy_executor::Spawner;
+use embassy_rp::block::ImageDef;
+use embassy_time::Timer;
+
+//Panic Handler
+use panic_probe as _;
+
+// Defmt Logging
+use defmt_rtt as _;
+
+// For USB
+use embassy_rp::{peripherals::USB, usb};
+
+// For SPI
+use embassy_rp::spi::Spi;
+use embassy_rp::{self as hal, spi};
+use embassy_time::Delay;
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+// For CS Pin and the IRQ pin
+use embassy_rp::gpio::{Input, Level, Output, Pull};
+
+// to prepare buffer with data before writing into USB serial
+use core::fmt::Write;
+use heapless::String;
+
+use crate::card::Card;
+use crate::card_type::atqa_value;
+use crate::inventory::Cards;
+use crate::reader::AsyncMfrc522;
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+embassy_rp::bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
+});
+
+/// How often the field is searched for cards
+const INVENTORY_INTERVAL_MS: u64 = 500;
+
+#[embassy_executor::task]
+async fn logger_task(usb: embassy_rp::Peri<'static, embassy_rp::peripherals::USB>) {
+    let driver = embassy_rp::usb::Driver::new(usb, Irqs);
+
+    embassy_usb_logger::run!(1024, log::LevelFilter::Info, driver);
+}
+
+fn hex(data: &[u8]) -> String<32> {
+    let mut buff = String::new();
+    for &d in data.iter() {
+        write!(buff, "{:02x}", d).expect("failed to write byte into buffer");
+    }
+    buff
+}
+
+fn contains(cards: &Cards, card: &Card) -> bool {
+    cards
+        .iter()
+        .any(|c| c.uid.as_bytes() == card.uid.as_bytes())
+}
+
+/// Report the cards that came or left since the last inventory
+fn report_changes(before: &Cards, now: &Cards) {
+    for card in now.iter().filter(|card| !contains(before, card)) {
+        log::info!(
+            "+ {} ATQA {:04x} SAK {:02x} {:?}",
+            hex(card.uid.as_bytes()),
+            atqa_value(card.atqa),
+            card.sak,
+            card.uid.get_type()
+        );
+    }
+    for card in before.iter().filter(|card| !contains(now, card)) {
+        log::info!("- {}", hex(card.uid.as_bytes()));
+    }
+    if before.len() != now.len() {
+        log::info!("{} cards in the field", now.len());
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    spawner.must_spawn(logger_task(p.USB));
+    Timer::after_secs(3).await;
+
+    let miso = p.PIN_0;
+    let cs_pin = Output::new(p.PIN_1, Level::High);
+    let clk = p.PIN_2;
+    let mosi = p.PIN_3;
+    // The MFRC522 drives IRQ open drain, active low
+    let irq = Input::new(p.PIN_4, Pull::Up);
+
+    let mut config = spi::Config::default();
+    config.frequency = 1_000_000;
+
+    let spi_bus = Spi::new(p.SPI0, clk, mosi, miso, p.DMA_CH0, p.DMA_CH1, config);
+
+    let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
+
+    let mut rfid = AsyncMfrc522::new(spi, irq)
+        .await
+        .expect("failed to initialize the RFID reader");
+    log::info!("Waiting for cards");
+
+    let mut before = Cards::new();
+    let mut now = Cards::new();
+    loop {
+        match inventory::inventory(&mut rfid, &mut now).await {
+            Ok(()) => {
+                report_changes(&before, &now);
+                core::mem::swap(&mut before, &mut now);
+            }
+            Err(_) => log::error!("Lost the RFID reader"),
+        }
+        Timer::after_millis(INVENTORY_INTERVAL_MS).await;
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recommended to have these minimal entries.
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
+    embassy_rp::binary_info::rp_program_name!(c"card-inventory"),
+    embassy_rp::binary_info::rp_program_description!(
+        c"Report cards entering and leaving the field"
+    ),
+    embassy_rp::binary_info::rp_cargo_version!(),
+    embassy_rp::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,233 @@
+//! Tests for the access bits of MIFARE Classic sector trailers.
+
+use crate::access::{
+    Access, AccessConditions, AccessError, Condition, DataPermissions, SectorTrailer,
+    TrailerPermissions,
+};
+
+use Access::*;
+
+/// Every condition as its bits C1 C2 C3
+fn condition(bits: u8) -> Condition {
+    Condition::new(bits & 0b100 != 0, bits & 0b010 != 0, bits & 0b001 != 0)
+}
+
+/// Data block permissions from the datasheet, as (C1 C2 C3, read, write,
+/// increment, decrement)
+const DATA: [(u8, Access, Access, Access, Access); 8] = [
+    (0b000, KeyAOrB, KeyAOrB, KeyAOrB, KeyAOrB),
+    (0b010, KeyAOrB, Never, Never, Never),
+    (0b100, KeyAOrB, KeyB, Never, Never),
+    (0b110, KeyAOrB, KeyB, KeyB, KeyAOrB),
+    (0b001, KeyAOrB, Never, Never, KeyAOrB),
+    (0b011, KeyB, KeyB, Never, Never),
+    (0b101, KeyB, Never, Never, Never),
+    (0b111, Never, Never, Never, Never),
+];
+
+/// Sector trailer permissions from the datasheet, as (C1 C2 C3, key A
+/// write, access bits read, access bits write, key B read, key B write)
+const TRAILER: [(u8, Access, Access, Access, Access, Access); 8] = [
+    (0b000, KeyA, KeyA, Never, KeyA, KeyA),
+    (0b010, Never, KeyA, Never, KeyA, Never),
+    (0b100, KeyB, KeyAOrB, Never, Never, KeyB),
+    (0b110, Never, KeyAOrB, Never, Never, Never),
+    (0b001, KeyA, KeyA, KeyA, KeyA, KeyA),
+    (0b011, KeyB, KeyAOrB, KeyB, Never, KeyB),
+    (0b101, Never, KeyAOrB, KeyB, Never, Never),
+    (0b111, Never, KeyAOrB, Never, Never, Never),
+];
+
+#[test]
+fn condition_bits() {
+    for bits in 0..8 {
+        let condition = condition(bits);
+        assert_eq!(condition.c1(), bits & 0b100 != 0);
+        assert_eq!(condition.c2(), bits & 0b010 != 0);
+        assert_eq!(condition.c3(), bits & 0b001 != 0);
+    }
+}
+
+#[test]
+fn data_permissions() {
+    for (bits, read, write, increment, decrement) in DATA {
+        assert_eq!(
+            condition(bits).data_permissions(),
+            DataPermissions {
+                read,
+                write,
+                increment,
+                decrement,
+            },
+            "data condition {bits:03b}"
+        );
+    }
+}
+
+#[test]
+fn trailer_permissions() {
+    for (bits, key_a_write, access_bits_read, access_bits_write, key_b_read, key_b_write) in TRAILER
+    {
+        assert_eq!(
+            condition(bits).trailer_permissions(),
+            TrailerPermissions {
+                key_a_write,
+                access_bits_read,
+                access_bits_write,
+                key_b_read,
+                key_b_write,
+            },
+            "trailer condition {bits:03b}"
+        );
+    }
+}
+
+#[test]
+fn well_known_access_bytes() {
+    assert_eq!(AccessConditions::TRANSPORT.encode(), [0xFF, 0x07, 0x80]);
+    assert_eq!(
+        AccessConditions::decode([0xFF, 0x07, 0x80]),
+        Ok(AccessConditions::TRANSPORT)
+    );
+
+    // Data blocks open to both keys, the trailer only writable with key B
+    let key_b_manages = AccessConditions {
+        blocks: [condition(0b000); 3],
+        trailer: condition(0b011),
+    };
+    assert_eq!(key_b_manages.encode(), [0x7F, 0x07, 0x88]);
+    assert_eq!(
+        AccessConditions::decode([0x7F, 0x07, 0x88]),
+        Ok(key_b_manages)
+    );
+}
+
+#[test]
+fn each_block_has_its_own_bits() {
+    for block in 0..4 {
+        for bits in 0..8 {
+            let mut conditions = AccessConditions {
+                blocks: [condition(0); 3],
+                trailer: condition(0),
+            };
+            match block {
+                3 => conditions.trailer = condition(bits),
+                _ => conditions.blocks[block] = condition(bits),
+            }
+
+            let bytes = conditions.encode();
+            let c1 = bytes[1] >> 4;
+            let c2 = bytes[2] & 0x0F;
+            let c3 = bytes[2] >> 4;
+            assert_eq!(c1, (bits >> 2 & 1) << block);
+            assert_eq!(c2, (bits >> 1 & 1) << block);
+            assert_eq!(c3, (bits & 1) << block);
+            // The inverted copies
+            assert_eq!(bytes[0], !((c2 << 4) | c1));
+            assert_eq!(bytes[1] & 0x0F, !c3 & 0x0F);
+        }
+    }
+}
+
+#[test]
+fn every_combination_round_trips() {
+    for combination in 0..8u16.pow(4) {
+        let bits = |n: u16| condition((combination >> (3 * n) & 0b111) as u8);
+        let conditions = AccessConditions {
+            blocks: [bits(0), bits(1), bits(2)],
+            trailer: bits(3),
+        };
+        assert_eq!(
+            AccessConditions::decode(conditions.encode()),
+            Ok(conditions)
+        );
+    }
+}
+
+#[test]
+fn corrupted_bits_are_refused() {
+    let bytes = AccessConditions::TRANSPORT.encode();
+    // Flipping any single bit, inverted or not, breaks the pairing
+    for byte in 0..3 {
+        for bit in 0..8 {
+            let mut corrupted = bytes;
+            corrupted[byte] ^= 1 << bit;
+            assert_eq!(
+                AccessConditions::decode(corrupted),
+                Err(AccessError::InvalidAccessBits),
+                "byte {byte} bit {bit}"
+            );
+        }
+    }
+    assert_eq!(
+        AccessConditions::decode([0x00, 0x00, 0x00]),
+        Err(AccessError::InvalidAccessBits)
+    );
+
+    let mut block = [0xFF; 16];
+    block[6..9].copy_from_slice(&[0xFF, 0x07, 0x81]);
+    assert_eq!(
+        SectorTrailer::parse(&block),
+        Err(AccessError::InvalidAccessBits)
+    );
+}
+
+#[test]
+fn permanent_locks_are_refused() {
+    for (bits, key_a_write, _, access_bits_write, _, key_b_write) in TRAILER {
+        let trailer = SectorTrailer {
+            key_a: [0xFF; 6],
+            access: AccessConditions {
+                blocks: [condition(0); 3],
+                trailer: condition(bits),
+            },
+            user_byte: 0x69,
+            key_b: [0xFF; 6],
+        };
+        let expected = match (access_bits_write, key_a_write, key_b_write) {
+            (Never, Never, Never) => Err(AccessError::PermanentLock),
+            _ => Ok(()),
+        };
+        assert_eq!(
+            trailer.to_block().map(|_| ()),
+            expected,
+            "trailer condition {bits:03b}"
+        );
+    }
+}
+
+#[test]
+fn only_sectors_frozen_for_good_are_refused() {
+    let frozen: Vec<u8> = (0..8).filter(|&bits| condition(bits).is_frozen()).collect();
+    assert_eq!(frozen, [0b010, 0b110, 0b111]);
+
+    // Access bits fixed for good, but key A can still change both keys
+    let trailer = SectorTrailer {
+        key_a: *b"Rusted",
+        access: AccessConditions {
+            blocks: [condition(0); 3],
+            trailer: condition(0b000),
+        },
+        user_byte: 0x69,
+        key_b: *b"Ferris",
+    };
+    assert!(trailer.to_block().is_ok());
+}
+
+#[test]
+fn trailer_blocks_round_trip() {
+    let trailer = SectorTrailer {
+        key_a: [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5],
+        access: AccessConditions {
+            blocks: [condition(0b000), condition(0b100), condition(0b110)],
+            trailer: condition(0b011),
+        },
+        user_byte: 0x69,
+        key_b: [0xB0, 0xB1, 0xB2, 0xB3, 0xB4, 0xB5],
+    };
+    let block = trailer.to_block().unwrap();
+    assert_eq!(block[..6], trailer.key_a);
+    assert_eq!(block[9], 0x69);
+    assert_eq!(block[10..], trailer.key_b);
+    assert_eq!(SectorTrailer::parse(&block), Ok(trailer));
+}
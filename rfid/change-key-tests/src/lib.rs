@@ -0,0 +1,12 @@
+//! Host tests for the sector trailer code of change-key.
+//!
+//! `access.rs` only uses `core`, so it is pulled in from the firmware with
+//! `#[path]` and run here with `cargo test`. ndef-tag and restore-card
+//! include the same file.
+
+// The firmware module under test, at the path it has in its crate
+#[cfg(test)]
+#[path = "../../change-key/src/access.rs"]
+mod access;
+#[cfg(test)]
+mod access_tests;
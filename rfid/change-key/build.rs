@@ -23,5 +23,29 @@ fn main() {
     f.write_all(rp235x_riscv_x).unwrap();
     println!("cargo:rerun-if-changed=rp235x_riscv.x");
 
+    // The master key of the per-card keys comes from the environment, so it
+    // never has to be in the source tree
+    println!("cargo:rerun-if-env-changed=MASTER_KEY");
+    if std::env::var_os("CARGO_FEATURE_DIVERSIFY").is_some() {
+        let hex = std::env::var("MASTER_KEY")
+            .expect("the diversify feature needs the master key in MASTER_KEY");
+        let key = parse_key(&hex).expect("MASTER_KEY must be 32 hex digits");
+        let mut f = File::create(out.join("master_key.rs")).unwrap();
+        writeln!(f, "const MASTER_KEY: [u8; 16] = {:?};", key).unwrap();
+    }
+
     println!("cargo:rerun-if-changed=build.rs");
 }
+
+/// A 16 byte key written as 32 hex digits
+fn parse_key(hex: &str) -> Option<[u8; 16]> {
+    let hex = hex.trim();
+    if hex.len() != 32 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0; 16];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
@@ -0,0 +1,251 @@
+//! MIFARE Classic sector trailers and their access bits.
+//!
+//! The last block of every sector is its trailer:
+//!
+//! ```text
+//! | key A (6) | access bits (3) | user byte (1) | key B (6) |
+//! ```
+//!
+//! Each of the four blocks of the sector gets an access condition made of
+//! three bits, C1 C2 C3. The card stores every bit twice, once inverted, and
+//! a sector whose access bits don't match their inverted copy is locked for
+//! good. So the access bytes are only ever built from [`AccessConditions`]
+//! here, never typed in by hand.
+//!
+//! ```text
+//! byte 6: !C2_3 !C2_2 !C2_1 !C2_0  !C1_3 !C1_2 !C1_1 !C1_0
+//! byte 7:  C1_3  C1_2  C1_1  C1_0  !C3_3 !C3_2 !C3_1 !C3_0
+//! byte 8:  C3_3  C3_2  C3_1  C3_0   C2_3  C2_2  C2_1  C2_0
+//! ```
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub enum AccessError {
+    /// The access bits don't match their inverted copy
+    InvalidAccessBits,
+    /// Neither the access bits nor the keys could ever be changed again after
+    /// writing the trailer
+    PermanentLock,
+}
+
+/// Which key allows an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub enum Access {
+    Never,
+    KeyA,
+    KeyB,
+    KeyAOrB,
+}
+
+/// What can be done with a data block
+///
+/// When key B can be read (trailer conditions 000, 010 and 001) it can't be
+/// used to authenticate, so only the key A part of these applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub struct DataPermissions {
+    pub read: Access,
+    pub write: Access,
+    pub increment: Access,
+    /// Also covers transfer and restore
+    pub decrement: Access,
+}
+
+/// What can be done with the sector trailer itself
+///
+/// Key A can never be read back, the card returns zeros in its place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub struct TrailerPermissions {
+    pub key_a_write: Access,
+    pub access_bits_read: Access,
+    pub access_bits_write: Access,
+    pub key_b_read: Access,
+    pub key_b_write: Access,
+}
+
+/// Access condition of one block, the bits C1 C2 C3 as a number from 0 to 7
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub struct Condition(u8);
+
+impl Condition {
+    pub const fn new(c1: bool, c2: bool, c3: bool) -> Self {
+        Condition(((c1 as u8) << 2) | ((c2 as u8) << 1) | c3 as u8)
+    }
+
+    pub fn c1(&self) -> bool {
+        self.0 & 0b100 != 0
+    }
+
+    pub fn c2(&self) -> bool {
+        self.0 & 0b010 != 0
+    }
+
+    pub fn c3(&self) -> bool {
+        self.0 & 0b001 != 0
+    }
+
+    /// The permissions this condition gives a data block
+    pub fn data_permissions(&self) -> DataPermissions {
+        use Access::*;
+
+        let (read, write, increment, decrement) = match self.0 {
+            0b000 => (KeyAOrB, KeyAOrB, KeyAOrB, KeyAOrB),
+            0b010 => (KeyAOrB, Never, Never, Never),
+            0b100 => (KeyAOrB, KeyB, Never, Never),
+            0b110 => (KeyAOrB, KeyB, KeyB, KeyAOrB),
+            0b001 => (KeyAOrB, Never, Never, KeyAOrB),
+            0b011 => (KeyB, KeyB, Never, Never),
+            0b101 => (KeyB, Never, Never, Never),
+            _ => (Never, Never, Never, Never),
+        };
+        DataPermissions {
+            read,
+            write,
+            increment,
+            decrement,
+        }
+    }
+
+    /// Whether this condition on a trailer keeps its access bits and both
+    /// keys as they are for good
+    pub fn is_frozen(&self) -> bool {
+        let permissions = self.trailer_permissions();
+        permissions.access_bits_write == Access::Never
+            && permissions.key_a_write == Access::Never
+            && permissions.key_b_write == Access::Never
+    }
+
+    /// The permissions this condition gives a sector trailer
+    pub fn trailer_permissions(&self) -> TrailerPermissions {
+        use Access::*;
+
+        let (key_a_write, access_bits_read, access_bits_write, key_b_read, key_b_write) =
+            match self.0 {
+                0b000 => (KeyA, KeyA, Never, KeyA, KeyA),
+                0b010 => (Never, KeyA, Never, KeyA, Never),
+                0b100 => (KeyB, KeyAOrB, Never, Never, KeyB),
+                0b110 => (Never, KeyAOrB, Never, Never, Never),
+                0b001 => (KeyA, KeyA, KeyA, KeyA, KeyA),
+                0b011 => (KeyB, KeyAOrB, KeyB, Never, KeyB),
+                0b101 => (Never, KeyAOrB, KeyB, Never, Never),
+                _ => (Never, KeyAOrB, Never, Never, Never),
+            };
+        TrailerPermissions {
+            key_a_write,
+            access_bits_read,
+            access_bits_write,
+            key_b_read,
+            key_b_write,
+        }
+    }
+}
+
+/// Access conditions of a whole sector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub struct AccessConditions {
+    /// Conditions of the three data blocks, in block order
+    pub blocks: [Condition; 3],
+    /// Condition of the sector trailer
+    pub trailer: Condition,
+}
+
+impl AccessConditions {
+    /// How cards come from the factory: key A can do everything, access bits `FF 07 80`
+    pub const TRANSPORT: AccessConditions = AccessConditions {
+        blocks: [Condition::new(false, false, false); 3],
+        trailer: Condition::new(false, false, true),
+    };
+
+    /// Decode the three access bytes (bytes 6 to 8 of the trailer)
+    pub fn decode(bytes: [u8; 3]) -> Result<Self, AccessError> {
+        let c1 = bytes[1] >> 4;
+        let c2 = bytes[2] & 0x0F;
+        let c3 = bytes[2] >> 4;
+
+        let inverted_ok = !bytes[0] & 0x0F == c1 && !bytes[0] >> 4 == c2 && !bytes[1] & 0x0F == c3;
+        if !inverted_ok {
+            return Err(AccessError::InvalidAccessBits);
+        }
+
+        let condition = |block: u8| {
+            let bit = |bits: u8| bits & (1 << block) != 0;
+            Condition::new(bit(c1), bit(c2), bit(c3))
+        };
+        Ok(AccessConditions {
+            blocks: [condition(0), condition(1), condition(2)],
+            trailer: condition(3),
+        })
+    }
+
+    /// Encode into the three access bytes, with the inverted copies filled in
+    pub fn encode(&self) -> [u8; 3] {
+        let conditions = [self.blocks[0], self.blocks[1], self.blocks[2], self.trailer];
+
+        let (mut c1, mut c2, mut c3) = (0u8, 0u8, 0u8);
+        for (block, condition) in conditions.iter().enumerate() {
+            c1 |= (condition.c1() as u8) << block;
+            c2 |= (condition.c2() as u8) << block;
+            c3 |= (condition.c3() as u8) << block;
+        }
+
+        [
+            ((!c2 & 0x0F) << 4) | (!c1 & 0x0F),
+            (c1 << 4) | (!c3 & 0x0F),
+            (c3 << 4) | c2,
+        ]
+    }
+}
+
+/// The contents of a sector trailer block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub struct SectorTrailer {
+    pub key_a: [u8; 6],
+    pub access: AccessConditions,
+    /// Free for the application, not used by the card
+    pub user_byte: u8,
+    pub key_b: [u8; 6],
+}
+
+impl SectorTrailer {
+    /// Parse a trailer block read from the card
+    ///
+    /// The card hides key A, so `key_a` comes back as zeros.
+    pub fn parse(block: &[u8; 16]) -> Result<Self, AccessError> {
+        let access = AccessConditions::decode([block[6], block[7], block[8]])?;
+
+        let mut key_a = [0; 6];
+        key_a.copy_from_slice(&block[..6]);
+        let mut key_b = [0; 6];
+        key_b.copy_from_slice(&block[10..]);
+
+        Ok(SectorTrailer {
+            key_a,
+            access,
+            user_byte: block[9],
+            key_b,
+        })
+    }
+
+    /// The 16 bytes to write to the trailer block
+    ///
+    /// Refuses trailers that freeze the sector, with access bits and keys
+    /// that could never be written again, since that can't be undone.
+    /// Trailers that only freeze the access bits still let the keys change.
+    pub fn to_block(self) -> Result<[u8; 16], AccessError> {
+        if self.access.trailer.is_frozen() {
+            return Err(AccessError::PermanentLock);
+        }
+
+        let mut block = [0; 16];
+        block[..6].copy_from_slice(&self.key_a);
+        block[6..9].copy_from_slice(&self.access.encode());
+        block[9] = self.user_byte;
+        block[10..].copy_from_slice(&self.key_b);
+        Ok(block)
+    }
+}
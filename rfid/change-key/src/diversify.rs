@@ -0,0 +1,49 @@
+//! Keys of their own for every card, derived from a master key and the UID.
+//!
+//! With the same keys on every card, a key read off one of them opens them
+//! all. Here each key is the start of an AES-128 CMAC (RFC 4493) of the UID
+//! under the master key, with a different first byte for key A and key B,
+//! the way NXP's AN10922 diversifies keys. It isn't byte for byte the same
+//! as AN10922 though, cards keyed by other tools won't open with these.
+//!
+//! The reader only needs the master key and the UID it just selected to get
+//! the keys back, nothing has to be stored per card.
+//!
+//! Nothing here needs the firmware, so reader-sim checks it on the host.
+
+use aes::Aes128;
+use cmac::{Cmac, Mac};
+
+/// First byte of the CMAC input, so key A and key B differ
+const KEY_A: u8 = 0x01;
+const KEY_B: u8 = 0x02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardKeys {
+    pub key_a: [u8; 6],
+    pub key_b: [u8; 6],
+}
+
+/// The keys of the card with this UID
+pub fn derive(master: &[u8; 16], uid: &[u8]) -> CardKeys {
+    CardKeys {
+        key_a: derive_key(master, KEY_A, uid),
+        key_b: derive_key(master, KEY_B, uid),
+    }
+}
+
+fn derive_key(master: &[u8; 16], key_type: u8, uid: &[u8]) -> [u8; 6] {
+    let mac = cmac(master, &[&[key_type], uid]);
+    let mut key = [0; 6];
+    key.copy_from_slice(&mac[..6]);
+    key
+}
+
+/// AES-128 CMAC of the parts one after the other
+pub fn cmac(key: &[u8; 16], parts: &[&[u8]]) -> [u8; 16] {
+    let mut mac = <Cmac<Aes128> as Mac>::new(key.into());
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
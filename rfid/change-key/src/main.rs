@@ -1,6 +1,20 @@
 #![no_std]
 #![no_main]
 
+mod access;
+#[cfg(feature = "diversify")]
+mod diversify;
+// Shared with memory-dump-usb and write-data, not everything is needed here
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+#[path = "../../memory-dump-usb/src/mifare.rs"]
+mod mifare;
+#[path = "../../write-data/src/transaction.rs"]
+mod transaction;
+
 use embassy_executor::Spawner;
 use embassy_rp::block::ImageDef;
 use embassy_time::Timer;
@@ -25,32 +39,119 @@ use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
 
 // to prepare buffer with data before logging
 use core::fmt::Write;
-use heapless::String;
+use heapless::{String, Vec};
+
+use crate::access::{AccessConditions, AccessError, SectorTrailer};
+use crate::error::Error;
+use crate::transaction::Transaction;
 
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
 pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
 
-fn write_block<E, COMM>(
+// The master key of the per-card keys, from the MASTER_KEY environment
+// variable at build time
+#[cfg(feature = "diversify")]
+include!(concat!(env!("OUT_DIR"), "/master_key.rs"));
+
+/// The key cards come with
+const FACTORY_KEY: [u8; 6] = [0xFF; 6];
+
+const TRAILER: SectorTrailer = SectorTrailer {
+    key_a: *b"Rusted",
+    // Access bits FF 07 80: key A can read and write everything
+    access: AccessConditions::TRANSPORT,
+    user_byte: 0x69,
+    key_b: *b"Ferris",
+};
+
+/// The trailer to write to the card with this UID
+///
+/// Every card gets the keys in `TRAILER`, unless the firmware is built with
+/// the `diversify` feature.
+#[cfg(not(feature = "diversify"))]
+fn card_trailer(_uid: &[u8]) -> SectorTrailer {
+    TRAILER
+}
+
+/// The trailer to write to the card with this UID
+///
+/// The keys are derived again on every tap, so the same UID always gets the
+/// same keys back.
+#[cfg(feature = "diversify")]
+fn card_trailer(uid: &[u8]) -> SectorTrailer {
+    let keys = diversify::derive(&MASTER_KEY, uid);
+    SectorTrailer {
+        key_a: keys.key_a,
+        key_b: keys.key_b,
+        ..TRAILER
+    }
+}
+
+/// Find the key A that opens the sector now
+///
+/// A card that was tapped before already has the key this firmware writes,
+/// a new one still has the factory key.
+fn find_key<E, COMM>(
     uid: &mfrc522::Uid,
     sector: u8,
-    rel_block: u8,
-    data: [u8; 16],
-    key: &[u8; 6],
+    trailer: &SectorTrailer,
     rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
+) -> Result<[u8; 6], Error>
 where
     COMM: mfrc522::comm::Interface<Error = E>,
 {
-    let block_offset = sector * 4;
-    let abs_block = block_offset + rel_block;
+    for key in [trailer.key_a, FACTORY_KEY] {
+        if rfid.mf_authenticate(uid, sector * 4, &key).is_ok() {
+            return Ok(key);
+        }
+        // A wrong key halts the card
+        mifare::reselect(uid, rfid)?;
+    }
+    Err(Error::NoKey { sector })
+}
 
-    rfid.mf_authenticate(uid, block_offset, key)
-        .map_err(|_| "Auth failed")?;
+/// Check a sector trailer so a bad one can't lock the sector, and get the
+/// transaction that writes it
+fn write_trailer(sector: u8, trailer: &SectorTrailer, key: [u8; 6]) -> Result<Transaction, Error> {
+    let data = trailer.to_block().map_err(|e| {
+        Error::InvalidData(match e {
+            AccessError::InvalidAccessBits => "Invalid access bits",
+            AccessError::PermanentLock => "Trailer would lock the sector for good",
+        })
+    })?;
 
-    rfid.mf_write(abs_block, data).map_err(|_| "Write failed")?;
+    Ok(Transaction::new(sector, key).write(3, data))
+}
+
+/// Read the sector trailer and print what each block of the sector allows
+fn print_access<E, COMM>(
+    uid: &mfrc522::Uid,
+    sector: u8,
+    key: &[u8; 6],
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let trailer_block = sector * 4 + 3;
+    rfid.mf_authenticate(uid, sector * 4, key)
+        .map_err(|e| Error::Auth {
+            sector,
+            cause: e.into(),
+        })?;
+    let data = mifare::read(rfid, trailer_block).map_err(|cause| Error::Read {
+        block: trailer_block,
+        cause,
+    })?;
 
+    let trailer =
+        SectorTrailer::parse(&data).map_err(|_| Error::InvalidData("Invalid access bits"))?;
+    for (block, condition) in trailer.access.blocks.iter().enumerate() {
+        defmt::println!("Block {}: {}", block, condition.data_permissions());
+    }
+    defmt::println!("Trailer: {}", trailer.access.trailer.trailer_permissions());
     Ok(())
 }
 
@@ -59,16 +160,22 @@ fn read_sector<E, COMM>(
     sector: u8,
     key: &[u8; 6],
     rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
+) -> Result<(), Error>
 where
     COMM: mfrc522::comm::Interface<Error = E>,
 {
     let block_offset = sector * 4;
     rfid.mf_authenticate(uid, block_offset, key)
-        .map_err(|_| "Auth failed")?;
+        .map_err(|e| Error::Auth {
+            sector,
+            cause: e.into(),
+        })?;
 
     for abs_block in block_offset..block_offset + 4 {
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
+        let data = mifare::read(rfid, abs_block).map_err(|cause| Error::Read {
+            block: abs_block,
+            cause,
+        })?;
         print_hex(&data);
     }
     Ok(())
@@ -92,7 +199,7 @@ async fn main(_spawner: Spawner) {
     let mosi = p.PIN_3;
 
     let mut config = spi::Config::default();
-    config.frequency = 1000_000;
+    config.frequency = 1_000_000;
 
     let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
 
@@ -108,40 +215,67 @@ async fn main(_spawner: Spawner) {
     defmt::info!("Initialized RFID reader");
 
     let target_sector = 1;
-    let rel_block = 3;
-    const DATA: [u8; 16] = [
-        0x52, 0x75, 0x73, 0x74, 0x65, 0x64, // Key A: "Rusted"
-        0xFF, 0x07, 0x80, 0x69, // Access bits and trailer byte
-        0x46, 0x65, 0x72, 0x72, 0x69, 0x73, // Key B: "Ferris"
-    ];
-    let current_key = &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
-    let new_key: &[u8; 6] = &DATA[..6].try_into().expect("have enough data");
+    // A trailer write the card was pulled away from, and the UID of that card
+    let mut pending: Option<(Vec<u8, 10>, Transaction)> = None;
 
     loop {
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                defmt::println!("\r\n----Before Write----\r\n");
-                if let Err(e) = read_sector(&uid, target_sector, current_key, &mut rfid) {
-                    defmt::error!("Error reading sector: {:?}", e);
+        if let Ok(atqa) = rfid.reqa()
+            && let Ok(uid) = rfid.select(&atqa)
+        {
+            let trailer = card_trailer(uid.as_bytes());
+            let current_key = match find_key(&uid, target_sector, &trailer, &mut rfid) {
+                Ok(key) => key,
+                Err(e) => {
+                    defmt::error!("{}", e);
+                    let _ = rfid.hlta();
+                    let _ = rfid.stop_crypto1();
+                    Timer::after_millis(500).await;
+                    continue;
                 }
-                Timer::after_millis(200).await;
+            };
 
-                if let Err(e) =
-                    write_block(&uid, target_sector, rel_block, DATA, current_key, &mut rfid)
-                {
-                    defmt::error!("Error writing block: {:?}", e);
-                }
-                Timer::after_millis(200).await;
+            defmt::println!("\r\n----Before Write----\r\n");
+            if let Err(e) = read_sector(&uid, target_sector, &current_key, &mut rfid) {
+                defmt::error!("Error reading sector: {}", e);
+            }
+            if let Err(e) = print_access(&uid, target_sector, &current_key, &mut rfid) {
+                defmt::error!("Error reading access bits: {}", e);
+            }
+            Timer::after_millis(200).await;
 
-                defmt::println!("\r\n----After Write----\r\n");
-                if let Err(e) = read_sector(&uid, target_sector, new_key, &mut rfid) {
-                    defmt::error!("Error reading sector: {:?}", e);
+            let transaction = match pending.take() {
+                Some((card, transaction)) if card == uid.as_bytes() => {
+                    defmt::println!("Finishing the write the card left in the middle of");
+                    Ok(transaction)
+                }
+                other => {
+                    pending = other;
+                    write_trailer(target_sector, &trailer, current_key)
                 }
+            };
+            match transaction {
+                Ok(mut transaction) => match transaction.commit(&uid, &mut rfid) {
+                    Ok(()) => {}
+                    Err(Error::CardLost) => {
+                        defmt::error!("Card lost while writing, hold it on the reader again");
+                        let card =
+                            Vec::from_slice(uid.as_bytes()).expect("UID is at most 10 bytes");
+                        pending = Some((card, transaction));
+                    }
+                    Err(e) => defmt::error!("Error writing trailer: {}", e),
+                },
+                Err(e) => defmt::error!("Error writing trailer: {}", e),
+            }
+            Timer::after_millis(200).await;
 
-                let _ = rfid.hlta();
-                let _ = rfid.stop_crypto1();
-                Timer::after_millis(500).await;
+            defmt::println!("\r\n----After Write----\r\n");
+            if let Err(e) = read_sector(&uid, target_sector, &trailer.key_a, &mut rfid) {
+                defmt::error!("Error reading sector: {}", e);
             }
+
+            let _ = rfid.hlta();
+            let _ = rfid.stop_crypto1();
+            Timer::after_millis(500).await;
         }
 
         Timer::after_millis(200).await;
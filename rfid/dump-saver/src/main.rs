@@ -0,0 +1,460 @@
+//! Save the card dumps sent by memory-dump-usb to files.
+//!
+//! Every dump becomes two files named after the card's UID: `<uid>.mfd`,
+//! the raw card image that other MIFARE tools can open, and `<uid>.json`,
+//! the metadata record with ATQA, SAK and the key that opened each sector.
+//!
+//! With `--ndef` or `--values` it prints the NDEF message or the value
+//! blocks of a saved dump instead.
+
+// Shared with memory-dump-usb, which sends the dumps
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+// The encoder is only used by the firmware, and here by the tests
+#[path = "../../memory-dump-usb/src/frame.rs"]
+#[allow(dead_code)]
+mod frame;
+// Shared with the ndef-tag firmware, which also writes tags
+#[path = "../../ndef-tag/src/mad.rs"]
+#[allow(dead_code)]
+mod mad;
+#[path = "../../ndef-tag/src/ndef.rs"]
+#[allow(dead_code)]
+mod ndef;
+mod tag;
+// Shared with the value-block firmware, which also writes value blocks
+#[path = "../../value-block/src/value.rs"]
+#[allow(dead_code)]
+mod value;
+mod values;
+
+use std::io::{ErrorKind, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use frame::Decoder;
+
+const USAGE: &str = "\
+Usage: dump-saver <port> [options]
+       dump-saver --ndef <file.mfd>
+       dump-saver --values <file.mfd>
+
+Options:
+  -o <dir>         Directory to save dumps in (default: current directory)
+  --once           Exit after saving one dump
+  --ndef <file>    Print the NDEF message of a saved dump and exit
+  --values <file>  Print the value blocks of a saved dump and exit";
+
+/// Sectors of the largest card, a MIFARE Classic 4K
+const MAX_SECTORS: u8 = 40;
+
+struct Options {
+    port: String,
+    output: PathBuf,
+    once: bool,
+    ndef: Option<PathBuf>,
+    values: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut port = None;
+    let mut options = Options {
+        port: String::new(),
+        output: PathBuf::from("."),
+        once: false,
+        ndef: None,
+        values: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => options.output = args.next().ok_or("-o needs a value")?.into(),
+            "--once" => options.once = true,
+            "--ndef" => options.ndef = Some(args.next().ok_or("--ndef needs a value")?.into()),
+            "--values" => {
+                options.values = Some(args.next().ok_or("--values needs a value")?.into())
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+            _ if port.is_none() => port = Some(arg),
+            _ => return Err(format!("unexpected argument {arg}\n\n{USAGE}")),
+        }
+    }
+
+    match port {
+        Some(port) => options.port = port,
+        None if options.ndef.is_some() || options.values.is_some() => {}
+        None => return Err(USAGE.into()),
+    }
+    Ok(options)
+}
+
+/// A complete dump, put together from its frames
+#[derive(Debug, PartialEq)]
+struct Dump {
+    uid: String,
+    meta: String,
+    image: Vec<u8>,
+}
+
+/// Collects the frames of one dump
+#[derive(Default)]
+struct Assembler {
+    meta: Option<String>,
+    image: Vec<u8>,
+    sectors: Vec<bool>,
+}
+
+impl Assembler {
+    /// Take one frame, returning the dump once its end marker arrives
+    fn push(&mut self, kind: u8, payload: &[u8]) -> Result<Option<Dump>, String> {
+        match kind {
+            frame::KIND_META => {
+                *self = Assembler::default();
+                let meta = String::from_utf8(payload.to_vec())
+                    .map_err(|_| "metadata isn't valid UTF-8".to_string())?;
+                self.meta = Some(meta);
+            }
+            frame::KIND_SECTOR => {
+                let [sector, status, data @ ..] = payload else {
+                    return Err("sector frame too short".into());
+                };
+                if *sector >= MAX_SECTORS {
+                    return Err(format!("sector {sector} is past the end of a 4K card"));
+                }
+                let range = card_type::sector_range(*sector);
+                if data.len() != range.len() {
+                    return Err(format!("sector {sector} has {} bytes", data.len()));
+                }
+                if *status != frame::STATUS_OK {
+                    eprintln!("sector {sector} wasn't read, saving zeros");
+                }
+
+                let sector = *sector as usize;
+                if self.image.len() < range.end {
+                    self.image.resize(range.end, 0);
+                    self.sectors.resize(sector + 1, false);
+                }
+                self.image[range].copy_from_slice(data);
+                self.sectors[sector] = true;
+            }
+            frame::KIND_END => {
+                let meta = self.meta.take().ok_or("dump ended without metadata")?;
+                let count = payload.first().copied().unwrap_or(0) as usize;
+                let missing = (0..count).find(|&s| !self.sectors.get(s).copied().unwrap_or(false));
+                if let Some(sector) = missing {
+                    return Err(format!("dump ended without sector {sector}"));
+                }
+
+                let uid = json_string(&meta, "uid").ok_or("metadata has no uid")?;
+                self.image.resize(card_type::sector_offset(count as u8), 0);
+                let image = std::mem::take(&mut self.image);
+                *self = Assembler::default();
+                return Ok(Some(Dump { uid, meta, image }));
+            }
+            frame::KIND_ERROR => {
+                *self = Assembler::default();
+                return Err(format!("dump failed: {}", String::from_utf8_lossy(payload)));
+            }
+            _ => return Err(format!("unknown frame kind {kind:#04x}")),
+        }
+        Ok(None)
+    }
+}
+
+/// Find a string field of the metadata record, which has no escapes in it
+fn json_string(json: &str, field: &str) -> Option<String> {
+    let start = json.find(&format!("\"{field}\":\""))? + field.len() + 4;
+    let len = json[start..].find('"')?;
+    Some(json[start..start + len].to_string())
+}
+
+fn save(dump: &Dump, options: &Options) -> std::io::Result<()> {
+    let base = options.output.join(&dump.uid);
+    std::fs::write(base.with_extension("mfd"), &dump.image)?;
+    std::fs::write(base.with_extension("json"), format!("{}\n", dump.meta))?;
+    println!("Saved {}.mfd and {}.json", dump.uid, dump.uid);
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let mut port = serialport::new(&options.port, 115_200)
+        .timeout(Duration::from_secs(1))
+        .open()
+        .map_err(|e| format!("can't open {}: {e}", options.port))?;
+    // The board only talks once the host says it's there
+    port.write_data_terminal_ready(true)
+        .map_err(|e| format!("can't set DTR: {e}"))?;
+    println!("Waiting for a card on {}", options.port);
+
+    let mut decoder = Decoder::new();
+    let mut assembler = Assembler::default();
+    let mut buff = [0u8; 256];
+    loop {
+        let n = match port.read(&mut buff) {
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::TimedOut => continue,
+            Err(e) => return Err(format!("read failed: {e}")),
+        };
+
+        for &byte in &buff[..n] {
+            let (kind, payload) = match decoder.push(byte) {
+                None => continue,
+                Some(Ok(frame)) => frame,
+                Some(Err(e)) => {
+                    eprintln!("dropped a frame: {e:?}");
+                    continue;
+                }
+            };
+            match assembler.push(kind, payload) {
+                Ok(Some(dump)) => {
+                    save(&dump, options).map_err(|e| format!("can't save {}: {e}", dump.uid))?;
+                    if options.once {
+                        return Ok(());
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = match (&options.ndef, &options.values) {
+        (Some(path), _) => tag::print_ndef_file(path),
+        (None, Some(path)) => values::print_values_file(path),
+        (None, None) => run(&options),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame::{FrameError, encode};
+
+    fn encoded(kind: u8, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![0; payload.len() + frame::OVERHEAD];
+        let len = encode(kind, payload, &mut out).unwrap();
+        out.truncate(len);
+        out
+    }
+
+    fn decode_all(bytes: &[u8]) -> Vec<Result<(u8, Vec<u8>), FrameError>> {
+        let mut decoder = Decoder::new();
+        let mut frames = Vec::new();
+        for &byte in bytes {
+            if let Some(result) = decoder.push(byte) {
+                frames.push(result.map(|(kind, payload)| (kind, payload.to_vec())));
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn crc_matches_ccitt_false() {
+        assert_eq!(frame::crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn round_trip() {
+        let payload: Vec<u8> = (0..=255).collect();
+        let bytes = encoded(frame::KIND_SECTOR, &payload);
+        assert_eq!(bytes.len(), payload.len() + frame::OVERHEAD);
+        assert_eq!(decode_all(&bytes), vec![Ok((frame::KIND_SECTOR, payload))]);
+    }
+
+    #[test]
+    fn empty_and_largest_payloads() {
+        let largest = vec![0xA5; frame::MAX_PAYLOAD];
+        let mut bytes = encoded(frame::KIND_END, &[]);
+        bytes.extend(encoded(frame::KIND_META, &largest));
+        assert_eq!(
+            decode_all(&bytes),
+            vec![
+                Ok((frame::KIND_END, vec![])),
+                Ok((frame::KIND_META, largest))
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_rejects_what_doesnt_fit() {
+        let mut out = [0u8; 16];
+        assert_eq!(
+            encode(1, &[0; 10], &mut out),
+            Err(FrameError::BufferTooSmall)
+        );
+        let mut out = vec![0u8; 2 * frame::MAX_PAYLOAD];
+        let payload = vec![0; frame::MAX_PAYLOAD + 1];
+        assert_eq!(encode(1, &payload, &mut out), Err(FrameError::TooLong));
+    }
+
+    #[test]
+    fn skips_noise_between_frames() {
+        let mut bytes = b"boot log\r\n\xA5\xA5".to_vec();
+        bytes.extend(encoded(frame::KIND_META, b"{}"));
+        bytes.extend([0x00, 0xA5, 0x13]);
+        bytes.extend(encoded(frame::KIND_END, &[16]));
+        assert_eq!(
+            decode_all(&bytes),
+            vec![
+                Ok((frame::KIND_META, b"{}".to_vec())),
+                Ok((frame::KIND_END, vec![16]))
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupted_frame_is_dropped() {
+        let mut bad = encoded(frame::KIND_SECTOR, &[1, 2, 3]);
+        bad[6] ^= 0x01;
+        let mut bytes = bad;
+        bytes.extend(encoded(frame::KIND_END, &[1]));
+        assert_eq!(
+            decode_all(&bytes),
+            vec![Err(FrameError::BadCrc), Ok((frame::KIND_END, vec![1]))]
+        );
+    }
+
+    #[test]
+    fn impossible_length_resyncs() {
+        let mut bytes = vec![0xA5, 0x5A, frame::KIND_META, 0xFF, 0xFF];
+        bytes.extend(encoded(frame::KIND_END, &[2]));
+        assert_eq!(decode_all(&bytes), vec![Ok((frame::KIND_END, vec![2]))]);
+    }
+
+    fn sector(number: u8, status: u8, fill: u8) -> Vec<u8> {
+        let mut payload = vec![number, status];
+        payload.extend(vec![fill; card_type::sector_range(number).len()]);
+        payload
+    }
+
+    #[test]
+    fn assembles_a_dump() {
+        let meta = r#"{"uid":"a1b2c3d4","atqa":"0004","sak":"08","sectors":[]}"#;
+        let mut assembler = Assembler::default();
+        assert_eq!(assembler.push(frame::KIND_META, meta.as_bytes()), Ok(None));
+        // Sectors can arrive in any order
+        assert_eq!(
+            assembler.push(frame::KIND_SECTOR, &sector(1, 0, 0x11)),
+            Ok(None)
+        );
+        assert_eq!(
+            assembler.push(frame::KIND_SECTOR, &sector(0, 1, 0)),
+            Ok(None)
+        );
+
+        let dump = assembler.push(frame::KIND_END, &[2]).unwrap().unwrap();
+        assert_eq!(dump.uid, "a1b2c3d4");
+        assert_eq!(dump.meta, meta);
+        assert_eq!(dump.image.len(), 128);
+        assert!(dump.image[..64].iter().all(|&b| b == 0));
+        assert!(dump.image[64..].iter().all(|&b| b == 0x11));
+    }
+
+    #[test]
+    fn assembles_a_4k_dump() {
+        let mut assembler = Assembler::default();
+        assembler
+            .push(frame::KIND_META, br#"{"uid":"01020304"}"#)
+            .unwrap();
+        for number in 0..40 {
+            assembler
+                .push(frame::KIND_SECTOR, &sector(number, 0, number))
+                .unwrap();
+        }
+        let dump = assembler.push(frame::KIND_END, &[40]).unwrap().unwrap();
+        assert_eq!(dump.image.len(), 4096);
+        // Sector 31 is the last small one, the large ones start at block 128
+        assert_eq!(dump.image[31 * 64], 31);
+        assert_eq!(dump.image[2048 - 1], 31);
+        assert_eq!(dump.image[2048], 32);
+        assert_eq!(dump.image[2048 + 256], 33);
+        assert_eq!(dump.image[4095], 39);
+
+        // A small sector in place of a large one
+        let mut short = vec![32, 0];
+        short.extend([0; 64]);
+        assert!(assembler.push(frame::KIND_SECTOR, &short).is_err());
+        assert!(
+            assembler
+                .push(frame::KIND_SECTOR, &sector(40, 0, 0))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn card_types() {
+        use card_type::CardType;
+
+        let cases = [
+            ([0x04, 0x00], 0x08, CardType::MifareClassic1K),
+            ([0x02, 0x00], 0x18, CardType::MifareClassic4K),
+            ([0x04, 0x00], 0x09, CardType::MifareMini),
+            ([0x04, 0x00], 0x88, CardType::MifareClassic1K),
+            ([0x44, 0x00], 0x00, CardType::Ultralight),
+            ([0x44, 0x03], 0x20, CardType::Desfire),
+            ([0x04, 0x00], 0x20, CardType::Iso14443_4),
+            ([0x44, 0x00], 0x11, CardType::MifarePlus),
+            ([0x04, 0x00], 0x00, CardType::Unknown),
+        ];
+        for (atqa, sak, expected) in cases {
+            assert_eq!(CardType::identify(atqa, sak), expected, "SAK {sak:02x}");
+        }
+        assert_eq!(CardType::MifareClassic4K.sectors(), Some(40));
+        assert_eq!(CardType::Ultralight.sectors(), None);
+    }
+
+    #[test]
+    fn classic_layouts() {
+        use card_type::*;
+
+        assert_eq!(sector_offset(5), 320);
+        assert_eq!(sector_offset(16), 1024);
+        assert_eq!(sector_offset(40), 4096);
+        assert_eq!((first_block(31), trailer_block(31)), (124, 127));
+        assert_eq!((first_block(32), trailer_block(32)), (128, 143));
+        assert_eq!((first_block(39), trailer_block(39)), (240, 255));
+        assert_eq!(sector_range(32), 2048..2304);
+
+        let trailers: Vec<u8> = (0..=255).filter(|&b| is_trailer(b)).collect();
+        let expected: Vec<u8> = (0..40).map(trailer_block).collect();
+        assert_eq!(trailers, expected);
+    }
+
+    #[test]
+    fn incomplete_dump_is_refused() {
+        let mut assembler = Assembler::default();
+        assembler
+            .push(frame::KIND_META, br#"{"uid":"01"}"#)
+            .unwrap();
+        assembler
+            .push(frame::KIND_SECTOR, &sector(0, 0, 0))
+            .unwrap();
+        assert!(assembler.push(frame::KIND_END, &[2]).is_err());
+
+        let mut assembler = Assembler::default();
+        assembler
+            .push(frame::KIND_SECTOR, &sector(0, 0, 0))
+            .unwrap();
+        assert!(assembler.push(frame::KIND_END, &[1]).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+//! Read the NDEF message out of a saved card image.
+
+use std::path::Path;
+
+use crate::mad::{self, Mad};
+use crate::ndef::{self, Record};
+
+const BLOCK_SIZE: usize = 16;
+const SECTOR_SIZE: usize = 64;
+/// The three data blocks of a sector, without its trailer
+const SECTOR_DATA: usize = 48;
+
+fn block(image: &[u8], block: usize) -> Result<&[u8; BLOCK_SIZE], String> {
+    image
+        .get(block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE)
+        .and_then(|b| b.try_into().ok())
+        .ok_or(format!("image has no block {block}"))
+}
+
+/// The data blocks of every NDEF sector listed in the MAD, in order
+pub fn ndef_area(image: &[u8]) -> Result<Vec<u8>, String> {
+    let mad = Mad::parse(block(image, 1)?, block(image, 2)?)
+        .map_err(|_| "no MAD in the image, the MAD CRC doesn't match".to_string())?;
+
+    let mut area = Vec::new();
+    for sector in mad.sectors(mad::NDEF_AID) {
+        let start = sector as usize * SECTOR_SIZE;
+        let data = image
+            .get(start..start + SECTOR_DATA)
+            .ok_or(format!("image has no sector {sector}"))?;
+        area.extend_from_slice(data);
+    }
+    Ok(area)
+}
+
+fn describe(record: &Record) -> String {
+    match record {
+        Record::Uri { prefix, rest } => format!("URI: {}{rest}", Record::uri_prefix(*prefix)),
+        Record::Text { language, text } => format!("Text ({language}): {text}"),
+        Record::Mime { mime_type, data } => format!("MIME {mime_type}: {} bytes", data.len()),
+        Record::Other { tnf, payload, .. } => {
+            format!("Record with TNF {tnf}: {} bytes", payload.len())
+        }
+    }
+}
+
+/// Describe every record of the image's NDEF message, one line each
+pub fn describe_ndef(image: &[u8]) -> Result<Vec<String>, String> {
+    let area = ndef_area(image)?;
+    let message = ndef::find_message(&area).map_err(|e| format!("{e:?}"))?;
+    ndef::records(message)
+        .map(|record| record.map(|r| describe(&r)).map_err(|e| format!("{e:?}")))
+        .collect()
+}
+
+pub fn print_ndef_file(path: &Path) -> Result<(), String> {
+    let image = std::fs::read(path).map_err(|e| format!("can't read {}: {e}", path.display()))?;
+    for line in describe_ndef(&image)? {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 1K card laid out the way NXP TagWriter formats it, holding a URI and a Text record
+    const TAG: &[u8] = include_bytes!("../fixtures/ndef-tag.mfd");
+
+    #[test]
+    fn reads_captured_tag() {
+        assert_eq!(
+            describe_ndef(TAG).unwrap(),
+            vec![
+                "URI: https://github.com/ImplFerris",
+                "Text (en): Hello from a tag"
+            ]
+        );
+    }
+
+    #[test]
+    fn blank_card_has_no_mad() {
+        assert!(ndef_area(&[0; 1024]).is_err());
+    }
+
+    #[test]
+    fn short_image() {
+        assert_eq!(
+            describe_ndef(&[0; 40]),
+            Err("image has no block 2".to_string())
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Find the value blocks in a saved card image.
+
+use std::path::Path;
+
+use crate::card_type::{self, BLOCK_SIZE};
+use crate::value::ValueBlock;
+
+/// Describe every data block that holds a valid value block, one line each
+pub fn describe_values(image: &[u8]) -> Vec<String> {
+    image
+        .chunks_exact(BLOCK_SIZE)
+        .enumerate()
+        // Block 0 and the trailers never hold values
+        .filter(|(block, _)| *block != 0 && !card_type::is_trailer(*block as u8))
+        .filter_map(|(block, data)| {
+            let value = ValueBlock::parse(data.try_into().unwrap()).ok()?;
+            Some(format!(
+                "Block {block:2}: value {} (address {})",
+                value.value, value.address
+            ))
+        })
+        .collect()
+}
+
+pub fn print_values_file(path: &Path) -> Result<(), String> {
+    let image = std::fs::read(path).map_err(|e| format!("can't read {}: {e}", path.display()))?;
+    let lines = describe_values(&image);
+    if lines.is_empty() {
+        println!("No value blocks");
+    }
+    for line in lines {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_values_in_an_image() {
+        let mut image = vec![0u8; 1024];
+        image[64..80].copy_from_slice(&ValueBlock::new(7, 4).to_block());
+        image[80..96].copy_from_slice(&ValueBlock::new(8, 4).to_block());
+        // A trailer that happens to look like a value block is skipped
+        image[112..128].copy_from_slice(&ValueBlock::new(1, 1).to_block());
+        assert_eq!(
+            describe_values(&image),
+            vec![
+                "Block  4: value 7 (address 4)",
+                "Block  5: value 8 (address 4)"
+            ]
+        );
+    }
+
+    #[test]
+    fn no_values_on_a_blank_card() {
+        assert!(describe_values(&[0; 1024]).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+//! Wake up and select a card, keeping the ATQA and SAK it answers with.
+//!
+//! The mfrc522 driver selects cards too, but keeps the ATQA and SAK bytes to
+//! itself. This does the same ISO 14443-3 steps through its public
+//! `transceive`, so we can tell what kind of card answered.
+
+use mfrc522::{GenericUid, Mfrc522, Uid};
+
+use crate::crc::crc_a;
+
+/// Request (REQA) and wake up (WUPA) commands, sent as 7 bit short frames
+const REQA: u8 = 0x26;
+const WUPA: u8 = 0x52;
+/// Anticollision and select commands for cascade levels 1 to 3
+const SELECT: [u8; 3] = [0x93, 0x95, 0x97];
+/// Cascade tag that starts the first part of a UID longer than 4 bytes
+const CASCADE_TAG: u8 = 0x88;
+/// Set in the SAK when the UID continues in the next cascade level
+const SAK_UID_INCOMPLETE: u8 = 0x04;
+
+/// A selected card and how it answered
+pub struct Card {
+    pub uid: Uid,
+    pub atqa: [u8; 2],
+    pub sak: u8,
+}
+
+/// Find a card in the field and select it
+///
+/// With `wake` set, cards that were halted answer too. Only one card may be
+/// in the field, there is no anticollision loop.
+pub fn activate<E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    wake: bool,
+) -> Result<Card, &'static str>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let request = if wake { WUPA } else { REQA };
+    let rx = rfid
+        .transceive::<2>(&[request], 7, 0)
+        .map_err(|_| "No card")?;
+    if rx.valid_bytes != 2 || rx.valid_bits != 0 {
+        return Err("Invalid ATQA");
+    }
+    let atqa = [rx.buffer[0], rx.buffer[1]];
+
+    let mut uid = [0u8; 10];
+    let mut len = 0;
+    for select in SELECT {
+        // Anticollision: the card answers with 4 UID bytes and their XOR (BCC)
+        let rx = rfid
+            .transceive::<5>(&[select, 0x20], 0, 0)
+            .map_err(|_| "Anticollision failed")?;
+        let part = rx.buffer;
+        if rx.valid_bytes != 5 || part[0] ^ part[1] ^ part[2] ^ part[3] != part[4] {
+            return Err("Invalid UID");
+        }
+
+        let mut tx = [0u8; 9];
+        tx[0] = select;
+        tx[1] = 0x70;
+        tx[2..7].copy_from_slice(&part);
+        let crc = crc_a(&tx[..7]);
+        tx[7..].copy_from_slice(&crc);
+
+        let rx = rfid
+            .transceive::<3>(&tx, 0, 0)
+            .map_err(|_| "Select failed")?;
+        if rx.valid_bytes != 3 || crc_a(&rx.buffer[..1]) != rx.buffer[1..] {
+            return Err("Invalid SAK");
+        }
+        let sak = rx.buffer[0];
+
+        if sak & SAK_UID_INCOMPLETE != 0 && part[0] == CASCADE_TAG {
+            uid[len..len + 3].copy_from_slice(&part[1..4]);
+            len += 3;
+            continue;
+        }
+
+        uid[len..len + 4].copy_from_slice(&part[..4]);
+        len += 4;
+
+        let uid = match len {
+            4 => Uid::Single(GenericUid::new(uid[..4].try_into().unwrap(), sak)),
+            7 => Uid::Double(GenericUid::new(uid[..7].try_into().unwrap(), sak)),
+            _ => Uid::Triple(GenericUid::new(uid, sak)),
+        };
+        return Ok(Card { uid, atqa, sak });
+    }
+
+    Err("UID too long")
+}
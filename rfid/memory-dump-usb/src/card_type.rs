@@ -0,0 +1,137 @@
+//! Tell what kind of card answered from its ATQA and SAK.
+//!
+//! The SAK says which protocol the card speaks, and for MIFARE Classic how
+//! much memory it has. Cards with the same SAK are told apart by the ATQA.
+//! The values are the ones in NXP's AN10833, "MIFARE type identification
+//! procedure".
+//!
+//! MIFARE Classic memory is split into sectors. The first 32 sectors have 4
+//! blocks, the last one being the sector trailer. A 4K card has 8 more
+//! sectors of 16 blocks each, so past block 127 sector and block numbers no
+//! longer line up by four.
+//!
+//! This file is shared with the host side dump-saver tool, so it only uses
+//! `core`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardType {
+    MifareMini,
+    MifareClassic1K,
+    MifareClassic4K,
+    MifarePlus,
+    /// MIFARE Ultralight and NTAG, which answer the same way
+    Ultralight,
+    Desfire,
+    /// Any other card that speaks ISO 14443-4
+    Iso14443_4,
+    Unknown,
+}
+
+/// Set in the SAK of cards that speak ISO 14443-4
+const SAK_ISO14443_4: u8 = 0x20;
+/// ATQA of MIFARE Ultralight and NTAG
+const ATQA_ULTRALIGHT: u16 = 0x0044;
+/// ATQA of MIFARE DESFire
+const ATQA_DESFIRE: u16 = 0x0344;
+
+/// Blocks in the small sectors, and in the sectors past `SMALL_SECTORS`
+const SMALL_SECTOR_BLOCKS: usize = 4;
+const LARGE_SECTOR_BLOCKS: usize = 16;
+const SMALL_SECTORS: usize = 32;
+pub const BLOCK_SIZE: usize = 16;
+pub const MAX_SECTOR_SIZE: usize = LARGE_SECTOR_BLOCKS * BLOCK_SIZE;
+
+/// The ATQA the way datasheets write it, `0x0044` for an Ultralight
+///
+/// `atqa` is as received, low byte first.
+pub fn atqa_value(atqa: [u8; 2]) -> u16 {
+    u16::from_le_bytes(atqa)
+}
+
+impl CardType {
+    /// `atqa` as received, low byte first
+    pub fn identify(atqa: [u8; 2], sak: u8) -> Self {
+        let atqa = atqa_value(atqa);
+        match sak {
+            0x09 => CardType::MifareMini,
+            // 0x88 is an Infineon 1K, 0x28 and 0x38 are SmartMX chips emulating one
+            0x08 | 0x88 | 0x28 => CardType::MifareClassic1K,
+            0x18 | 0x38 => CardType::MifareClassic4K,
+            0x10 | 0x11 => CardType::MifarePlus,
+            0x00 if atqa == ATQA_ULTRALIGHT => CardType::Ultralight,
+            0x20 if atqa == ATQA_DESFIRE => CardType::Desfire,
+            sak if sak & SAK_ISO14443_4 != 0 => CardType::Iso14443_4,
+            _ => CardType::Unknown,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CardType::MifareMini => "MIFARE Mini",
+            CardType::MifareClassic1K => "MIFARE Classic 1K",
+            CardType::MifareClassic4K => "MIFARE Classic 4K",
+            CardType::MifarePlus => "MIFARE Plus",
+            CardType::Ultralight => "MIFARE Ultralight or NTAG",
+            CardType::Desfire => "MIFARE DESFire",
+            CardType::Iso14443_4 => "ISO 14443-4 card",
+            CardType::Unknown => "Unknown card",
+        }
+    }
+
+    /// Number of sectors, for the cards with MIFARE Classic memory
+    pub fn sectors(self) -> Option<u8> {
+        match self {
+            CardType::MifareMini => Some(5),
+            CardType::MifareClassic1K => Some(16),
+            CardType::MifareClassic4K => Some(40),
+            _ => None,
+        }
+    }
+}
+
+/// Blocks in a sector, counting its trailer
+pub const fn blocks_in_sector(sector: u8) -> u8 {
+    if (sector as usize) < SMALL_SECTORS {
+        SMALL_SECTOR_BLOCKS as u8
+    } else {
+        LARGE_SECTOR_BLOCKS as u8
+    }
+}
+
+/// Blocks before a sector, which is also the number of blocks in the sectors before it
+const fn blocks_before(sector: u8) -> usize {
+    let sector = sector as usize;
+    if sector <= SMALL_SECTORS {
+        sector * SMALL_SECTOR_BLOCKS
+    } else {
+        SMALL_SECTORS * SMALL_SECTOR_BLOCKS + (sector - SMALL_SECTORS) * LARGE_SECTOR_BLOCKS
+    }
+}
+
+pub const fn first_block(sector: u8) -> u8 {
+    blocks_before(sector) as u8
+}
+
+pub const fn trailer_block(sector: u8) -> u8 {
+    first_block(sector) + (blocks_in_sector(sector) - 1)
+}
+
+pub const fn is_trailer(block: u8) -> bool {
+    let block = block as usize;
+    if block < SMALL_SECTORS * SMALL_SECTOR_BLOCKS {
+        block % SMALL_SECTOR_BLOCKS == SMALL_SECTOR_BLOCKS - 1
+    } else {
+        block % LARGE_SECTOR_BLOCKS == LARGE_SECTOR_BLOCKS - 1
+    }
+}
+
+/// Where a sector starts in a card image, which also is the size of an
+/// image of `sector` sectors
+pub const fn sector_offset(sector: u8) -> usize {
+    blocks_before(sector) * BLOCK_SIZE
+}
+
+/// The bytes of a sector in a card image
+pub const fn sector_range(sector: u8) -> core::ops::Range<usize> {
+    sector_offset(sector)..sector_offset(sector) + blocks_in_sector(sector) as usize * BLOCK_SIZE
+}
@@ -0,0 +1,16 @@
+//! The CRC_A that ends the frames between the reader and the card.
+//!
+//! The MFRC522 can add and check it on its own, but the frames here go
+//! through `transceive` with the chip's CRC turned off, so the firmware
+//! computes it.
+
+/// CRC_A from ISO 14443-3, sent low byte first
+pub fn crc_a(data: &[u8]) -> [u8; 2] {
+    let mut crc: u16 = 0x6363;
+    for &byte in data {
+        let mut b = byte ^ crc as u8;
+        b ^= b << 4;
+        crc = (crc >> 8) ^ ((b as u16) << 8) ^ ((b as u16) << 3) ^ ((b as u16) >> 4);
+    }
+    crc.to_le_bytes()
+}
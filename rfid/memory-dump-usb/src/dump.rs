@@ -0,0 +1,99 @@
+//! Read a whole MIFARE Classic card into memory.
+//!
+//! Every sector is opened with the first key of the dictionary that works,
+//! see `keys`. The reader-sim crate runs this on the host against a
+//! simulated reader and card, so it logs nothing and only uses `core`.
+
+use mfrc522::Mfrc522;
+
+use crate::card::Card;
+use crate::card_type::{self, CardType};
+use crate::error::Error;
+use crate::frame;
+use crate::keys::{self, SectorKey};
+
+/// Sectors of the largest card, a MIFARE Classic 4K
+pub const MAX_SECTORS: usize = 40;
+pub const IMAGE_SIZE: usize = card_type::sector_offset(MAX_SECTORS as u8);
+
+/// Everything read from a card, ready to be sent to the host
+pub struct Dump {
+    pub card: Card,
+    pub card_type: CardType,
+    pub sectors: u8,
+    pub keys: [Option<SectorKey>; MAX_SECTORS],
+    pub status: [u8; MAX_SECTORS],
+    /// Same layout as a `.mfd` file, unread sectors are zeros
+    pub image: [u8; IMAGE_SIZE],
+}
+
+impl Dump {
+    pub fn sector(&self, sector: u8) -> &[u8] {
+        &self.image[card_type::sector_range(sector)]
+    }
+}
+
+/// Read the blocks of a sector, which must already be opened with `key`
+pub fn read_sector<E, COMM>(
+    uid: &mfrc522::Uid,
+    sector: u8,
+    key: &SectorKey,
+    out: &mut [u8],
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let blocks = card_type::first_block(sector)..=card_type::trailer_block(sector);
+    for (abs_block, block) in blocks.zip(out.chunks_exact_mut(card_type::BLOCK_SIZE)) {
+        let data = keys::read_block(uid, sector, abs_block, key, rfid)?;
+        block.copy_from_slice(&data);
+    }
+    Ok(())
+}
+
+/// Read every sector that a key from the dictionary opens
+///
+/// The card type sets how many sectors there are. Locked sectors and
+/// unreadable blocks are marked in the dump and skipped, only losing the
+/// card stops it.
+pub fn dump_memory<E, COMM>(
+    card: Card,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<Dump, Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let card_type = CardType::identify(card.atqa, card.sak);
+    let sectors = card_type.sectors().ok_or(Error::UnsupportedCard)?;
+
+    let mut dump = Dump {
+        card,
+        card_type,
+        sectors,
+        keys: [None; MAX_SECTORS],
+        status: [frame::STATUS_LOCKED; MAX_SECTORS],
+        image: [0; IMAGE_SIZE],
+    };
+
+    for sector in 0..sectors {
+        let out = &mut dump.image[card_type::sector_range(sector)];
+        let key = keys::authenticate_sector(&dump.card.uid, sector, rfid)?;
+        let sector_index = sector as usize;
+        dump.keys[sector_index] = key;
+        let Some(key) = key else {
+            continue;
+        };
+
+        dump.status[sector_index] = match read_sector(&dump.card.uid, sector, &key, out, rfid) {
+            Ok(()) => frame::STATUS_OK,
+            Err(Error::CardLost) => return Err(Error::CardLost),
+            // The card refused a block, or kept failing on it
+            Err(_) => {
+                out.fill(0);
+                frame::STATUS_READ_FAILED
+            }
+        };
+    }
+    Ok(dump)
+}
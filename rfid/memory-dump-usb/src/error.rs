@@ -0,0 +1,196 @@
+//! Errors of card operations, keeping the driver error and the block or
+//! sector it happened on.
+//!
+//! The rfid projects that read and write blocks share this file. reader-sim
+//! runs it on the host too, so only the `defmt::Format` impls need more than
+//! `core`, and they are left out of host builds.
+
+use core::fmt;
+
+/// An `mfrc522::Error`, without the SPI error it may carry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriverError {
+    Bcc,
+    BufferOverflow,
+    Collision,
+    Crc,
+    IncompleteFrame,
+    Overheating,
+    Parity,
+    Protocol,
+    Timeout,
+    Wr,
+    Nak,
+    NoRoom,
+    Proprietary,
+    /// The SPI bus failed
+    Comm,
+}
+
+impl<E> From<mfrc522::Error<E>> for DriverError {
+    fn from(error: mfrc522::Error<E>) -> Self {
+        match error {
+            mfrc522::Error::Bcc => DriverError::Bcc,
+            mfrc522::Error::BufferOverflow => DriverError::BufferOverflow,
+            mfrc522::Error::Collision => DriverError::Collision,
+            mfrc522::Error::Crc => DriverError::Crc,
+            mfrc522::Error::IncompleteFrame => DriverError::IncompleteFrame,
+            mfrc522::Error::Overheating => DriverError::Overheating,
+            mfrc522::Error::Parity => DriverError::Parity,
+            mfrc522::Error::Protocol => DriverError::Protocol,
+            mfrc522::Error::Timeout => DriverError::Timeout,
+            mfrc522::Error::Wr => DriverError::Wr,
+            mfrc522::Error::Nak => DriverError::Nak,
+            mfrc522::Error::NoRoom => DriverError::NoRoom,
+            mfrc522::Error::Proprietary => DriverError::Proprietary,
+            mfrc522::Error::Comm(_) => DriverError::Comm,
+        }
+    }
+}
+
+impl DriverError {
+    /// Errors a card at the edge of the field or a noisy frame cause, which
+    /// selecting the card again and retrying can get past
+    ///
+    /// A NAK is the card refusing the command, retrying won't change that.
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            DriverError::Bcc
+                | DriverError::BufferOverflow
+                | DriverError::Collision
+                | DriverError::Crc
+                | DriverError::IncompleteFrame
+                | DriverError::Parity
+                | DriverError::Protocol
+                | DriverError::Timeout
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DriverError::Bcc => "wrong BCC",
+            DriverError::BufferOverflow => "FIFO overflow",
+            DriverError::Collision => "collision",
+            DriverError::Crc => "wrong CRC",
+            DriverError::IncompleteFrame => "incomplete frame",
+            DriverError::Overheating => "overheating",
+            DriverError::Parity => "parity error",
+            DriverError::Protocol => "protocol error",
+            DriverError::Timeout => "timeout",
+            DriverError::Wr => "FIFO written at the wrong time",
+            DriverError::Nak => "NAK",
+            DriverError::NoRoom => "buffer too small",
+            DriverError::Proprietary => "proprietary frame",
+            DriverError::Comm => "SPI error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The card didn't answer when selected again, or another one did
+    CardLost,
+    /// Not a MIFARE Classic card
+    UnsupportedCard,
+    /// None of the keys tried opens the sector
+    NoKey {
+        sector: u8,
+    },
+    /// The key doesn't open the sector, the card only stops answering
+    Auth {
+        sector: u8,
+        cause: DriverError,
+    },
+    Read {
+        block: u8,
+        cause: DriverError,
+    },
+    Write {
+        block: u8,
+        cause: DriverError,
+    },
+    /// The block doesn't read back as written, or the new key A of a
+    /// trailer doesn't open the sector
+    VerifyMismatch {
+        block: u8,
+    },
+    /// What the card holds, or what was going to be written to it, doesn't
+    /// make sense, like a missing MAD or a trailer that would lock a sector
+    InvalidData(&'static str),
+}
+
+impl Error {
+    /// Whether selecting the card again and retrying may get past the error
+    pub fn is_transient(self) -> bool {
+        match self {
+            Error::Auth { cause, .. } | Error::Read { cause, .. } | Error::Write { cause, .. } => {
+                cause.is_transient()
+            }
+            // A frame can be cut short without the card noticing
+            Error::VerifyMismatch { .. } => true,
+            Error::CardLost
+            | Error::UnsupportedCard
+            | Error::NoKey { .. }
+            | Error::InvalidData(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CardLost => write!(f, "Card lost"),
+            Error::UnsupportedCard => write!(f, "Not a MIFARE Classic card"),
+            Error::NoKey { sector } => write!(f, "No key opens sector {}", sector),
+            Error::Auth { sector, cause } => {
+                write!(f, "Auth failed on sector {}: {}", sector, cause)
+            }
+            Error::Read { block, cause } => write!(f, "Read failed on block {}: {}", block, cause),
+            Error::Write { block, cause } => {
+                write!(f, "Write failed on block {}: {}", block, cause)
+            }
+            Error::VerifyMismatch { block } => {
+                write!(f, "Block {} doesn't read back as written", block)
+            }
+            Error::InvalidData(reason) => f.write_str(reason),
+        }
+    }
+}
+
+#[cfg(target_os = "none")]
+impl defmt::Format for DriverError {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=str}", self.as_str())
+    }
+}
+
+#[cfg(target_os = "none")]
+impl defmt::Format for Error {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Error::CardLost => defmt::write!(f, "Card lost"),
+            Error::UnsupportedCard => defmt::write!(f, "Not a MIFARE Classic card"),
+            Error::NoKey { sector } => defmt::write!(f, "No key opens sector {}", sector),
+            Error::Auth { sector, cause } => {
+                defmt::write!(f, "Auth failed on sector {}: {}", sector, cause)
+            }
+            Error::Read { block, cause } => {
+                defmt::write!(f, "Read failed on block {}: {}", block, cause)
+            }
+            Error::Write { block, cause } => {
+                defmt::write!(f, "Write failed on block {}: {}", block, cause)
+            }
+            Error::VerifyMismatch { block } => {
+                defmt::write!(f, "Block {} doesn't read back as written", block)
+            }
+            Error::InvalidData(reason) => defmt::write!(f, "{=str}", reason),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Framing for sending card dumps over the USB serial port.
+//!
+//! Every frame looks like this, numbers are little endian:
+//!
+//! ```text
+//! | 0xA5 0x5A | kind (1) | length (2) | payload (length) | CRC-16 (2) |
+//! ```
+//!
+//! The CRC (CRC-16/CCITT-FALSE) covers the kind, length and payload. A
+//! reader that loses its place skips ahead to the next `A5 5A` and drops
+//! frames whose CRC doesn't match, so stray bytes can't end up in a dump.
+//!
+//! A dump is sent as one `META` frame, one `SECTOR` frame per sector and an
+//! `END` frame. The sectors put together in order are a card image in the
+//! same layout as a `.mfd` file: 320 bytes for a Mini, 1024 for a 1K and 4096
+//! for a 4K card.
+//!
+//! This file is shared with the host side dump-saver tool, so it only uses
+//! `core`.
+
+/// Start of every frame
+pub const MAGIC: [u8; 2] = [0xA5, 0x5A];
+
+/// JSON text describing the card: UID, ATQA, SAK and which key opened each sector
+pub const KIND_META: u8 = 0x01;
+/// Sector number, sector status (see `STATUS_*`), then the sector's 4 or 16 blocks
+pub const KIND_SECTOR: u8 = 0x02;
+/// The dump is complete, the payload is the number of sectors sent
+pub const KIND_END: u8 = 0x03;
+/// The dump failed part way, the payload is the reason as text
+pub const KIND_ERROR: u8 = 0x04;
+
+/// Every block of the sector was read
+pub const STATUS_OK: u8 = 0;
+/// No key opened the sector, the blocks are zeros
+pub const STATUS_LOCKED: u8 = 1;
+/// The sector was opened but a block couldn't be read, it is left as zeros
+pub const STATUS_READ_FAILED: u8 = 2;
+
+/// Largest payload a frame can carry
+pub const MAX_PAYLOAD: usize = 4096;
+/// Bytes a frame adds around its payload
+pub const OVERHEAD: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// The payload is longer than `MAX_PAYLOAD`
+    TooLong,
+    /// The output buffer can't hold the frame
+    BufferTooSmall,
+    /// The frame arrived but its CRC doesn't match
+    BadCrc,
+}
+
+/// CRC-16/CCITT-FALSE
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(0xFFFF, data)
+}
+
+/// Write a frame into `out`, returning how many bytes it took
+pub fn encode(kind: u8, payload: &[u8], out: &mut [u8]) -> Result<usize, FrameError> {
+    if payload.len() > MAX_PAYLOAD {
+        return Err(FrameError::TooLong);
+    }
+    let total = payload.len() + OVERHEAD;
+    if out.len() < total {
+        return Err(FrameError::BufferTooSmall);
+    }
+
+    out[..2].copy_from_slice(&MAGIC);
+    out[2] = kind;
+    out[3..5].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+    out[5..5 + payload.len()].copy_from_slice(payload);
+    let crc = crc16(&out[2..5 + payload.len()]);
+    out[5 + payload.len()..total].copy_from_slice(&crc.to_le_bytes());
+
+    Ok(total)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DecodeState {
+    Magic(usize),
+    Header(usize),
+    Payload,
+    Crc(usize),
+}
+
+/// Pull frames out of a byte stream, one byte at a time
+pub struct Decoder {
+    state: DecodeState,
+    header: [u8; 3],
+    payload: [u8; MAX_PAYLOAD],
+    len: usize,
+    received: usize,
+    crc: [u8; 2],
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder {
+    pub const fn new() -> Self {
+        Self {
+            state: DecodeState::Magic(0),
+            header: [0; 3],
+            payload: [0; MAX_PAYLOAD],
+            len: 0,
+            received: 0,
+            crc: [0; 2],
+        }
+    }
+
+    /// Feed one byte, getting back `(kind, payload)` when it completes a frame
+    pub fn push(&mut self, byte: u8) -> Option<Result<(u8, &[u8]), FrameError>> {
+        match self.state {
+            DecodeState::Magic(i) => {
+                self.state = if byte == MAGIC[i] {
+                    if i + 1 == MAGIC.len() {
+                        DecodeState::Header(0)
+                    } else {
+                        DecodeState::Magic(i + 1)
+                    }
+                } else if byte == MAGIC[0] {
+                    DecodeState::Magic(1)
+                } else {
+                    DecodeState::Magic(0)
+                };
+            }
+            DecodeState::Header(i) => {
+                self.header[i] = byte;
+                if i + 1 < self.header.len() {
+                    self.state = DecodeState::Header(i + 1);
+                } else {
+                    self.len = u16::from_le_bytes([self.header[1], self.header[2]]) as usize;
+                    self.received = 0;
+                    self.state = if self.len > MAX_PAYLOAD {
+                        // Can't be a real frame, look for the next one
+                        DecodeState::Magic(0)
+                    } else if self.len == 0 {
+                        DecodeState::Crc(0)
+                    } else {
+                        DecodeState::Payload
+                    };
+                }
+            }
+            DecodeState::Payload => {
+                self.payload[self.received] = byte;
+                self.received += 1;
+                if self.received == self.len {
+                    self.state = DecodeState::Crc(0);
+                }
+            }
+            DecodeState::Crc(i) => {
+                self.crc[i] = byte;
+                if i == 0 {
+                    self.state = DecodeState::Crc(1);
+                    return None;
+                }
+
+                self.state = DecodeState::Magic(0);
+                // Continue the CRC over the payload without copying it next to the header
+                let crc = crc16_update(crc16(&self.header), &self.payload[..self.len]);
+                if crc != u16::from_le_bytes(self.crc) {
+                    return Some(Err(FrameError::BadCrc));
+                }
+                return Some(Ok((self.header[0], &self.payload[..self.len])));
+            }
+        }
+        None
+    }
+}
+
+/// Carry on a CRC-16/CCITT-FALSE computation with more data
+fn crc16_update(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
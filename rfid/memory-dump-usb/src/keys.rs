@@ -0,0 +1,176 @@
+//! Find the key that opens each sector by trying a dictionary of keys.
+//!
+//! Cards leave the factory with every key set to `FF FF FF FF FF FF`, but
+//! cards that have been formatted for MAD or NDEF, or by us with change-key,
+//! use other well known keys. Every key is tried as key A and as key B.
+//!
+//! The mfrc522 crate can only authenticate with key A. [`KeySelect`] sits
+//! between the driver and the SPI bus and turns its key A authentication
+//! command into the key B one while [`KeyType::B`] is being tried.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+
+use crate::card_type;
+use crate::error::{DriverError, Error};
+use crate::mifare;
+
+/// Keys tried on every sector, in this order
+pub const KEYS: [[u8; 6]; 7] = [
+    // Factory default
+    [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+    // MAD sector key A
+    [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5],
+    // MAD sector key B
+    [0xB0, 0xB1, 0xB2, 0xB3, 0xB4, 0xB5],
+    // NDEF sector key A
+    [0xD3, 0xF7, 0xD3, 0xF7, 0xD3, 0xF7],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // Our own keys, as written by change-key
+    *b"Rusted",
+    *b"Ferris",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    A,
+    B,
+}
+
+/// The key that opened a sector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectorKey {
+    pub key_type: KeyType,
+    pub key: [u8; 6],
+}
+
+/// Set while authenticating with key B
+static USE_KEY_B: AtomicBool = AtomicBool::new(false);
+
+/// MFRC522 register address byte for writing to the FIFO
+const FIFO_DATA_WRITE: u8 = 0x09 << 1;
+/// MIFARE authentication commands, followed by block, key and UID
+const MF_AUTH_KEY_A: u8 = 0x60;
+const MF_AUTH_KEY_B: u8 = 0x61;
+const MF_AUTH_LEN: usize = 12;
+
+/// SPI device wrapper that lets the mfrc522 driver authenticate with key B
+pub struct KeySelect<SPI> {
+    spi: SPI,
+}
+
+impl<SPI> KeySelect<SPI> {
+    pub fn new(spi: SPI) -> Self {
+        Self { spi }
+    }
+}
+
+impl<SPI: ErrorType> ErrorType for KeySelect<SPI> {
+    type Error = SPI::Error;
+}
+
+impl<SPI: SpiDevice> SpiDevice for KeySelect<SPI> {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+        // The driver loads the authentication command into the FIFO in one go
+        if USE_KEY_B.load(Ordering::Relaxed)
+            && let [Operation::Write(address), Operation::Write(data)] = operations
+            && *address == [FIFO_DATA_WRITE]
+            && data.len() == MF_AUTH_LEN
+            && data[0] == MF_AUTH_KEY_A
+        {
+            let mut command = [0u8; MF_AUTH_LEN];
+            command.copy_from_slice(data);
+            command[0] = MF_AUTH_KEY_B;
+            return self.spi.transaction(&mut [
+                Operation::Write(&[FIFO_DATA_WRITE]),
+                Operation::Write(&command),
+            ]);
+        }
+
+        self.spi.transaction(operations)
+    }
+}
+
+/// Authenticate a block's sector with one key
+///
+/// A failed authentication halts the card, call [`mifare::reselect`] before talking
+/// to it again.
+pub fn authenticate_with<E, COMM>(
+    uid: &mfrc522::Uid,
+    block: u8,
+    key: &SectorKey,
+    rfid: &mut mfrc522::Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    USE_KEY_B.store(key.key_type == KeyType::B, Ordering::Relaxed);
+    let result = rfid.mf_authenticate(uid, block, &key.key);
+    USE_KEY_B.store(false, Ordering::Relaxed);
+    result.map_err(DriverError::from)
+}
+
+/// Try every key of the dictionary on a sector until one works
+///
+/// A failed authentication halts the card, so it is woken up and selected
+/// again before the next try. Returns `Ok(None)` if no key opens the sector
+/// and an error if the card is gone.
+pub fn authenticate_sector<E, COMM>(
+    uid: &mfrc522::Uid,
+    sector: u8,
+    rfid: &mut mfrc522::Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<Option<SectorKey>, Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let block = card_type::first_block(sector);
+    for key in KEYS {
+        for key_type in [KeyType::A, KeyType::B] {
+            let candidate = SectorKey { key_type, key };
+            if authenticate_with(uid, block, &candidate, rfid).is_ok() {
+                return Ok(Some(candidate));
+            }
+
+            mifare::reselect(uid, rfid)?;
+        }
+    }
+    Ok(None)
+}
+
+/// Times a block is read again after a transient error
+const READ_RETRIES: usize = 2;
+
+/// Read a block of `sector`, which is already opened with `key`
+///
+/// Any error sends the card back to idle, so it is selected again. After a
+/// transient error the sector is opened again and the read retried, a long
+/// dump shouldn't stop on one bad frame.
+pub fn read_block<E, COMM>(
+    uid: &mfrc522::Uid,
+    sector: u8,
+    block: u8,
+    key: &SectorKey,
+    rfid: &mut mfrc522::Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<[u8; 16], Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut retries = 0;
+    loop {
+        let cause = match mifare::read(rfid, block) {
+            Ok(data) => return Ok(data),
+            Err(cause) => cause,
+        };
+        mifare::reselect(uid, rfid)?;
+        if !cause.is_transient() || retries == READ_RETRIES {
+            return Err(Error::Read { block, cause });
+        }
+        retries += 1;
+
+        if let Err(cause) = authenticate_with(uid, block, key, rfid) {
+            mifare::reselect(uid, rfid)?;
+            return Err(Error::Auth { sector, cause });
+        }
+    }
+}
@@ -1,6 +1,24 @@
 #![no_std]
 #![no_main]
 
+mod card;
+// Shared with the dump-saver tool, not everything is needed here
+#[allow(dead_code)]
+mod card_type;
+mod crc;
+mod dump;
+// Shared by the rfid projects that read and write blocks, not every error
+// happens here
+#[allow(dead_code)]
+mod error;
+// Shared with the dump-saver tool, the decoder is only used there
+#[allow(dead_code)]
+mod frame;
+mod keys;
+// Shared with the rfid projects that write blocks, the dump only reads
+#[allow(dead_code)]
+mod mifare;
+
 use embassy_executor::Spawner;
 use embassy_rp as hal;
 use embassy_rp::block::ImageDef;
@@ -9,8 +27,15 @@ use embassy_time::Timer;
 //Panic Handler
 use panic_probe as _;
 
+// Defmt Logging, over the debug probe
+use defmt_rtt as _;
+
 // For USB
 use embassy_rp::{peripherals::USB, usb};
+use embassy_usb::UsbDevice;
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embassy_usb::driver::EndpointError;
+use static_cell::StaticCell;
 
 // For SPI
 use embassy_rp::spi;
@@ -24,10 +49,13 @@ use embassy_rp::gpio::{Level, Output};
 // Driver for the MFRC522
 use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
 
-// to prepare buffer with data before writing into USB serial
+// to prepare the metadata record and error messages before sending them
 use core::fmt::Write;
 use heapless::String;
 
+use crate::dump::{Dump, dump_memory};
+use crate::keys::{KeySelect, KeyType};
+
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
@@ -37,89 +65,170 @@ embassy_rp::bind_interrupts!(struct Irqs {
     USBCTRL_IRQ => usb::InterruptHandler<USB>;
 });
 
+type MyUsbDriver = usb::Driver<'static, USB>;
+type MyUsbDevice = UsbDevice<'static, MyUsbDriver>;
+
 #[embassy_executor::task]
-async fn logger_task(usb: embassy_rp::Peri<'static, embassy_rp::peripherals::USB>) {
-    let driver = embassy_rp::usb::Driver::new(usb, Irqs);
+async fn usb_task(mut usb: MyUsbDevice) -> ! {
+    usb.run().await
+}
 
-    embassy_usb_logger::run!(8192, log::LevelFilter::Info, driver);
+/// Log the card type and the sectors that couldn't be read
+fn log_dump(dump: &Dump) {
+    defmt::info!("{=str}", dump.card_type.name());
+    for sector in 0..dump.sectors {
+        match dump.status[sector as usize] {
+            frame::STATUS_OK => {}
+            frame::STATUS_LOCKED => {
+                defmt::info!("Sector {}: no key in the dictionary opens it", sector)
+            }
+            _ => defmt::warn!("Sector {}: read failed", sector),
+        }
+    }
 }
 
-fn get_block_type(sector: u8, rel_block: u8) -> &'static str {
-    match rel_block {
-        0 if sector == 0 => "MFD",
-        3 => "TRAILER",
-        _ => "DATA",
+fn write_hex<const N: usize>(out: &mut String<N>, data: &[u8]) -> core::fmt::Result {
+    for &d in data.iter() {
+        write!(out, "{:02x}", d)?;
     }
+    Ok(())
 }
 
-fn read_sector<E, COMM>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
-where
-    COMM: mfrc522::comm::Interface<Error = E>,
-{
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
-    let mut buff: String<64> = String::new();
-
-    let block_offset = sector * 4;
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
-
-    for abs_block in block_offset..block_offset + 4 {
-        let rel_block = abs_block - block_offset;
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
-
-        // Printing the block data
-        for &d in data.iter() {
-            write!(buff, "{:02x} ", d).expect("failed to write byte into buffer");
+/// Describe the card and how each sector was opened, as JSON
+///
+/// ```json
+/// {"uid":"a1b2c3d4","atqa":"0004","sak":"08","type":"MIFARE Classic 1K",
+///  "sectors":[{"auth":"A","key":"ffffffffffff","status":"ok"},{"auth":null,"key":null,"status":"locked"},...]}
+/// ```
+fn write_meta<const N: usize>(dump: &Dump, out: &mut String<N>) -> core::fmt::Result {
+    out.push_str("{\"uid\":\"").map_err(|_| core::fmt::Error)?;
+    write_hex(out, dump.card.uid.as_bytes())?;
+    out.push_str("\",\"atqa\":\"")
+        .map_err(|_| core::fmt::Error)?;
+    write!(
+        out,
+        "{:04x}\",\"sak\":\"{:02x}\",\"type\":\"{}\",\"sectors\":[",
+        card_type::atqa_value(dump.card.atqa),
+        dump.card.sak,
+        dump.card_type.name()
+    )?;
+
+    let sectors = dump.sectors as usize;
+    for (sector, (key, status)) in dump.keys[..sectors].iter().zip(dump.status).enumerate() {
+        if sector > 0 {
+            out.push(',').map_err(|_| core::fmt::Error)?;
         }
+        match key {
+            Some(key) => {
+                let key_type = match key.key_type {
+                    KeyType::A => "A",
+                    KeyType::B => "B",
+                };
+                write!(out, "{{\"auth\":\"{}\",\"key\":\"", key_type)?;
+                write_hex(out, &key.key)?;
+                out.push('"').map_err(|_| core::fmt::Error)?;
+            }
+            None => out
+                .push_str("{\"auth\":null,\"key\":null")
+                .map_err(|_| core::fmt::Error)?,
+        }
+        let status = match status {
+            frame::STATUS_OK => "ok",
+            frame::STATUS_LOCKED => "locked",
+            _ => "read_failed",
+        };
+        write!(out, ",\"status\":\"{}\"}}", status)?;
+    }
+    out.push_str("]}").map_err(|_| core::fmt::Error)
+}
 
-        // Printing block type
-        let block_type = get_block_type(sector, rel_block);
-
-        log::info!(
-            "BLOCK {} (REL: {}) | {} | {}",
-            abs_block,
-            rel_block,
-            buff,
-            block_type
-        );
-
-        buff.clear();
+/// Send one frame, split into USB packets
+async fn send_frame(
+    class: &mut CdcAcmClass<'static, MyUsbDriver>,
+    kind: u8,
+    payload: &[u8],
+) -> Result<(), EndpointError> {
+    let mut buff = [0u8; frame::MAX_PAYLOAD + frame::OVERHEAD];
+    let len = frame::encode(kind, payload, &mut buff).map_err(|_| EndpointError::BufferOverflow)?;
+
+    let max_packet = class.max_packet_size() as usize;
+    for packet in buff[..len].chunks(max_packet) {
+        class.write_packet(packet).await?;
+    }
+    // A full last packet would leave the host waiting for more
+    if len.is_multiple_of(max_packet) {
+        class.write_packet(&[]).await?;
     }
-    log::info!("");
     Ok(())
 }
 
-fn dump_memory<E, COMM>(
-    uid: &mfrc522::Uid,
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
-where
-    COMM: mfrc522::comm::Interface<Error = E>,
-{
-    let mut buff: String<64> = String::new();
-    for sector in 0..16 {
-        // Printing the Sector number
-        write!(buff, "-----------SECTOR {}-----------", sector)
-            .expect("failed to write into heapless buff");
-        log::info!("{}", buff);
-        buff.clear();
-
-        read_sector(uid, sector, rfid)?;
+/// Send the metadata record, every sector and the end marker
+async fn send_dump(
+    class: &mut CdcAcmClass<'static, MyUsbDriver>,
+    dump: &Dump,
+) -> Result<(), EndpointError> {
+    let mut meta: String<{ frame::MAX_PAYLOAD }> = String::new();
+    if write_meta(dump, &mut meta).is_err() {
+        defmt::error!("Metadata record doesn't fit in a frame");
+        return send_frame(class, frame::KIND_ERROR, b"Metadata too long").await;
     }
-    Ok(())
+    send_frame(class, frame::KIND_META, meta.as_bytes()).await?;
+
+    let mut payload = [0u8; 2 + card_type::MAX_SECTOR_SIZE];
+    for sector in 0..dump.sectors {
+        let data = dump.sector(sector);
+        payload[0] = sector;
+        payload[1] = dump.status[sector as usize];
+        payload[2..2 + data.len()].copy_from_slice(data);
+        send_frame(class, frame::KIND_SECTOR, &payload[..2 + data.len()]).await?;
+    }
+
+    send_frame(class, frame::KIND_END, &[dump.sectors]).await
 }
 
 #[embassy_executor::main]
 async fn main(spawner: Spawner) {
     let p = embassy_rp::init(Default::default());
 
-    spawner.must_spawn(logger_task(p.USB));
-    Timer::after_secs(3).await;
+    let driver = usb::Driver::new(p.USB, Irqs);
+    // Create embassy-usb Config
+    let config = {
+        let mut config = embassy_usb::Config::new(0xc0de, 0xcafe);
+        config.manufacturer = Some("implRust");
+        config.product = Some("Ferris Card Dumper");
+        config.serial_number = Some("12345678");
+        config.max_power = 100;
+        config.max_packet_size_0 = 64;
+        config
+    };
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut builder = {
+        static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+        static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+        static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+
+        embassy_usb::Builder::new(
+            driver,
+            config,
+            CONFIG_DESCRIPTOR.init([0; 256]),
+            BOS_DESCRIPTOR.init([0; 256]),
+            &mut [], // no msos descriptors
+            CONTROL_BUF.init([0; 64]),
+        )
+    };
+
+    // Create classes on the builder.
+    let mut class = {
+        static STATE: StaticCell<State> = StaticCell::new();
+        let state = STATE.init(State::new());
+        CdcAcmClass::new(&mut builder, state, 64)
+    };
+
+    // Build the builder.
+    let usb = builder.build();
+    spawner.must_spawn(usb_task(usb));
 
     let miso = p.PIN_0;
     let cs_pin = Output::new(p.PIN_1, Level::High);
@@ -127,42 +236,49 @@ async fn main(spawner: Spawner) {
     let mosi = p.PIN_3;
 
     let mut config = spi::Config::default();
-    config.frequency = 1000_000;
+    config.frequency = 1_000_000;
 
     let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
     let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
-    let itf = SpiInterface::new(spi);
+    let itf = SpiInterface::new(KeySelect::new(spi));
 
-    log::info!("Initializing MFRC522...");
+    Timer::after_millis(100).await;
 
-    let mut rfid = match Mfrc522::new(itf).init() {
-        Ok(rfid) => {
-            log::info!("MFRC522 initialized successfully");
-            rfid
-        }
-        Err(e) => {
-            log::error!("Failed to initialize MFRC522: {:?}", e);
-            loop {
-                Timer::after_secs(1).await;
-            }
-        }
-    };
-
-    log::info!("Waiting for RFID");
+    let mut rfid = Mfrc522::new(itf)
+        .init()
+        .expect("failed to initialize the RFID reader");
+    defmt::info!("Initialized RFID reader");
 
     loop {
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                if let Err(e) = dump_memory(&uid, &mut rfid) {
-                    log::error!("Error reading sector: {:?}", e);
-                }
+        class.wait_connection().await;
+        defmt::info!("Host connected, waiting for RFID");
+
+        loop {
+            if let Ok(card) = card::activate(&mut rfid, false) {
+                let sent = match dump_memory(card, &mut rfid) {
+                    Ok(dump) => {
+                        log_dump(&dump);
+                        send_dump(&mut class, &dump).await
+                    }
+                    Err(e) => {
+                        defmt::error!("Error dumping memory: {}", e);
+                        let mut message: String<64> = String::new();
+                        let _ = write!(message, "{}", e);
+                        send_frame(&mut class, frame::KIND_ERROR, message.as_bytes()).await
+                    }
+                };
                 let _ = rfid.hlta();
                 let _ = rfid.stop_crypto1();
+
+                if sent.is_err() {
+                    break;
+                }
                 Timer::after_millis(500).await;
             }
-        }
 
-        Timer::after_millis(200).await;
+            Timer::after_millis(200).await;
+        }
+        defmt::info!("Host disconnected");
     }
 }
 
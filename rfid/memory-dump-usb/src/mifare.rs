@@ -0,0 +1,95 @@
+//! MIFARE Classic READ and WRITE that tell a NAK from a bad frame.
+//!
+//! The driver's `mf_read` and `mf_write` don't: a refused READ fails their
+//! CRC check, and the NAK to a WRITE is taken for an ACK, so only the data
+//! frame after it fails, with a timeout. These send the same frames through
+//! `transceive` and report `DriverError::Nak` when the card refuses.
+//!
+//! The sector has to be authenticated first, the MFRC522 then encrypts and
+//! decrypts the frames on its own. Any error sends the card back to idle,
+//! [`reselect`] brings it back.
+
+use mfrc522::{FifoData, Mfrc522};
+
+use crate::crc::crc_a;
+use crate::error::{DriverError, Error};
+
+const MF_READ: u8 = 0x30;
+const MF_WRITE: u8 = 0xA0;
+/// The 4 bit answer of a card that accepts a command, anything else is a NAK
+const ACK: u8 = 0x0A;
+
+pub fn read<E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    block: u8,
+) -> Result<[u8; 16], DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let rx = rfid.transceive::<18>(&with_crc::<4>(&[MF_READ, block]), 0, 0)?;
+    if rx.valid_bytes == 1 && rx.valid_bits == 4 {
+        return Err(DriverError::Nak);
+    }
+    if rx.valid_bytes != 18 || rx.valid_bits != 0 {
+        return Err(DriverError::IncompleteFrame);
+    }
+    if crc_a(&rx.buffer[..16]) != rx.buffer[16..] {
+        return Err(DriverError::Crc);
+    }
+    Ok(rx.buffer[..16].try_into().unwrap())
+}
+
+pub fn write<E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    block: u8,
+    data: [u8; 16],
+) -> Result<(), DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    ack(rfid.transceive::<1>(&with_crc::<4>(&[MF_WRITE, block]), 0, 0)?)?;
+    ack(rfid.transceive::<1>(&with_crc::<18>(&data), 0, 0)?)
+}
+
+fn ack(rx: FifoData<1>) -> Result<(), DriverError> {
+    if rx.valid_bytes != 1 || rx.valid_bits != 4 {
+        return Err(DriverError::IncompleteFrame);
+    }
+    if rx.buffer[0] & 0x0F != ACK {
+        return Err(DriverError::Nak);
+    }
+    Ok(())
+}
+
+/// `data` followed by its CRC_A, `N` being two bytes more than `data`
+fn with_crc<const N: usize>(data: &[u8]) -> [u8; N] {
+    let mut frame = [0; N];
+    frame[..N - 2].copy_from_slice(data);
+    frame[N - 2..].copy_from_slice(&crc_a(data));
+    frame
+}
+
+/// Wake the card up and select it again after it went idle
+///
+/// The card has to be the same one, writing the backup of a sector to
+/// another card would be worse than losing it.
+pub fn reselect<E, COMM>(
+    uid: &mfrc522::Uid,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let _ = rfid.stop_crypto1();
+    // A card still authenticated can't make sense of a plain WUPA, the first
+    // one only sends it back to idle
+    let atqa = rfid
+        .wupa()
+        .or_else(|_| rfid.wupa())
+        .map_err(|_| Error::CardLost)?;
+    let selected = rfid.select(&atqa).map_err(|_| Error::CardLost)?;
+    if selected.as_bytes() != uid.as_bytes() {
+        return Err(Error::CardLost);
+    }
+    Ok(())
+}
@@ -1,6 +1,27 @@
 #![no_std]
 #![no_main]
 
+// Shared with memory-dump-usb, which sends the dump over USB instead, not
+// everything is needed here
+#[path = "../../memory-dump-usb/src/card.rs"]
+mod card;
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+#[path = "../../memory-dump-usb/src/keys.rs"]
+mod keys;
+#[path = "../../memory-dump-usb/src/mifare.rs"]
+#[allow(dead_code)]
+mod mifare;
+// The dump only reads, writing is there for other tools
+#[allow(dead_code)]
+mod ntag;
+
 use embassy_executor::Spawner;
 use embassy_rp::block::ImageDef;
 use embassy_time::Timer;
@@ -27,38 +48,45 @@ use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
 use core::fmt::Write;
 use heapless::String;
 
+use crate::card_type::CardType;
+use crate::error::{DriverError, Error};
+use crate::keys::{KeySelect, KeyType, SectorKey};
+
+/// Sectors of the largest card, a MIFARE Classic 4K
+const MAX_SECTORS: usize = 40;
+/// Password of the NTAG and Ultralight EV1 cards to dump, `None` to read only
+/// the pages that aren't protected
+const NTAG_PASSWORD: Option<[u8; 4]> = None;
+
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
 pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
 
-fn get_block_type(sector: u8, rel_block: u8) -> &'static str {
-    match rel_block {
-        0 if sector == 0 => "MFD",
-        3 => "TRAILER",
+fn get_block_type(abs_block: u8) -> &'static str {
+    match abs_block {
+        0 => "MFD",
+        block if card_type::is_trailer(block) => "TRAILER",
         _ => "DATA",
     }
 }
 
+/// Print the blocks of a sector, which must already be opened with `key`
 fn read_sector<E, COMM>(
     uid: &mfrc522::Uid,
     sector: u8,
+    key: &SectorKey,
     rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
+) -> Result<(), Error>
 where
     COMM: mfrc522::comm::Interface<Error = E>,
 {
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
     let mut buff: String<64> = String::new();
 
-    let block_offset = sector * 4;
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
-
-    for abs_block in block_offset..block_offset + 4 {
+    let block_offset = card_type::first_block(sector);
+    for abs_block in block_offset..=card_type::trailer_block(sector) {
         let rel_block = abs_block - block_offset;
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
+        let data = keys::read_block(uid, sector, abs_block, key, rfid)?;
 
         // Printing the block data
         for &d in data.iter() {
@@ -66,7 +94,7 @@ where
         }
 
         // Printing block type
-        let block_type = get_block_type(sector, rel_block);
+        let block_type = get_block_type(abs_block);
 
         defmt::println!(
             "BLOCK {} (REL: {}) | {} | {}",
@@ -82,22 +110,132 @@ where
     Ok(())
 }
 
+fn print_sector_key(sector: usize, key: &Option<SectorKey>) {
+    let mut buff: String<64> = String::new();
+    match key {
+        Some(key) => {
+            let key_type = match key.key_type {
+                KeyType::A => "A",
+                KeyType::B => "B",
+            };
+            write!(buff, "SECTOR {:2} | KEY {} | ", sector, key_type)
+                .expect("failed to write into heapless buff");
+            for &d in key.key.iter() {
+                write!(buff, "{:02x} ", d).expect("failed to write byte into buffer");
+            }
+        }
+        None => write!(buff, "SECTOR {:2} | LOCKED", sector)
+            .expect("failed to write into heapless buff"),
+    }
+    defmt::println!("{}", buff);
+}
+
+/// Dump every sector that a key from the dictionary opens
+///
+/// Locked sectors and unreadable blocks are reported and skipped, only
+/// losing the card stops the dump.
 fn dump_memory<E, COMM>(
-    uid: &mfrc522::Uid,
+    card: &card::Card,
+    card_type: CardType,
     rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
+) -> Result<(), Error>
 where
     COMM: mfrc522::comm::Interface<Error = E>,
 {
+    let sectors = card_type.sectors().ok_or(Error::UnsupportedCard)? as usize;
+
     let mut buff: String<64> = String::new();
-    for sector in 0..16 {
+    let mut sector_keys = [None; MAX_SECTORS];
+    let sector_keys = &mut sector_keys[..sectors];
+
+    for (sector, sector_key) in sector_keys.iter_mut().enumerate() {
         // Printing the Sector number
         write!(buff, "-----------SECTOR {}-----------", sector)
             .expect("failed to write into heapless buff");
         defmt::println!("{}", buff);
         buff.clear();
 
-        read_sector(uid, sector, rfid)?;
+        let sector = sector as u8;
+        *sector_key = keys::authenticate_sector(&card.uid, sector, rfid)?;
+        let Some(key) = sector_key else {
+            defmt::println!("No key in the dictionary opens this sector\n");
+            continue;
+        };
+
+        match read_sector(&card.uid, sector, key, rfid) {
+            Ok(()) => {}
+            Err(Error::CardLost) => return Err(Error::CardLost),
+            Err(e) => defmt::error!("Error reading sector {}: {}", sector, e),
+        }
+    }
+
+    defmt::println!("-----------KEYS-----------");
+    for (sector, key) in sector_keys.iter().enumerate() {
+        print_sector_key(sector, key);
+    }
+    Ok(())
+}
+
+/// Dump the pages of an Ultralight or NTAG card
+///
+/// Password protected pages answer with a NAK, which ends the dump unless
+/// `NTAG_PASSWORD` unlocks them. PWD and PACK always read back as zeros.
+fn dump_pages<E, COMM>(rfid: &mut Mfrc522<COMM, mfrc522::Initialized>) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut buff: String<64> = String::new();
+
+    let model = match ntag::get_version(rfid) {
+        Ok(version) => {
+            defmt::println!("VERSION | {=[u8]:02x}", version);
+            ntag::Model::from_version(&version)
+        }
+        Err(_) => {
+            // A plain Ultralight doesn't know GET_VERSION, and went idle after the NAK
+            card::activate(rfid, true).map_err(|_| Error::CardLost)?;
+            None
+        }
+    };
+
+    // Only the cards that know GET_VERSION have a signature and a password
+    if model.is_some() {
+        // The signature isn't in a page, it is read from its own address 0
+        let signature = ntag::read_sig(rfid).map_err(|cause| Error::Read { block: 0, cause })?;
+        defmt::println!("SIGNATURE | {=[u8]:02x}", signature);
+
+        if let Some(password) = NTAG_PASSWORD {
+            // The password protects the whole card, which counts as one sector
+            let pack =
+                ntag::pwd_auth(rfid, password).map_err(|cause| Error::Auth { sector: 0, cause })?;
+            defmt::println!("PACK | {=[u8]:02x}", pack);
+        }
+    }
+
+    let model = model.unwrap_or(ntag::ULTRALIGHT);
+    write!(buff, "-----------{}-----------", model.name)
+        .expect("failed to write into heapless buff");
+    defmt::println!("{}", buff);
+    buff.clear();
+
+    for first_page in (0..model.pages).step_by(ntag::PAGES_PER_READ as usize) {
+        let data = ntag::read(rfid, first_page).map_err(|cause| {
+            if cause == DriverError::Nak {
+                defmt::warn!("The pages from {} may be password protected", first_page);
+            }
+            Error::Read {
+                block: first_page,
+                cause,
+            }
+        })?;
+
+        for (page, bytes) in (first_page..model.pages).zip(data.chunks_exact(ntag::PAGE_SIZE)) {
+            for &d in bytes.iter() {
+                write!(buff, "{:02x} ", d).expect("failed to write byte into buffer");
+            }
+            defmt::println!("PAGE {} | {} | {}", page, buff, model.page_type(page));
+            buff.clear();
+        }
     }
     Ok(())
 }
@@ -118,7 +256,7 @@ async fn main(_spawner: Spawner) {
 
     let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
 
-    let itf = SpiInterface::new(spi);
+    let itf = SpiInterface::new(KeySelect::new(spi));
 
     Timer::after_millis(100).await;
 
@@ -128,15 +266,25 @@ async fn main(_spawner: Spawner) {
     defmt::info!("Initialized RFID reader");
 
     loop {
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                if let Err(e) = dump_memory(&uid, &mut rfid) {
-                    defmt::error!("Error reading sector: {:?}", e);
+        if let Ok(card) = card::activate(&mut rfid, false) {
+            let card_type = CardType::identify(card.atqa, card.sak);
+            defmt::println!("{=str}", card_type.name());
+
+            match card_type {
+                CardType::Ultralight => {
+                    if let Err(e) = dump_pages(&mut rfid) {
+                        defmt::error!("Error dumping pages: {}", e);
+                    }
+                }
+                _ => {
+                    if let Err(e) = dump_memory(&card, card_type, &mut rfid) {
+                        defmt::error!("Error dumping memory: {}", e);
+                    }
                 }
-                let _ = rfid.hlta();
-                let _ = rfid.stop_crypto1();
-                Timer::after_millis(500).await;
             }
+            let _ = rfid.hlta();
+            let _ = rfid.stop_crypto1();
+            Timer::after_millis(500).await;
         }
 
         Timer::after_millis(200).await;
@@ -0,0 +1,258 @@
+//! MIFARE Ultralight and NTAG21x commands.
+//!
+//! These cards have no sectors and no crypto1. Their memory is a list of 4
+//! byte pages, read four at a time and written one at a time. NTAG21x and
+//! Ultralight EV1 cards tell what they are with GET_VERSION, and can protect
+//! part of their memory with a 32 bit password (PWD_AUTH).
+//!
+//! The reader doesn't add the CRC_A to these commands, so we do. A card that
+//! doesn't accept a command answers with a 4 bit NAK and goes back to idle,
+//! it has to be selected again before the next command.
+
+use mfrc522::{FifoData, Mfrc522};
+
+use crate::crc::crc_a;
+use crate::error::DriverError;
+
+pub const PAGE_SIZE: usize = 4;
+/// Pages returned by one READ
+pub const PAGES_PER_READ: u8 = 4;
+
+const READ: u8 = 0x30;
+const WRITE: u8 = 0xA2;
+const GET_VERSION: u8 = 0x60;
+const READ_SIG: u8 = 0x3C;
+const PWD_AUTH: u8 = 0x1B;
+/// 4 bit answer to a WRITE that went through
+const ACK: u8 = 0x0A;
+
+/// Longest command we send: WRITE, page, 4 bytes and the CRC
+const MAX_COMMAND: usize = 8;
+
+/// Vendor and product bytes of GET_VERSION
+const VENDOR_NXP: u8 = 0x04;
+const PRODUCT_ULTRALIGHT: u8 = 0x03;
+const PRODUCT_NTAG: u8 = 0x04;
+
+/// Memory layout of a card
+///
+/// Pages 0 to 3 hold the UID, the static lock bytes and the capability
+/// container (CC), user memory starts at page 4. Cards that have them end
+/// with the dynamic lock bytes and four configuration pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Model {
+    pub name: &'static str,
+    pub pages: u8,
+    /// First page after user memory
+    user_end: u8,
+    dynamic_lock: bool,
+    /// Page 3 is a capability container for NDEF, not just OTP bytes
+    cc: bool,
+}
+
+/// Plain Ultralight, which doesn't know GET_VERSION
+pub const ULTRALIGHT: Model = Model {
+    name: "MIFARE Ultralight",
+    pages: 16,
+    user_end: 16,
+    dynamic_lock: false,
+    cc: false,
+};
+
+/// Known cards, by the storage size byte of GET_VERSION
+const MODELS: [(u8, u8, Model); 5] = [
+    (
+        PRODUCT_ULTRALIGHT,
+        0x0B,
+        Model {
+            name: "MIFARE Ultralight EV1 (MF0UL11)",
+            pages: 20,
+            user_end: 16,
+            dynamic_lock: false,
+            cc: false,
+        },
+    ),
+    (
+        PRODUCT_ULTRALIGHT,
+        0x0E,
+        Model {
+            name: "MIFARE Ultralight EV1 (MF0UL21)",
+            pages: 41,
+            user_end: 36,
+            dynamic_lock: true,
+            cc: false,
+        },
+    ),
+    (
+        PRODUCT_NTAG,
+        0x0F,
+        Model {
+            name: "NTAG213",
+            pages: 45,
+            user_end: 40,
+            dynamic_lock: true,
+            cc: true,
+        },
+    ),
+    (
+        PRODUCT_NTAG,
+        0x11,
+        Model {
+            name: "NTAG215",
+            pages: 135,
+            user_end: 130,
+            dynamic_lock: true,
+            cc: true,
+        },
+    ),
+    (
+        PRODUCT_NTAG,
+        0x13,
+        Model {
+            name: "NTAG216",
+            pages: 231,
+            user_end: 226,
+            dynamic_lock: true,
+            cc: true,
+        },
+    ),
+];
+
+impl Model {
+    /// Find the model from what GET_VERSION returned
+    pub fn from_version(version: &[u8; 8]) -> Option<Model> {
+        let [_, vendor, product, _, _, _, storage, _] = *version;
+        if vendor != VENDOR_NXP {
+            return None;
+        }
+        MODELS
+            .iter()
+            .find(|(p, s, _)| *p == product && *s == storage)
+            .map(|(_, _, model)| *model)
+    }
+
+    pub fn page_type(&self, page: u8) -> &'static str {
+        match page {
+            0 | 1 => "UID",
+            2 => "UID/LOCK",
+            3 if self.cc => "CC",
+            3 => "OTP",
+            page if page < self.user_end => "USER",
+            page if page == self.user_end && self.dynamic_lock => "LOCK",
+            page => match self.pages - page {
+                4 => "CFG0",
+                3 => "CFG1",
+                2 => "PWD",
+                1 => "PACK",
+                _ => "UNKNOWN",
+            },
+        }
+    }
+}
+
+/// Send a command with its CRC and check the CRC of the answer
+///
+/// The answer is `RX` bytes counting the CRC, anything else is an error.
+fn command<const RX: usize, E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    command: &[u8],
+) -> Result<FifoData<RX>, DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut tx = [0u8; MAX_COMMAND];
+    let len = command.len();
+    tx[..len].copy_from_slice(command);
+    let crc = crc_a(command);
+    tx[len..len + 2].copy_from_slice(&crc);
+
+    let rx = rfid.transceive::<RX>(&tx[..len + 2], 0, 0)?;
+    if rx.valid_bytes == 1 && rx.valid_bits == 4 {
+        return Err(DriverError::Nak);
+    }
+    if rx.valid_bytes != RX || rx.valid_bits != 0 {
+        return Err(DriverError::IncompleteFrame);
+    }
+    if crc_a(&rx.buffer[..RX - 2]) != rx.buffer[RX - 2..] {
+        return Err(DriverError::Crc);
+    }
+    Ok(rx)
+}
+
+/// Read 4 pages starting at `page`
+///
+/// Past the last page the card wraps around to page 0.
+pub fn read<E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    page: u8,
+) -> Result<[u8; 16], DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let rx = command::<18, _, _>(rfid, &[READ, page])?;
+    Ok(rx.buffer[..16].try_into().unwrap())
+}
+
+/// Write one page
+pub fn write<E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    page: u8,
+    data: [u8; PAGE_SIZE],
+) -> Result<(), DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut tx = [0u8; MAX_COMMAND];
+    tx[0] = WRITE;
+    tx[1] = page;
+    tx[2..6].copy_from_slice(&data);
+    let crc = crc_a(&tx[..6]);
+    tx[6..].copy_from_slice(&crc);
+
+    let rx = rfid.transceive::<1>(&tx, 0, 0)?;
+    if rx.valid_bytes != 1 || rx.valid_bits != 4 {
+        return Err(DriverError::IncompleteFrame);
+    }
+    if rx.buffer[0] & 0x0F != ACK {
+        return Err(DriverError::Nak);
+    }
+    Ok(())
+}
+
+/// Vendor, product type, subtype, version, storage size and protocol bytes
+pub fn get_version<E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<[u8; 8], DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let rx = command::<10, _, _>(rfid, &[GET_VERSION])?;
+    Ok(rx.buffer[..8].try_into().unwrap())
+}
+
+/// NXP's ECC signature of the UID
+pub fn read_sig<E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<[u8; 32], DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let rx = command::<34, _, _>(rfid, &[READ_SIG, 0x00])?;
+    Ok(rx.buffer[..32].try_into().unwrap())
+}
+
+/// Unlock the protected pages, returning the password acknowledge (PACK)
+///
+/// Check the PACK against the one you set, a fake card would accept any
+/// password.
+pub fn pwd_auth<E, COMM>(
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    password: [u8; 4],
+) -> Result<[u8; 2], DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let [a, b, c, d] = password;
+    let rx = command::<4, _, _>(rfid, &[PWD_AUTH, a, b, c, d])?;
+    Ok([rx.buffer[0], rx.buffer[1]])
+}
@@ -0,0 +1,19 @@
+//! Host tests for the MAD and NDEF code of ndef-tag.
+//!
+//! `mad.rs` and `ndef.rs` only use `core`, so they are pulled in from the
+//! firmware with `#[path]` and run here with `cargo test`. dump-saver
+//! includes the same files.
+
+// The firmware modules under test, at the paths they have in their crate
+#[cfg(test)]
+#[path = "../../ndef-tag/src/mad.rs"]
+#[allow(dead_code)]
+mod mad;
+#[cfg(test)]
+mod mad_tests;
+#[cfg(test)]
+#[path = "../../ndef-tag/src/ndef.rs"]
+#[allow(dead_code)]
+mod ndef;
+#[cfg(test)]
+mod ndef_tests;
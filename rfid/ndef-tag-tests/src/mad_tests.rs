@@ -0,0 +1,56 @@
+//! The MIFARE Application Directory of sector 0.
+
+use crate::mad::{self, Mad, MadError};
+
+/// A 1K card laid out the way NXP TagWriter formats it, saved by dump-saver
+const TAG: &[u8; 1024] = include_bytes!("../../dump-saver/fixtures/ndef-tag.mfd");
+
+fn block(image: &[u8; 1024], block: usize) -> &[u8; 16] {
+    image[block * 16..(block + 1) * 16].try_into().unwrap()
+}
+
+#[test]
+fn mad_of_captured_tag() {
+    let mad = Mad::parse(block(TAG, 1), block(TAG, 2)).unwrap();
+    assert_eq!(mad.info, 1);
+    assert_eq!(mad.sectors(mad::NDEF_AID).count(), 15);
+    assert_eq!(
+        mad,
+        Mad {
+            info: 1,
+            ..Mad::ndef(15)
+        }
+    );
+}
+
+#[test]
+fn mad_round_trip() {
+    let mad = Mad::ndef(3);
+    let (block1, block2) = mad.to_blocks();
+    assert_eq!(&block1[2..8], &[0x03, 0xE1, 0x03, 0xE1, 0x03, 0xE1]);
+    assert_eq!(Mad::parse(&block1, &block2), Ok(mad));
+    assert_eq!(
+        mad.sectors(mad::NDEF_AID).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+
+    // The full NDEF MAD has the CRC TagWriter writes
+    assert_eq!(
+        Mad {
+            info: 1,
+            ..Mad::ndef(15)
+        }
+        .to_blocks()
+        .0[0],
+        0x14
+    );
+
+    let mut broken = block1;
+    broken[4] = 0;
+    assert_eq!(Mad::parse(&broken, &block2), Err(MadError::BadCrc));
+}
+
+#[test]
+fn blank_card_has_no_mad() {
+    assert_eq!(Mad::parse(&[0; 16], &[0; 16]), Err(MadError::BadCrc));
+}
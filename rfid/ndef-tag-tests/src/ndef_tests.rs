@@ -0,0 +1,83 @@
+//! NDEF records and the TLVs around them.
+
+use crate::ndef::{self, NdefError, Record, encode_message, encode_tlv, find_message, records};
+
+fn decode(area: &[u8]) -> Result<Vec<Record<'_>>, NdefError> {
+    records(find_message(area)?).collect()
+}
+
+#[test]
+fn write_then_read() {
+    let written = [
+        Record::uri("https://www.rust-lang.org"),
+        Record::Text {
+            language: "fr",
+            text: "Bonjour",
+        },
+        Record::Mime {
+            mime_type: "application/json",
+            data: b"{}",
+        },
+    ];
+    assert_eq!(
+        written[0],
+        Record::Uri {
+            prefix: 2,
+            rest: "rust-lang.org"
+        }
+    );
+
+    let mut message = [0u8; 256];
+    let len = encode_message(&written, &mut message).unwrap();
+    let mut area = [0u8; 256];
+    let area_len = encode_tlv(&message[..len], &mut area).unwrap();
+    assert_eq!(area[area_len - 1], ndef::TLV_TERMINATOR);
+    assert_eq!(decode(&area).unwrap(), written);
+}
+
+#[test]
+fn long_records_and_tlvs() {
+    let text = "x".repeat(300);
+    let written = [Record::Text {
+        language: "en",
+        text: &text,
+    }];
+    let mut message = [0u8; 512];
+    let len = encode_message(&written, &mut message).unwrap();
+    // No short record flag, so the payload length takes four bytes
+    assert_eq!(message[0] & 0x10, 0);
+    assert_eq!(&message[2..6], &303u32.to_be_bytes());
+
+    let mut area = [0u8; 512];
+    encode_tlv(&message[..len], &mut area).unwrap();
+    assert_eq!(&area[..4], &[ndef::TLV_NDEF, 0xFF, 0x01, 0x36]);
+    assert_eq!(decode(&area).unwrap(), written);
+
+    assert_eq!(
+        encode_message(&written, &mut [0u8; 64]),
+        Err(NdefError::BufferTooSmall)
+    );
+}
+
+#[test]
+fn skips_other_tlvs() {
+    // NULL padding and a proprietary TLV before the NDEF message
+    let area = [0x00, 0x00, 0xFD, 0x02, 0xAA, 0xBB, 0x03, 0x00, 0xFE];
+    assert_eq!(decode(&area), Ok(vec![]));
+    assert_eq!(find_message(&[0xFE, 0x03]), Err(NdefError::NoMessage));
+    assert_eq!(find_message(&[0x03, 0x10, 0xD1]), Err(NdefError::Truncated));
+}
+
+#[test]
+fn broken_records() {
+    // Says the payload is 9 bytes, only has 2
+    assert_eq!(
+        decode(&[0x03, 0x06, 0xD1, 0x01, 0x09, 0x55, 0x04, b'a']),
+        Err(NdefError::Truncated)
+    );
+    // UTF-16 text
+    assert_eq!(
+        decode(&[0x03, 0x06, 0xD1, 0x01, 0x02, 0x54, 0x80, b'a']),
+        Err(NdefError::Unsupported)
+    );
+}
@@ -0,0 +1,117 @@
+//! MIFARE Application Directory (MAD) version 1, for 1K cards.
+//!
+//! Blocks 1 and 2 of sector 0 say which application uses each of the other
+//! 15 sectors:
+//!
+//! ```text
+//! block 1: | CRC | info | AID sector 1 | ... | AID sector 7  |
+//! block 2: | AID sector 8 | ...               | AID sector 15 |
+//! ```
+//!
+//! Every AID is two bytes, low byte first. The CRC-8 covers everything
+//! after it. The trailer of sector 0 has `0xC1` as its user byte to say the
+//! card has a MAD.
+//!
+//! This file is shared with the host side dump-saver tool, so it only uses
+//! `core`.
+
+/// Sectors listed in the MAD, sector 0 holds the MAD itself
+pub const SECTORS: usize = 15;
+
+/// Sector used by NFC Forum NDEF data
+pub const NDEF_AID: u16 = 0xE103;
+/// Sector not used by any application
+pub const FREE_AID: u16 = 0x0000;
+
+/// Key A of sector 0, publicly known so anyone can read the MAD
+pub const MAD_KEY_A: [u8; 6] = [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5];
+/// Key A of NDEF sectors, publicly known so any phone can read them
+pub const NDEF_KEY_A: [u8; 6] = [0xD3, 0xF7, 0xD3, 0xF7, 0xD3, 0xF7];
+
+/// Trailer user byte of sector 0: a MAD version 1 is present, multi application card
+pub const MAD_USER_BYTE: u8 = 0xC1;
+/// Trailer user byte of NDEF sectors: mapping version 1.0, read and write allowed
+pub const NDEF_USER_BYTE: u8 = 0x40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MadError {
+    /// The CRC doesn't match, the card probably has no MAD
+    BadCrc,
+}
+
+/// CRC-8 of the MAD: polynomial 0x1D, preset 0xC7
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0xC7;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x1D
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mad {
+    /// Sector holding the card issuer's information, 0 if there is none
+    pub info: u8,
+    /// Application of sectors 1 to 15
+    pub aids: [u16; SECTORS],
+}
+
+impl Mad {
+    /// A MAD giving the first `sectors` sectors after sector 0 to NDEF
+    pub fn ndef(sectors: usize) -> Self {
+        let mut aids = [FREE_AID; SECTORS];
+        aids[..sectors.min(SECTORS)].fill(NDEF_AID);
+        Mad { info: 0, aids }
+    }
+
+    /// Parse blocks 1 and 2 of sector 0
+    pub fn parse(block1: &[u8; 16], block2: &[u8; 16]) -> Result<Self, MadError> {
+        let mut data = [0u8; 32];
+        data[..16].copy_from_slice(block1);
+        data[16..].copy_from_slice(block2);
+
+        if crc8(&data[1..]) != data[0] {
+            return Err(MadError::BadCrc);
+        }
+
+        let mut aids = [FREE_AID; SECTORS];
+        for (aid, bytes) in aids.iter_mut().zip(data[2..].chunks_exact(2)) {
+            *aid = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        Ok(Mad {
+            info: data[1] & 0x3F,
+            aids,
+        })
+    }
+
+    /// Blocks 1 and 2 of sector 0
+    pub fn to_blocks(self) -> ([u8; 16], [u8; 16]) {
+        let mut data = [0u8; 32];
+        data[1] = self.info;
+        for (aid, bytes) in self.aids.iter().zip(data[2..].chunks_exact_mut(2)) {
+            bytes.copy_from_slice(&aid.to_le_bytes());
+        }
+        data[0] = crc8(&data[1..]);
+
+        let mut block1 = [0u8; 16];
+        let mut block2 = [0u8; 16];
+        block1.copy_from_slice(&data[..16]);
+        block2.copy_from_slice(&data[16..]);
+        (block1, block2)
+    }
+
+    /// Sectors used by an application, in order
+    pub fn sectors(&self, aid: u16) -> impl Iterator<Item = u8> + '_ {
+        (1..)
+            .zip(self.aids.iter())
+            .filter(move |&(_, &a)| a == aid)
+            .map(|(sector, _)| sector)
+    }
+}
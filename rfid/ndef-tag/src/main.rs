@@ -0,0 +1,343 @@
+#![no_std]
+#![no_main]
+
+// Shared with change-key, not everything is needed here
+#[path = "../../change-key/src/access.rs"]
+#[allow(dead_code)]
+mod access;
+// Shared with memory-dump-usb, not everything is needed here
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+#[path = "../../memory-dump-usb/src/keys.rs"]
+mod keys;
+#[path = "../../memory-dump-usb/src/mifare.rs"]
+mod mifare;
+// Shared with the dump-saver tool
+#[allow(dead_code)]
+mod mad;
+#[allow(dead_code)]
+mod ndef;
+
+use embassy_executor::Spawner;
+use embassy_rp::block::ImageDef;
+use embassy_time::Timer;
+
+//Panic Handler
+use panic_probe as _;
+
+// Defmt Logging
+use defmt_rtt as _;
+
+// For SPI
+use embassy_rp::spi::Spi;
+use embassy_rp::{self as hal, spi};
+use embassy_time::Delay;
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+// For CS Pin
+use embassy_rp::gpio::{Level, Output};
+
+// Driver for the MFRC522
+use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
+
+use crate::access::{AccessConditions, Condition, SectorTrailer};
+use crate::error::Error;
+use crate::keys::{KeySelect, KeyType, SectorKey};
+use crate::mad::Mad;
+use crate::ndef::{NdefError, Record};
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+/// Bytes of NDEF data a sector holds, its three data blocks
+const SECTOR_DATA: usize = 48;
+/// Room for TLVs when every sector after the MAD holds NDEF data
+const AREA_SIZE: usize = mad::SECTORS * SECTOR_DATA;
+
+/// Set to format every card tapped for NDEF and write `MESSAGE` to it
+const WRITE: bool = false;
+
+const MESSAGE: [Record; 2] = [
+    Record::Uri {
+        // "https://"
+        prefix: 4,
+        rest: "github.com/ImplFerris",
+    },
+    Record::Text {
+        language: "en",
+        text: "Hello from Pico 2",
+    },
+];
+
+/// Key B given to the MAD and NDEF sectors, it allows changing them later
+const KEY_B: [u8; 6] = [0xFF; 6];
+
+/// Keys tried for writing: key A of a blank card, then key B of a card we formatted
+const WRITE_KEYS: [SectorKey; 2] = [
+    SectorKey {
+        key_type: KeyType::A,
+        key: [0xFF; 6],
+    },
+    SectorKey {
+        key_type: KeyType::B,
+        key: KEY_B,
+    },
+];
+
+/// Sector 0: everyone can read the MAD, only key B can change it
+const MAD_TRAILER: SectorTrailer = SectorTrailer {
+    key_a: mad::MAD_KEY_A,
+    // Access bits 78 77 88
+    access: AccessConditions {
+        blocks: [Condition::new(true, false, false); 3],
+        trailer: Condition::new(false, true, true),
+    },
+    user_byte: mad::MAD_USER_BYTE,
+    key_b: KEY_B,
+};
+
+/// NDEF sectors: everyone can read and write the data, only key B can change the keys
+const NDEF_TRAILER: SectorTrailer = SectorTrailer {
+    key_a: mad::NDEF_KEY_A,
+    // Access bits 7F 07 88
+    access: AccessConditions {
+        blocks: [Condition::new(false, false, false); 3],
+        trailer: Condition::new(false, true, true),
+    },
+    user_byte: mad::NDEF_USER_BYTE,
+    key_b: KEY_B,
+};
+
+fn ndef_error(e: NdefError) -> Error {
+    Error::InvalidData(match e {
+        NdefError::Truncated => "NDEF data is cut short",
+        NdefError::NoMessage => "No NDEF message",
+        NdefError::Unsupported => "Unsupported NDEF record",
+        NdefError::InvalidUtf8 => "Invalid text in NDEF record",
+        NdefError::BufferTooSmall => "NDEF message too long",
+    })
+}
+
+fn write_block<E, COMM>(
+    block: u8,
+    data: [u8; 16],
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    mifare::write(rfid, block, data).map_err(|cause| Error::Write { block, cause })
+}
+
+/// Read the MAD and the data blocks of every NDEF sector, in order
+fn read_ndef_area<E, COMM>(
+    uid: &mfrc522::Uid,
+    area: &mut [u8; AREA_SIZE],
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<usize, Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let Some(key) = keys::authenticate_sector(uid, 0, rfid)? else {
+        return Err(Error::NoKey { sector: 0 });
+    };
+    let block1 = keys::read_block(uid, 0, 1, &key, rfid)?;
+    let block2 = keys::read_block(uid, 0, 2, &key, rfid)?;
+    let mad =
+        Mad::parse(&block1, &block2).map_err(|_| Error::InvalidData("No MAD on this card"))?;
+
+    let mut len = 0;
+    for sector in mad.sectors(mad::NDEF_AID) {
+        let Some(key) = keys::authenticate_sector(uid, sector, rfid)? else {
+            return Err(Error::NoKey { sector });
+        };
+        for block in sector * 4..sector * 4 + 3 {
+            let data = keys::read_block(uid, sector, block, &key, rfid)?;
+            area[len..len + 16].copy_from_slice(&data);
+            len += 16;
+        }
+    }
+    Ok(len)
+}
+
+fn print_record(record: &Record) {
+    match record {
+        Record::Uri { prefix, rest } => {
+            defmt::println!("URI: {}{}", Record::uri_prefix(*prefix), rest)
+        }
+        Record::Text { language, text } => defmt::println!("Text ({}): {}", language, text),
+        Record::Mime { mime_type, data } => {
+            defmt::println!("MIME {}: {} bytes", mime_type, data.len())
+        }
+        Record::Other { tnf, payload, .. } => {
+            defmt::println!("Record with TNF {}: {} bytes", tnf, payload.len())
+        }
+    }
+}
+
+fn print_ndef<E, COMM>(
+    uid: &mfrc522::Uid,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut area = [0u8; AREA_SIZE];
+    let len = read_ndef_area(uid, &mut area, rfid)?;
+    let message = ndef::find_message(&area[..len]).map_err(ndef_error)?;
+
+    defmt::println!("NDEF message, {} bytes", message.len());
+    for record in ndef::records(message) {
+        print_record(&record.map_err(ndef_error)?);
+    }
+    Ok(())
+}
+
+/// Open a sector with whichever of `WRITE_KEYS` works
+fn authenticate_for_write<E, COMM>(
+    uid: &mfrc522::Uid,
+    sector: u8,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    for key in WRITE_KEYS.iter() {
+        if keys::authenticate_with(uid, sector * 4, key, rfid).is_ok() {
+            return Ok(());
+        }
+        mifare::reselect(uid, rfid)?;
+    }
+    Err(Error::NoKey { sector })
+}
+
+/// Write a sector's data blocks, then its trailer
+fn write_sector<E, COMM>(
+    uid: &mfrc522::Uid,
+    sector: u8,
+    data: &[u8],
+    trailer: &SectorTrailer,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    authenticate_for_write(uid, sector, rfid)?;
+    for (block, chunk) in (sector * 4..).zip(data.chunks(16)) {
+        let mut buff = [0u8; 16];
+        buff[..chunk.len()].copy_from_slice(chunk);
+        write_block(block, buff, rfid)?;
+    }
+
+    let trailer = trailer
+        .to_block()
+        .map_err(|_| Error::InvalidData("Invalid trailer"))?;
+    write_block(sector * 4 + 3, trailer, rfid)
+}
+
+/// Format the card for NDEF and write a message to it
+///
+/// The MAD gives NDEF only the sectors the message needs, the contents of
+/// the other sectors are left alone.
+fn write_ndef<E, COMM>(
+    uid: &mfrc522::Uid,
+    records: &[Record],
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut message = [0u8; AREA_SIZE];
+    let len = ndef::encode_message(records, &mut message).map_err(ndef_error)?;
+    let mut area = [0u8; AREA_SIZE];
+    let area_len = ndef::encode_tlv(&message[..len], &mut area).map_err(ndef_error)?;
+
+    let sectors = area_len.div_ceil(SECTOR_DATA);
+    let (block1, block2) = Mad::ndef(sectors).to_blocks();
+
+    // Sector 0 starts with the manufacturer block, the MAD goes in blocks 1 and 2
+    authenticate_for_write(uid, 0, rfid)?;
+    write_block(1, block1, rfid)?;
+    write_block(2, block2, rfid)?;
+    let trailer = MAD_TRAILER
+        .to_block()
+        .map_err(|_| Error::InvalidData("Invalid trailer"))?;
+    write_block(3, trailer, rfid)?;
+
+    for (sector, data) in (1..).zip(area[..sectors * SECTOR_DATA].chunks(SECTOR_DATA)) {
+        write_sector(uid, sector, data, &NDEF_TRAILER, rfid)?;
+    }
+    defmt::info!(
+        "Wrote {} bytes of NDEF data to {} sectors",
+        area_len,
+        sectors
+    );
+    Ok(())
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let miso = p.PIN_0;
+    let cs_pin = Output::new(p.PIN_1, Level::High);
+    let clk = p.PIN_2;
+    let mosi = p.PIN_3;
+
+    let mut config = spi::Config::default();
+    config.frequency = 1_000_000;
+
+    let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
+
+    let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
+
+    let itf = SpiInterface::new(KeySelect::new(spi));
+
+    Timer::after_millis(100).await;
+
+    let mut rfid = Mfrc522::new(itf)
+        .init()
+        .expect("failed to initialize the RFID reader");
+    defmt::info!("Initialized RFID reader");
+
+    loop {
+        if let Ok(atqa) = rfid.reqa()
+            && let Ok(uid) = rfid.select(&atqa)
+        {
+            if WRITE && let Err(e) = write_ndef(&uid, &MESSAGE, &mut rfid) {
+                defmt::error!("Error writing NDEF message: {}", e);
+            }
+            if let Err(e) = print_ndef(&uid, &mut rfid) {
+                defmt::error!("Error reading NDEF message: {}", e);
+            }
+            let _ = rfid.hlta();
+            let _ = rfid.stop_crypto1();
+            Timer::after_millis(500).await;
+        }
+
+        Timer::after_millis(200).await;
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recommended to have these minimal entries.
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
+    embassy_rp::binary_info::rp_program_name!(c"ndef-tag"),
+    embassy_rp::binary_info::rp_program_description!(
+        c"Read and write NDEF messages on MIFARE Classic"
+    ),
+    embassy_rp::binary_info::rp_cargo_version!(),
+    embassy_rp::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
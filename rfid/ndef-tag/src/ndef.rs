@@ -0,0 +1,377 @@
+//! NDEF messages and the TLVs that hold them on a tag.
+//!
+//! The data blocks of the NDEF sectors, put together in order, hold a list
+//! of TLVs (type, length, value). The NDEF message is the value of the TLV
+//! with type `0x03`, and a `0xFE` TLV ends the list:
+//!
+//! ```text
+//! | 03 | length (1, or FF + 2 big endian) | NDEF message | FE |
+//! ```
+//!
+//! The message is a list of records, each one starting with a header byte:
+//!
+//! ```text
+//! | MB | ME | CF | SR | IL | TNF (3) |
+//! ```
+//!
+//! MB and ME mark the first and last record, SR says the payload length
+//! takes one byte instead of four, IL says an ID follows the type and TNF
+//! says how to read the type. Chunked records (CF) aren't supported.
+//!
+//! This file is shared with the host side dump-saver tool, so it only uses
+//! `core`.
+
+pub const TLV_NULL: u8 = 0x00;
+pub const TLV_NDEF: u8 = 0x03;
+pub const TLV_TERMINATOR: u8 = 0xFE;
+
+const MB: u8 = 0x80;
+const ME: u8 = 0x40;
+const CF: u8 = 0x20;
+const SR: u8 = 0x10;
+const IL: u8 = 0x08;
+
+/// Type name formats
+pub const TNF_WELL_KNOWN: u8 = 0x01;
+pub const TNF_MIME: u8 = 0x02;
+
+/// Text encoding flag in the status byte of a Text record
+const TEXT_UTF16: u8 = 0x80;
+
+/// URI prefixes, a URI record stores the index of its prefix instead of the text
+const URI_PREFIXES: [&str; 36] = [
+    "",
+    "http://www.",
+    "https://www.",
+    "http://",
+    "https://",
+    "tel:",
+    "mailto:",
+    "ftp://anonymous:anonymous@",
+    "ftp://ftp.",
+    "ftps://",
+    "sftp://",
+    "smb://",
+    "nfs://",
+    "ftp://",
+    "dav://",
+    "news:",
+    "telnet://",
+    "imap:",
+    "rtsp://",
+    "urn:",
+    "pop:",
+    "sip:",
+    "sips:",
+    "tftp:",
+    "btspp://",
+    "btl2cap://",
+    "btgoep://",
+    "tcpobex://",
+    "irdaobex://",
+    "file://",
+    "urn:epc:id:",
+    "urn:epc:tag:",
+    "urn:epc:pat:",
+    "urn:epc:raw:",
+    "urn:epc:",
+    "urn:nfc:",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NdefError {
+    /// The data ends in the middle of a TLV or record
+    Truncated,
+    /// There is no NDEF message TLV
+    NoMessage,
+    /// Chunked records, UTF-16 text or an unknown URI prefix
+    Unsupported,
+    /// Text that should be UTF-8 isn't
+    InvalidUtf8,
+    /// The output buffer can't hold the message
+    BufferTooSmall,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Record<'a> {
+    /// A URI, its first part replaced by one of the standard prefixes
+    Uri {
+        prefix: u8,
+        rest: &'a str,
+    },
+    Text {
+        language: &'a str,
+        text: &'a str,
+    },
+    Mime {
+        mime_type: &'a str,
+        data: &'a [u8],
+    },
+    /// Any other record, left as it is
+    Other {
+        tnf: u8,
+        record_type: &'a [u8],
+        payload: &'a [u8],
+    },
+}
+
+impl<'a> Record<'a> {
+    /// A URI record, using the longest prefix that matches
+    pub fn uri(uri: &'a str) -> Self {
+        let (prefix, text) = URI_PREFIXES
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| uri.starts_with(*p))
+            .max_by_key(|(_, p)| p.len())
+            .unwrap_or((0, &""));
+        Record::Uri {
+            prefix: prefix as u8,
+            rest: &uri[text.len()..],
+        }
+    }
+
+    /// The text a URI record's prefix stands for
+    pub fn uri_prefix(prefix: u8) -> &'static str {
+        URI_PREFIXES.get(prefix as usize).copied().unwrap_or("")
+    }
+
+    fn parse(tnf: u8, record_type: &'a [u8], payload: &'a [u8]) -> Result<Self, NdefError> {
+        let utf8 = |bytes| core::str::from_utf8(bytes).map_err(|_| NdefError::InvalidUtf8);
+
+        Ok(match (tnf, record_type) {
+            (TNF_WELL_KNOWN, b"U") => {
+                let (&prefix, rest) = payload.split_first().ok_or(NdefError::Truncated)?;
+                if prefix as usize >= URI_PREFIXES.len() {
+                    return Err(NdefError::Unsupported);
+                }
+                Record::Uri {
+                    prefix,
+                    rest: utf8(rest)?,
+                }
+            }
+            (TNF_WELL_KNOWN, b"T") => {
+                let (&status, rest) = payload.split_first().ok_or(NdefError::Truncated)?;
+                if status & TEXT_UTF16 != 0 {
+                    return Err(NdefError::Unsupported);
+                }
+                let len = (status & 0x3F) as usize;
+                if rest.len() < len {
+                    return Err(NdefError::Truncated);
+                }
+                Record::Text {
+                    language: utf8(&rest[..len])?,
+                    text: utf8(&rest[len..])?,
+                }
+            }
+            (TNF_MIME, _) => Record::Mime {
+                mime_type: utf8(record_type)?,
+                data: payload,
+            },
+            _ => Record::Other {
+                tnf,
+                record_type,
+                payload,
+            },
+        })
+    }
+
+    /// TNF and type of the record
+    fn record_type(&self) -> (u8, &'a [u8]) {
+        match *self {
+            Record::Uri { .. } => (TNF_WELL_KNOWN, b"U"),
+            Record::Text { .. } => (TNF_WELL_KNOWN, b"T"),
+            Record::Mime { mime_type, .. } => (TNF_MIME, mime_type.as_bytes()),
+            Record::Other {
+                tnf, record_type, ..
+            } => (tnf, record_type),
+        }
+    }
+
+    /// Payload as an optional first byte (URI prefix or Text status) and
+    /// two parts that follow it
+    fn payload(&self) -> (Option<u8>, &'a [u8], &'a [u8]) {
+        match *self {
+            Record::Uri { prefix, rest } => (Some(prefix), b"", rest.as_bytes()),
+            Record::Text { language, text } => (
+                Some(language.len() as u8),
+                language.as_bytes(),
+                text.as_bytes(),
+            ),
+            Record::Mime { data, .. } => (None, b"", data),
+            Record::Other { payload, .. } => (None, b"", payload),
+        }
+    }
+
+    fn payload_len(&self) -> usize {
+        let (first, a, b) = self.payload();
+        first.is_some() as usize + a.len() + b.len()
+    }
+}
+
+/// Find the NDEF message in the TLVs of a tag
+pub fn find_message(area: &[u8]) -> Result<&[u8], NdefError> {
+    let mut i = 0;
+    while i < area.len() {
+        let tlv = area[i];
+        match tlv {
+            TLV_NULL => {
+                i += 1;
+                continue;
+            }
+            TLV_TERMINATOR => break,
+            _ => {}
+        }
+
+        let (len, start) = match area.get(i + 1) {
+            Some(0xFF) => {
+                let bytes = area.get(i + 2..i + 4).ok_or(NdefError::Truncated)?;
+                (u16::from_be_bytes([bytes[0], bytes[1]]) as usize, i + 4)
+            }
+            Some(&len) => (len as usize, i + 2),
+            None => return Err(NdefError::Truncated),
+        };
+        let value = area.get(start..start + len).ok_or(NdefError::Truncated)?;
+        if tlv == TLV_NDEF {
+            return Ok(value);
+        }
+        i = start + len;
+    }
+    Err(NdefError::NoMessage)
+}
+
+/// The records of an NDEF message
+pub fn records(message: &[u8]) -> Records<'_> {
+    Records {
+        data: message,
+        done: message.is_empty(),
+    }
+}
+
+pub struct Records<'a> {
+    data: &'a [u8],
+    done: bool,
+}
+
+impl<'a> Records<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], NdefError> {
+        if self.data.len() < len {
+            return Err(NdefError::Truncated);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn next_record(&mut self) -> Result<Record<'a>, NdefError> {
+        let header = self.take(1)?[0];
+        if header & ME != 0 {
+            self.done = true;
+        }
+        if header & CF != 0 {
+            return Err(NdefError::Unsupported);
+        }
+
+        let type_len = self.take(1)?[0] as usize;
+        let payload_len = if header & SR != 0 {
+            self.take(1)?[0] as usize
+        } else {
+            let bytes = self.take(4)?;
+            u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+        };
+        let id_len = if header & IL != 0 {
+            self.take(1)?[0] as usize
+        } else {
+            0
+        };
+
+        let record_type = self.take(type_len)?;
+        self.take(id_len)?;
+        let payload = self.take(payload_len)?;
+        Record::parse(header & 0x07, record_type, payload)
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<Record<'a>, NdefError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.next_record();
+        if record.is_err() {
+            self.done = true;
+        }
+        Some(record)
+    }
+}
+
+/// Writes bytes into a buffer, failing once it is full
+struct Writer<'a> {
+    out: &'a mut [u8],
+    len: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), NdefError> {
+        let end = self.len + bytes.len();
+        self.out
+            .get_mut(self.len..end)
+            .ok_or(NdefError::BufferTooSmall)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+}
+
+/// Write an NDEF message holding the records, returning its length
+pub fn encode_message(records: &[Record], out: &mut [u8]) -> Result<usize, NdefError> {
+    let mut writer = Writer { out, len: 0 };
+
+    for (i, record) in records.iter().enumerate() {
+        let (tnf, record_type) = record.record_type();
+        let payload_len = record.payload_len();
+
+        let mut header = tnf;
+        if i == 0 {
+            header |= MB;
+        }
+        if i + 1 == records.len() {
+            header |= ME;
+        }
+        if payload_len < 256 {
+            header |= SR;
+        }
+
+        writer.put(&[header, record_type.len() as u8])?;
+        if payload_len < 256 {
+            writer.put(&[payload_len as u8])?;
+        } else {
+            writer.put(&(payload_len as u32).to_be_bytes())?;
+        }
+        writer.put(record_type)?;
+
+        let (first, a, b) = record.payload();
+        if let Some(first) = first {
+            writer.put(&[first])?;
+        }
+        writer.put(a)?;
+        writer.put(b)?;
+    }
+    Ok(writer.len)
+}
+
+/// Wrap an NDEF message in its TLV followed by the terminator
+pub fn encode_tlv(message: &[u8], out: &mut [u8]) -> Result<usize, NdefError> {
+    let mut writer = Writer { out, len: 0 };
+    if message.len() < 0xFF {
+        writer.put(&[TLV_NDEF, message.len() as u8])?;
+    } else {
+        let len = u16::try_from(message.len()).map_err(|_| NdefError::BufferTooSmall)?;
+        writer.put(&[TLV_NDEF, 0xFF])?;
+        writer.put(&len.to_be_bytes())?;
+    }
+    writer.put(message)?;
+    writer.put(&[TLV_TERMINATOR])?;
+    Ok(writer.len)
+}
@@ -1,6 +1,15 @@
 #![no_std]
 #![no_main]
 
+// Shared with memory-dump-usb, only the decoder of card_type is needed here
+#[path = "../../memory-dump-usb/src/card.rs"]
+mod card;
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+
 use embassy_executor::Spawner;
 use embassy_rp::block::ImageDef;
 use embassy_time::Timer;
@@ -23,6 +32,8 @@ use embassy_rp::gpio::{Level, Output};
 // Driver for the MFRC522
 use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
 
+use crate::card_type::{CardType, atqa_value};
+
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
@@ -38,7 +49,7 @@ async fn main(_spawner: Spawner) {
     let mosi = p.PIN_3;
 
     let mut config = spi::Config::default();
-    config.frequency = 1000_000;
+    config.frequency = 1_000_000;
 
     let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
 
@@ -50,11 +61,16 @@ async fn main(_spawner: Spawner) {
         .expect("failed to initialize the RFID reader");
 
     loop {
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                defmt::info!("UID: {:02x}", uid.as_bytes());
-                Timer::after_millis(500).await;
-            }
+        if let Ok(card) = card::activate(&mut rfid, false) {
+            let card_type = CardType::identify(card.atqa, card.sak);
+            defmt::info!("UID: {:02x}", card.uid.as_bytes());
+            defmt::info!(
+                "ATQA: {:04x} SAK: {:02x} ({=str})",
+                atqa_value(card.atqa),
+                card.sak,
+                card_type.name()
+            );
+            Timer::after_millis(500).await;
         }
     }
 }
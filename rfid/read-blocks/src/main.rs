@@ -1,6 +1,16 @@
 #![no_std]
 #![no_main]
 
+// Shared with memory-dump-usb, not everything is needed here
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+#[path = "../../memory-dump-usb/src/mifare.rs"]
+#[allow(dead_code)]
+mod mifare;
+
 use embassy_executor::Spawner;
 use embassy_rp::block::ImageDef;
 use embassy_time::Timer;
@@ -27,6 +37,8 @@ use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
 use core::fmt::Write;
 use heapless::String;
 
+use crate::error::Error;
+
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
@@ -36,7 +48,7 @@ fn read_sector<E, COMM>(
     uid: &mfrc522::Uid,
     sector: u8,
     rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
+) -> Result<(), Error>
 where
     COMM: mfrc522::comm::Interface<Error = E>,
 {
@@ -44,10 +56,16 @@ where
 
     let block_offset = sector * 4;
     rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
+        .map_err(|e| Error::Auth {
+            sector,
+            cause: e.into(),
+        })?;
 
     for abs_block in block_offset..block_offset + 4 {
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
+        let data = mifare::read(rfid, abs_block).map_err(|cause| Error::Read {
+            block: abs_block,
+            cause,
+        })?;
         print_hex(&data);
     }
     Ok(())
@@ -71,7 +89,7 @@ async fn main(_spawner: Spawner) {
     let mosi = p.PIN_3;
 
     let mut config = spi::Config::default();
-    config.frequency = 1000_000;
+    config.frequency = 1_000_000;
 
     let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
 
@@ -87,15 +105,15 @@ async fn main(_spawner: Spawner) {
     defmt::info!("Initialized RFID reader");
 
     loop {
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                if let Err(e) = read_sector(&uid, 0, &mut rfid) {
-                    defmt::error!("Error reading sector: {:?}", e);
-                }
-                let _ = rfid.hlta();
-                let _ = rfid.stop_crypto1();
-                Timer::after_millis(100).await;
+        if let Ok(atqa) = rfid.reqa()
+            && let Ok(uid) = rfid.select(&atqa)
+        {
+            if let Err(e) = read_sector(&uid, 0, &mut rfid) {
+                defmt::error!("Error reading sector: {}", e);
             }
+            let _ = rfid.hlta();
+            let _ = rfid.stop_crypto1();
+            Timer::after_millis(100).await;
         }
 
         Timer::after_millis(100).await;
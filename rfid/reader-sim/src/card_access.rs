@@ -0,0 +1,113 @@
+//! Access bits of the virtual card.
+//!
+//! Every sector trailer holds one access condition per block group, three
+//! bits C1 C2 C3, stored twice with the second copy inverted. Small sectors
+//! have a group per block. The large sectors of a 4K card have 15 data
+//! blocks, which share the three data conditions five at a time.
+//!
+//! This is written from the MIFARE Classic datasheet on its own, not shared
+//! with the firmwares, so the tests don't check their code against itself.
+
+use crate::picc::KeyType;
+
+/// Which key allows an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Never,
+    KeyA,
+    KeyB,
+    KeyAOrB,
+}
+
+impl Access {
+    pub fn allows(self, key_type: KeyType) -> bool {
+        matches!(
+            (self, key_type),
+            (Access::KeyAOrB, _) | (Access::KeyA, KeyType::A) | (Access::KeyB, KeyType::B)
+        )
+    }
+}
+
+/// Read and write access to a data block
+pub fn data(condition: u8) -> (Access, Access) {
+    use Access::*;
+    match condition {
+        0b000 => (KeyAOrB, KeyAOrB),
+        0b010 | 0b001 => (KeyAOrB, Never),
+        0b100 | 0b110 => (KeyAOrB, KeyB),
+        0b011 => (KeyB, KeyB),
+        0b101 => (KeyB, Never),
+        _ => (Never, Never),
+    }
+}
+
+/// What can be done with a sector trailer
+pub struct Trailer {
+    pub key_a_write: Access,
+    pub access_bits_read: Access,
+    pub access_bits_write: Access,
+    pub key_b_read: Access,
+    pub key_b_write: Access,
+}
+
+pub fn trailer(condition: u8) -> Trailer {
+    use Access::*;
+    let (key_a_write, access_bits_read, access_bits_write, key_b_read, key_b_write) =
+        match condition {
+            0b000 => (KeyA, KeyA, Never, KeyA, KeyA),
+            0b010 => (Never, KeyA, Never, KeyA, Never),
+            0b100 => (KeyB, KeyAOrB, Never, Never, KeyB),
+            0b110 => (Never, KeyAOrB, Never, Never, Never),
+            0b001 => (KeyA, KeyA, KeyA, KeyA, KeyA),
+            0b011 => (KeyB, KeyAOrB, KeyB, Never, KeyB),
+            0b101 => (Never, KeyAOrB, KeyB, Never, Never),
+            _ => (Never, KeyAOrB, Never, Never, Never),
+        };
+    Trailer {
+        key_a_write,
+        access_bits_read,
+        access_bits_write,
+        key_b_read,
+        key_b_write,
+    }
+}
+
+/// The conditions of the three data groups and the trailer, or `None` if
+/// the inverted copy doesn't match and the sector is locked for good
+pub fn decode(bytes: [u8; 3]) -> Option<[u8; 4]> {
+    let c1 = bytes[1] >> 4;
+    let c2 = bytes[2] & 0x0F;
+    let c3 = bytes[2] >> 4;
+    if !bytes[0] & 0x0F != c1 || !bytes[0] >> 4 != c2 || !bytes[1] & 0x0F != c3 {
+        return None;
+    }
+
+    let mut conditions = [0; 4];
+    for (group, condition) in conditions.iter_mut().enumerate() {
+        let bit = |bits: u8| (bits >> group) & 1;
+        *condition = (bit(c1) << 2) | (bit(c2) << 1) | bit(c3);
+    }
+    Some(conditions)
+}
+
+/// The access bytes for the conditions of the three data groups and the
+/// trailer, each written as `0bC1C2C3`
+pub fn encode(conditions: [u8; 4]) -> [u8; 3] {
+    let (mut c1, mut c2, mut c3) = (0u8, 0u8, 0u8);
+    for (group, condition) in conditions.iter().enumerate() {
+        c1 |= ((condition >> 2) & 1) << group;
+        c2 |= ((condition >> 1) & 1) << group;
+        c3 |= (condition & 1) << group;
+    }
+    [
+        ((!c2 & 0x0F) << 4) | (!c1 & 0x0F),
+        (c1 << 4) | (!c3 & 0x0F),
+        (c3 << 4) | c2,
+    ]
+}
+
+/// Key B can be read in the trailer conditions 000, 010 and 001, and then
+/// doesn't give access to anything
+pub fn key_b_readable(trailer_condition: u8) -> bool {
+    matches!(trailer_condition, 0b000..=0b010)
+}
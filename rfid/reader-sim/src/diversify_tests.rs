@@ -0,0 +1,104 @@
+//! The per-card keys of change-key.
+
+use mfrc522::Mfrc522;
+use mfrc522::comm::blocking::spi::SpiInterface;
+
+use crate::diversify::{self, CardKeys};
+use crate::transaction::Transaction;
+use crate::{Card, Simulator};
+
+const MASTER_KEY: [u8; 16] = *b"implRust-master!";
+const UID: [u8; 4] = [0xC0, 0xFF, 0xEE, 0x42];
+const OTHER_UID: [u8; 4] = [0x13, 0x37, 0xC0, 0xDE];
+
+/// The key of RFC 4493's examples
+const RFC_KEY: [u8; 16] = [
+    0x2B, 0x7E, 0x15, 0x16, 0x28, 0xAE, 0xD2, 0xA6, 0xAB, 0xF7, 0x15, 0x88, 0x09, 0xCF, 0x4F, 0x3C,
+];
+
+#[test]
+fn cmac_matches_rfc_4493() {
+    assert_eq!(
+        diversify::cmac(&RFC_KEY, &[]),
+        [
+            0xBB, 0x1D, 0x69, 0x29, 0xE9, 0x59, 0x37, 0x28, 0x7F, 0xA3, 0x7D, 0x12, 0x9B, 0x75,
+            0x67, 0x46
+        ]
+    );
+
+    let message = [
+        0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93, 0x17,
+        0x2A,
+    ];
+    let expected = [
+        0x07, 0x0A, 0x16, 0xB4, 0x6B, 0x4D, 0x41, 0x44, 0xF7, 0x9B, 0xDD, 0x9D, 0xD0, 0x4A, 0x28,
+        0x7C,
+    ];
+    assert_eq!(diversify::cmac(&RFC_KEY, &[&message]), expected);
+    // Split anywhere, the parts are one message
+    assert_eq!(
+        diversify::cmac(&RFC_KEY, &[&message[..5], &message[5..]]),
+        expected
+    );
+}
+
+#[test]
+fn keys_match_the_vectors() {
+    assert_eq!(
+        diversify::derive(&MASTER_KEY, &UID),
+        CardKeys {
+            key_a: [0xD7, 0xE1, 0x05, 0xF1, 0xE8, 0x01],
+            key_b: [0x88, 0x6B, 0xC5, 0x69, 0xC2, 0xD2],
+        }
+    );
+    // A 7 byte UID
+    assert_eq!(
+        diversify::derive(&MASTER_KEY, &[0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66]),
+        CardKeys {
+            key_a: [0xB5, 0x72, 0x6D, 0x5D, 0x30, 0xDD],
+            key_b: [0x8C, 0xAD, 0xD8, 0x88, 0x6F, 0x42],
+        }
+    );
+}
+
+#[test]
+fn every_card_gets_its_own_keys() {
+    let keys = diversify::derive(&MASTER_KEY, &UID);
+    assert_ne!(keys.key_a, keys.key_b);
+    assert_ne!(keys, diversify::derive(&MASTER_KEY, &OTHER_UID));
+    assert_ne!(keys, diversify::derive(b"another master!!", &UID));
+}
+
+#[test]
+fn derived_key_opens_only_its_card() {
+    let sim = Simulator::new();
+    sim.insert(Card::classic_1k(UID));
+    let mut rfid = Mfrc522::new(SpiInterface::new(sim.clone())).init().unwrap();
+    let atqa = rfid.reqa().unwrap();
+    let uid = rfid.select(&atqa).unwrap();
+
+    // Key A, transport access bits and key B, as change-key writes them
+    let keys = diversify::derive(&MASTER_KEY, uid.as_bytes());
+    let mut trailer = [
+        0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x80, 0x69, 0, 0, 0, 0, 0, 0,
+    ];
+    trailer[..6].copy_from_slice(&keys.key_a);
+    trailer[10..].copy_from_slice(&keys.key_b);
+    let mut transaction = Transaction::new(1, [0xFF; 6]).write(3, trailer);
+    transaction.commit(&uid, &mut rfid).unwrap();
+    assert_eq!(sim.card().unwrap().block(7), trailer);
+
+    // Tapped again, the keys come back from the UID alone
+    rfid.hlta().unwrap();
+    rfid.stop_crypto1().unwrap();
+    let atqa = rfid.wupa().unwrap();
+    let uid = rfid.select(&atqa).unwrap();
+    let other = diversify::derive(&MASTER_KEY, &OTHER_UID);
+    assert!(rfid.mf_authenticate(&uid, 4, &other.key_a).is_err());
+
+    let atqa = rfid.wupa().unwrap();
+    let uid = rfid.select(&atqa).unwrap();
+    let keys = diversify::derive(&MASTER_KEY, uid.as_bytes());
+    rfid.mf_authenticate(&uid, 4, &keys.key_a).unwrap();
+    assert_eq!(rfid.mf_read(4).unwrap(), [0; 16]);
+}
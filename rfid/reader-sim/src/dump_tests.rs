@@ -0,0 +1,210 @@
+//! The dump of memory-dump-usb, from select to the last sector.
+
+use mfrc522::Mfrc522;
+use mfrc522::comm::blocking::spi::{DummyDelay, SpiInterface};
+
+use crate::card::{self, Card as SelectedCard};
+use crate::card_type::{self, CardType};
+use crate::dump::{Dump, dump_memory};
+use crate::error::Error;
+use crate::frame::{STATUS_LOCKED, STATUS_OK, STATUS_READ_FAILED};
+use crate::keys::{KeySelect, KeyType, SectorKey};
+use crate::{Card, Simulator};
+
+type Reader = Mfrc522<SpiInterface<KeySelect<Simulator>, DummyDelay>, mfrc522::Initialized>;
+
+const UID: [u8; 4] = [0xDE, 0xAD, 0xBE, 0xEF];
+const FACTORY_KEY: SectorKey = SectorKey {
+    key_type: KeyType::A,
+    key: [0xFF; 6],
+};
+
+fn reader(card: Card) -> (Simulator, Reader) {
+    let sim = Simulator::new();
+    sim.insert(card);
+    let rfid = Mfrc522::new(SpiInterface::new(KeySelect::new(sim.clone())))
+        .init()
+        .unwrap();
+    (sim, rfid)
+}
+
+fn select(rfid: &mut Reader) -> SelectedCard {
+    card::activate(rfid, false).unwrap()
+}
+
+fn dump(card: Card) -> (Simulator, Dump) {
+    let (sim, mut rfid) = reader(card);
+    let selected = select(&mut rfid);
+    let dump = dump_memory(selected, &mut rfid).unwrap();
+    (sim, dump)
+}
+
+/// What a dump of the card should hold: everything but key A
+fn expected_image(card: &Card) -> Vec<u8> {
+    let mut image = card.image();
+    for sector in 0..card.sectors() {
+        let trailer = card_type::sector_offset(sector + 1) - card_type::BLOCK_SIZE;
+        image[trailer..trailer + 6].fill(0);
+    }
+    image
+}
+
+fn pattern(block: u8) -> [u8; 16] {
+    core::array::from_fn(|i| block.wrapping_add(i as u8 * 0x11))
+}
+
+/// A card with every data block filled with its own pattern
+fn filled(mut card: Card) -> Card {
+    let last = card_type::trailer_block(card.sectors() - 1);
+    for block in 1..=last {
+        if !card_type::is_trailer(block) {
+            card.set_block(block, pattern(block));
+        }
+    }
+    card
+}
+
+#[test]
+fn select_reads_atqa_and_sak() {
+    let (_, mut rfid) = reader(Card::classic_4k(UID));
+    let card = select(&mut rfid);
+    assert_eq!(card.uid.as_bytes(), UID);
+    assert_eq!(card.atqa, [0x02, 0x00]);
+    assert_eq!(card.sak, 0x18);
+    assert_eq!(
+        CardType::identify(card.atqa, card.sak),
+        CardType::MifareClassic4K
+    );
+}
+
+#[test]
+fn no_card_no_select() {
+    let sim = Simulator::new();
+    let mut rfid = Mfrc522::new(SpiInterface::new(KeySelect::new(sim)))
+        .init()
+        .unwrap();
+    assert!(card::activate(&mut rfid, false).is_err());
+}
+
+#[test]
+fn factory_1k_card() {
+    let card = filled(Card::classic_1k(UID));
+    let (_, dump) = dump(card.clone());
+
+    assert_eq!(dump.card_type, CardType::MifareClassic1K);
+    assert_eq!(dump.sectors, 16);
+    assert!(dump.status[..16].iter().all(|&s| s == STATUS_OK));
+    assert!(dump.keys[..16].iter().all(|&k| k == Some(FACTORY_KEY)));
+    assert_eq!(dump.image[..1024], expected_image(&card)[..]);
+}
+
+#[test]
+fn mini_card() {
+    let card = filled(Card::mini(UID));
+    let (_, dump) = dump(card.clone());
+    assert_eq!(dump.sectors, 5);
+    assert_eq!(dump.image[..320], expected_image(&card)[..]);
+    assert!(dump.image[320..].iter().all(|&b| b == 0));
+}
+
+#[test]
+fn large_sectors_of_a_4k_card() {
+    let card = filled(Card::classic_4k(UID));
+    let (_, dump) = dump(card.clone());
+
+    assert_eq!(dump.sectors, 40);
+    assert!(dump.status[..40].iter().all(|&s| s == STATUS_OK));
+    assert_eq!(dump.image[..], expected_image(&card)[..]);
+    // Sector 36 starts at block 192
+    assert_eq!(dump.sector(36)[8 * 16..9 * 16], pattern(200));
+}
+
+#[test]
+fn key_b_through_key_select() {
+    let mut card = filled(Card::classic_1k(UID));
+    // Key A unknown, key B from the dictionary and not readable, so it
+    // gives access to the data blocks
+    card.set_trailer(5, [0x12; 6], [0b000, 0b000, 0b000, 0b011], *b"Ferris");
+    let (_, dump) = dump(card.clone());
+
+    assert_eq!(
+        dump.keys[5],
+        Some(SectorKey {
+            key_type: KeyType::B,
+            key: *b"Ferris"
+        })
+    );
+    assert_eq!(dump.status[5], STATUS_OK);
+    assert_eq!(dump.sector(5)[..48], card.image()[5 * 64..5 * 64 + 48]);
+    // Key B isn't readable, both keys read back as zeros
+    let trailer = &dump.sector(5)[48..];
+    assert_eq!(trailer[..6], [0; 6]);
+    assert_eq!(trailer[10..], [0; 6]);
+}
+
+#[test]
+fn locked_sector_is_skipped() {
+    let mut card = filled(Card::classic_1k(UID));
+    card.set_trailer(3, [0x12; 6], [0b000, 0b000, 0b000, 0b001], [0x34; 6]);
+    let (_, dump) = dump(card);
+
+    assert_eq!(dump.keys[3], None);
+    assert_eq!(dump.status[3], STATUS_LOCKED);
+    assert!(dump.sector(3).iter().all(|&b| b == 0));
+    // Every failed try halts the card, the sectors after are still read
+    assert!(dump.status[4..16].iter().all(|&s| s == STATUS_OK));
+    assert_eq!(dump.sector(4)[..16], pattern(16));
+}
+
+#[test]
+fn unreadable_block_fails_the_sector() {
+    let mut card = filled(Card::classic_1k(UID));
+    // Block 9 can't be read with any key
+    card.set_trailer(2, [0xFF; 6], [0b000, 0b111, 0b000, 0b001], [0xFF; 6]);
+    let (_, dump) = dump(card);
+
+    assert_eq!(dump.keys[2], Some(FACTORY_KEY));
+    assert_eq!(dump.status[2], STATUS_READ_FAILED);
+    assert!(dump.sector(2).iter().all(|&b| b == 0));
+    assert_eq!(dump.status[3], STATUS_OK);
+}
+
+#[test]
+fn card_removed_mid_dump() {
+    let (sim, mut rfid) = reader(filled(Card::classic_1k(UID)));
+    let selected = select(&mut rfid);
+    // Authenticating and reading a sector is 5 frames, this is gone in sector 3
+    sim.remove_after(17);
+
+    assert_eq!(
+        dump_memory(selected, &mut rfid).err(),
+        Some(Error::CardLost)
+    );
+}
+
+#[test]
+fn other_card_in_the_field_is_not_dumped() {
+    let (sim, mut rfid) = reader(filled(Card::classic_1k(UID)));
+    let selected = select(&mut rfid);
+    // Swapped before the first sector, the new card answers the reselect
+    sim.insert(filled(Card::classic_1k([0x13, 0x37, 0x73, 0x31])));
+
+    assert_eq!(
+        dump_memory(selected, &mut rfid).err(),
+        Some(Error::CardLost)
+    );
+}
+
+#[test]
+fn glitch_mid_dump_is_read_again() {
+    let card = filled(Card::classic_1k(UID));
+    let (sim, mut rfid) = reader(card.clone());
+    let selected = select(&mut rfid);
+    // Authenticating and reading a sector is 5 frames, this is the second
+    // block of sector 2
+    sim.glitch_after(12);
+    let dump = dump_memory(selected, &mut rfid).unwrap();
+
+    assert!(dump.status[..16].iter().all(|&s| s == STATUS_OK));
+    assert_eq!(dump.image[..1024], expected_image(&card)[..]);
+}
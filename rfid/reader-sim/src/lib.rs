@@ -0,0 +1,415 @@
+//! A simulated MFRC522 with a virtual MIFARE Classic card, to run the rfid
+//! firmware code on the host.
+//!
+//! The mfrc522 crate keeps its `Register` type private, so its `Interface`
+//! trait can't be implemented outside of it. The simulator sits one level
+//! lower instead and answers SPI transactions the way the chip does, so the
+//! driver is used through its own `SpiInterface`, and SPI wrappers like the
+//! `KeySelect` of memory-dump-usb are part of what gets tested.
+//!
+//! Only what the firmwares use is simulated: the FIFO, the interrupt flags,
+//! the CRC coprocessor, and the Transceive and MFAuthent commands. Timing
+//! isn't, a card that doesn't answer sets the timer interrupt right away.
+//!
+//! ```
+//! use mfrc522::Mfrc522;
+//! use mfrc522::comm::blocking::spi::SpiInterface;
+//! use reader_sim::{Card, Simulator};
+//!
+//! let sim = Simulator::new();
+//! sim.insert(Card::classic_1k([0xDE, 0xAD, 0xBE, 0xEF]));
+//! let mut rfid = Mfrc522::new(SpiInterface::new(sim.clone())).init().unwrap();
+//!
+//! let atqa = rfid.reqa().unwrap();
+//! let uid = rfid.select(&atqa).unwrap();
+//! assert_eq!(uid.as_bytes(), [0xDE, 0xAD, 0xBE, 0xEF]);
+//! ```
+
+mod card_access;
+// The same CRC the firmwares put on their frames
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+mod picc;
+
+// The firmware modules under test, at the paths they have in their crates
+#[cfg(test)]
+#[path = "../../change-key/src/access.rs"]
+#[allow(dead_code)]
+mod access;
+#[cfg(test)]
+#[path = "../../memory-dump-usb/src/card.rs"]
+mod card;
+#[cfg(test)]
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[cfg(test)]
+#[path = "../../change-key/src/diversify.rs"]
+mod diversify;
+#[cfg(test)]
+mod diversify_tests;
+#[cfg(test)]
+#[path = "../../memory-dump-usb/src/dump.rs"]
+mod dump;
+#[cfg(test)]
+mod dump_tests;
+#[cfg(test)]
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+#[cfg(test)]
+#[path = "../../memory-dump-usb/src/frame.rs"]
+#[allow(dead_code)]
+mod frame;
+#[cfg(test)]
+#[path = "../../memory-dump-usb/src/keys.rs"]
+mod keys;
+#[cfg(test)]
+#[path = "../../memory-dump-usb/src/mifare.rs"]
+mod mifare;
+#[cfg(test)]
+#[path = "../../restore-card/src/restore.rs"]
+#[allow(dead_code)]
+mod restore;
+#[cfg(test)]
+mod restore_tests;
+#[cfg(test)]
+#[path = "../../write-data/src/sector.rs"]
+mod sector;
+#[cfg(test)]
+mod sector_tests;
+#[cfg(test)]
+#[path = "../../write-data/src/transaction.rs"]
+#[allow(dead_code)]
+mod transaction;
+#[cfg(test)]
+mod transaction_tests;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use embedded_hal::spi::{ErrorType, Operation, SpiDevice};
+
+use crate::crc::crc_a;
+
+use picc::Answer;
+pub use picc::{Card, KeyType};
+
+// Registers
+const COMMAND: u8 = 0x01;
+const COM_IRQ: u8 = 0x04;
+const DIV_IRQ: u8 = 0x05;
+const ERROR: u8 = 0x06;
+const STATUS2: u8 = 0x08;
+const FIFO_DATA: u8 = 0x09;
+const FIFO_LEVEL: u8 = 0x0A;
+const CONTROL: u8 = 0x0C;
+const BIT_FRAMING: u8 = 0x0D;
+const TX_CONTROL: u8 = 0x14;
+const CRC_RESULT_HIGH: u8 = 0x21;
+const CRC_RESULT_LOW: u8 = 0x22;
+const VERSION: u8 = 0x37;
+
+// Commands
+const IDLE: u8 = 0x00;
+const CALC_CRC: u8 = 0x03;
+const TRANSCEIVE: u8 = 0x0C;
+const MF_AUTHENT: u8 = 0x0E;
+const SOFT_RESET: u8 = 0x0F;
+
+// Bits
+const TIMER_IRQ: u8 = 1 << 0;
+const IDLE_IRQ: u8 = 1 << 4;
+const RX_IRQ: u8 = 1 << 5;
+const CRC_IRQ: u8 = 1 << 2;
+/// Set in the IRQ registers to set the bits written instead of clearing them
+const SET_BITS: u8 = 1 << 7;
+const BUFFER_OVFL: u8 = 1 << 4;
+const MF_CRYPTO1_ON: u8 = 1 << 3;
+const FLUSH_BUFFER: u8 = 1 << 7;
+const START_SEND: u8 = 1 << 7;
+/// Both antenna drivers on
+const ANTENNA_ON: u8 = 0b11;
+
+const FIFO_SIZE: usize = 64;
+/// What the VersionReg of an MFRC522 v2.0 reads
+const CHIP_VERSION: u8 = 0x92;
+
+struct Chip {
+    registers: [u8; 64],
+    fifo: VecDeque<u8>,
+    card: Option<Card>,
+    /// The card is out of the field, but kept to look at its memory
+    removed: bool,
+    /// Frames the card answers before it leaves the field
+    frames_left: Option<usize>,
+    /// Frames before one whose answer noise corrupts
+    glitch_in: Option<usize>,
+    /// The answer to the frame being handled is corrupted
+    glitch: bool,
+}
+
+impl Default for Chip {
+    fn default() -> Self {
+        Chip {
+            registers: [0; 64],
+            fifo: VecDeque::new(),
+            card: None,
+            removed: false,
+            frames_left: None,
+            glitch_in: None,
+            glitch: false,
+        }
+    }
+}
+
+/// The simulated reader, handed to the driver as its SPI device
+///
+/// Clones share the same chip and card, so the test keeps one to look at
+/// the card after the driver has used it.
+#[derive(Clone, Default)]
+pub struct Simulator {
+    chip: Rc<RefCell<Chip>>,
+}
+
+impl Simulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put a card in the field
+    pub fn insert(&self, card: Card) {
+        let mut chip = self.chip.borrow_mut();
+        chip.card = Some(card);
+        chip.removed = false;
+        chip.frames_left = None;
+    }
+
+    /// Take the card out of the field
+    pub fn remove(&self) {
+        let mut chip = self.chip.borrow_mut();
+        chip.removed = true;
+        if let Some(card) = &mut chip.card {
+            card.power_off();
+        }
+    }
+
+    /// Take the card out of the field after it has answered `frames` more
+    /// frames, counting authentications
+    pub fn remove_after(&self, frames: usize) {
+        self.chip.borrow_mut().frames_left = Some(frames);
+    }
+
+    /// Corrupt the answer to the frame after the next `frames`, counting
+    /// authentications, as noise in the field would. The card doesn't
+    /// notice, the reader gets a wrong CRC.
+    pub fn glitch_after(&self, frames: usize) {
+        self.chip.borrow_mut().glitch_in = Some(frames);
+    }
+
+    /// The card, in or out of the field
+    pub fn card(&self) -> Option<Card> {
+        self.chip.borrow().card.clone()
+    }
+}
+
+impl ErrorType for Simulator {
+    type Error = Infallible;
+}
+
+impl SpiDevice for Simulator {
+    fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Infallible> {
+        let mut chip = self.chip.borrow_mut();
+        // The first byte is an address, then each byte either is the next
+        // address of a read or data for the register being written
+        let mut address = None;
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => {
+                    for &byte in bytes.iter() {
+                        chip.spi_byte(&mut address, byte);
+                    }
+                }
+                Operation::Read(bytes) => {
+                    for byte in bytes.iter_mut() {
+                        *byte = chip.spi_byte(&mut address, 0);
+                    }
+                }
+                Operation::TransferInPlace(bytes) => {
+                    for byte in bytes.iter_mut() {
+                        *byte = chip.spi_byte(&mut address, *byte);
+                    }
+                }
+                Operation::Transfer(read, write) => {
+                    for i in 0..read.len().max(write.len()) {
+                        let miso = chip.spi_byte(&mut address, write.get(i).copied().unwrap_or(0));
+                        if let Some(byte) = read.get_mut(i) {
+                            *byte = miso;
+                        }
+                    }
+                }
+                Operation::DelayNs(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Chip {
+    /// Clock one byte in on MOSI, returning the one out on MISO
+    fn spi_byte(&mut self, address: &mut Option<u8>, mosi: u8) -> u8 {
+        let Some(current) = *address else {
+            *address = Some(mosi);
+            return 0;
+        };
+        let register = (current >> 1) & 0x3F;
+        if current & 0x80 != 0 {
+            *address = Some(mosi);
+            self.read(register)
+        } else {
+            self.write(register, mosi);
+            0
+        }
+    }
+
+    fn read(&mut self, register: u8) -> u8 {
+        match register {
+            FIFO_DATA => self.fifo.pop_front().unwrap_or(0),
+            FIFO_LEVEL => self.fifo.len() as u8,
+            VERSION => CHIP_VERSION,
+            register => self.registers[register as usize],
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        match register {
+            COMMAND => self.command(value & 0x0F),
+            COM_IRQ | DIV_IRQ => {
+                let flags = &mut self.registers[register as usize];
+                if value & SET_BITS != 0 {
+                    *flags |= value & !SET_BITS;
+                } else {
+                    *flags &= !value;
+                }
+            }
+            FIFO_DATA => {
+                if self.fifo.len() < FIFO_SIZE {
+                    self.fifo.push_back(value);
+                } else {
+                    self.registers[ERROR as usize] |= BUFFER_OVFL;
+                }
+            }
+            FIFO_LEVEL => {
+                if value & FLUSH_BUFFER != 0 {
+                    self.fifo.clear();
+                    self.registers[ERROR as usize] &= !BUFFER_OVFL;
+                }
+            }
+            BIT_FRAMING => {
+                self.registers[BIT_FRAMING as usize] = value & !START_SEND;
+                if value & START_SEND != 0 && self.registers[COMMAND as usize] == TRANSCEIVE {
+                    self.transceive(value & 0x07);
+                }
+            }
+            TX_CONTROL => {
+                self.registers[TX_CONTROL as usize] = value;
+                if value & ANTENNA_ON == 0
+                    && let Some(card) = &mut self.card
+                {
+                    card.power_off();
+                }
+            }
+            register => self.registers[register as usize] = value,
+        }
+    }
+
+    fn command(&mut self, command: u8) {
+        self.registers[COMMAND as usize] = command;
+        match command {
+            CALC_CRC => {
+                let data: Vec<u8> = self.fifo.drain(..).collect();
+                let [low, high] = crc_a(&data);
+                self.registers[CRC_RESULT_LOW as usize] = low;
+                self.registers[CRC_RESULT_HIGH as usize] = high;
+                self.registers[DIV_IRQ as usize] |= CRC_IRQ;
+            }
+            MF_AUTHENT => {
+                let data: Vec<u8> = self.fifo.drain(..).collect();
+                self.registers[ERROR as usize] = 0;
+                let authenticated = self
+                    .card_in_field()
+                    .is_some_and(|card| card.authenticate(&data));
+                if authenticated {
+                    self.registers[STATUS2 as usize] |= MF_CRYPTO1_ON;
+                    self.registers[COM_IRQ as usize] |= IDLE_IRQ;
+                    self.registers[COMMAND as usize] = IDLE;
+                } else {
+                    // The card stops answering, the timer runs out
+                    self.registers[COM_IRQ as usize] |= TIMER_IRQ;
+                }
+            }
+            SOFT_RESET => {
+                self.registers = [0; 64];
+                self.fifo.clear();
+                if let Some(card) = &mut self.card {
+                    card.power_off();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The card, if it's in the field and powered, counting the frame it
+    /// is about to get
+    fn card_in_field(&mut self) -> Option<&mut Card> {
+        self.glitch = self.glitch_in == Some(0);
+        self.glitch_in = match self.glitch_in {
+            Some(0) | None => None,
+            Some(frames) => Some(frames - 1),
+        };
+        if self.registers[TX_CONTROL as usize] & ANTENNA_ON == 0 || self.removed {
+            return None;
+        }
+        match self.frames_left {
+            Some(0) => {
+                self.removed = true;
+                if let Some(card) = &mut self.card {
+                    card.power_off();
+                }
+                return None;
+            }
+            Some(frames) => self.frames_left = Some(frames - 1),
+            None => {}
+        }
+        self.card.as_mut()
+    }
+
+    fn transceive(&mut self, last_bits: u8) {
+        let frame: Vec<u8> = self.fifo.drain(..).collect();
+        let crypto1 = self.registers[STATUS2 as usize] & MF_CRYPTO1_ON != 0;
+        self.registers[ERROR as usize] = 0;
+
+        let answer = match self.card_in_field() {
+            Some(card) => card.receive(&frame, last_bits, crypto1),
+            None => Answer::Silent,
+        };
+        match answer {
+            Answer::Silent => self.registers[COM_IRQ as usize] |= TIMER_IRQ,
+            Answer::Bytes(mut bytes) => {
+                if self.glitch {
+                    bytes[0] ^= 0x01;
+                }
+                self.fifo.extend(bytes);
+                self.registers[CONTROL as usize] = 0;
+                self.registers[COM_IRQ as usize] |= RX_IRQ;
+            }
+            Answer::Nibble(nibble) => {
+                self.fifo.push_back(nibble);
+                self.registers[CONTROL as usize] = 4;
+                self.registers[COM_IRQ as usize] |= RX_IRQ;
+            }
+        }
+    }
+}
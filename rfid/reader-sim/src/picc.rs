@@ -0,0 +1,406 @@
+//! A virtual MIFARE Classic card.
+//!
+//! The card goes through the ISO 14443-3 states (idle, ready, active, halt),
+//! answers anticollision and select with its 4 byte UID, and checks keys and
+//! access bits the way a real card does.
+//!
+//! Crypto1 runs between the MFRC522 and the card, so the firmware never sees
+//! it. The frames the card gets here are in the clear: authentication only
+//! compares the key, and the reader's MFCrypto1On bit stands in for the
+//! cipher. A card that was authenticated and gets a frame without it falls
+//! back to idle, like a real one that can't decrypt it.
+
+use crate::card_access;
+use crate::crc::crc_a;
+
+pub const BLOCK_SIZE: usize = 16;
+
+const REQA: u8 = 0x26;
+const WUPA: u8 = 0x52;
+const SEL_CL1: u8 = 0x93;
+/// NVB of an anticollision frame with no UID bits yet, and of a select
+const NVB_ANTICOLLISION: u8 = 0x20;
+const NVB_SELECT: u8 = 0x70;
+const HLTA: u8 = 0x50;
+const MF_AUTH_KEY_A: u8 = 0x60;
+const MF_AUTH_KEY_B: u8 = 0x61;
+const MF_READ: u8 = 0x30;
+const MF_WRITE: u8 = 0xA0;
+
+/// 4 bit answers to a command
+pub const ACK: u8 = 0x0A;
+pub const NAK: u8 = 0x04;
+
+/// Trailer of a card fresh from the factory: key A and key B all `FF`,
+/// access bits `FF 07 80` and user byte `69`
+const TRANSPORT_TRAILER: [u8; BLOCK_SIZE] = [
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x80, 0x69, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Ready,
+    Active,
+    Halt,
+}
+
+/// What the card sends back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    /// Nothing, the reader times out
+    Silent,
+    Bytes(Vec<u8>),
+    /// A 4 bit ACK or NAK
+    Nibble(u8),
+}
+
+#[derive(Debug, Clone)]
+pub struct Card {
+    uid: [u8; 4],
+    atqa: [u8; 2],
+    sak: u8,
+    blocks: Vec<[u8; BLOCK_SIZE]>,
+    state: State,
+    /// Sector and key of the last authentication
+    auth: Option<(u8, KeyType)>,
+    /// Block waiting for the second part of a WRITE
+    pending_write: Option<u8>,
+}
+
+impl Card {
+    /// A MIFARE Classic 1K with 16 sectors, as it comes from the factory
+    pub fn classic_1k(uid: [u8; 4]) -> Self {
+        Self::new(uid, [0x04, 0x00], 0x08, 16)
+    }
+
+    /// A MIFARE Classic 4K with 32 small and 8 large sectors
+    pub fn classic_4k(uid: [u8; 4]) -> Self {
+        Self::new(uid, [0x02, 0x00], 0x18, 40)
+    }
+
+    /// A MIFARE Mini with 5 sectors
+    pub fn mini(uid: [u8; 4]) -> Self {
+        Self::new(uid, [0x04, 0x00], 0x09, 5)
+    }
+
+    fn new(uid: [u8; 4], atqa: [u8; 2], sak: u8, sectors: u8) -> Self {
+        let blocks = first_block(sectors) as usize;
+        let mut card = Card {
+            uid,
+            atqa,
+            sak,
+            blocks: vec![[0; BLOCK_SIZE]; blocks],
+            state: State::Idle,
+            auth: None,
+            pending_write: None,
+        };
+
+        // Manufacturer block: UID, BCC, SAK, ATQA and the rest of the
+        // manufacturer data
+        let block0 = &mut card.blocks[0];
+        block0[..4].copy_from_slice(&uid);
+        block0[4] = bcc(&uid);
+        block0[5] = sak;
+        block0[6..8].copy_from_slice(&atqa);
+        for sector in 0..sectors {
+            card.blocks[trailer_block(sector) as usize] = TRANSPORT_TRAILER;
+        }
+        card
+    }
+
+    pub fn uid(&self) -> [u8; 4] {
+        self.uid
+    }
+
+    pub fn sectors(&self) -> u8 {
+        (0..=40)
+            .find(|&sector| first_block(sector) as usize == self.blocks.len())
+            .unwrap()
+    }
+
+    /// The whole memory in `.mfd` layout, keys included
+    pub fn image(&self) -> Vec<u8> {
+        self.blocks.concat()
+    }
+
+    pub fn block(&self, block: u8) -> [u8; BLOCK_SIZE] {
+        self.blocks[block as usize]
+    }
+
+    /// Change a block directly, without keys or access bits
+    pub fn set_block(&mut self, block: u8, data: [u8; BLOCK_SIZE]) {
+        self.blocks[block as usize] = data;
+    }
+
+    /// Change the keys and access conditions of a sector
+    ///
+    /// `conditions` are those of the three data groups and the trailer, each
+    /// written as `0bC1C2C3`.
+    pub fn set_trailer(&mut self, sector: u8, key_a: [u8; 6], conditions: [u8; 4], key_b: [u8; 6]) {
+        let trailer = &mut self.blocks[trailer_block(sector) as usize];
+        trailer[..6].copy_from_slice(&key_a);
+        trailer[6..9].copy_from_slice(&card_access::encode(conditions));
+        trailer[10..].copy_from_slice(&key_b);
+    }
+
+    /// Back to idle, as when the card leaves the field or the reader resets
+    pub fn power_off(&mut self) {
+        self.state = State::Idle;
+        self.auth = None;
+        self.pending_write = None;
+    }
+
+    /// Handle a frame, `last_bits` being the number of bits sent of its last
+    /// byte (0 for all of them) and `crypto1` the reader's MFCrypto1On bit
+    pub fn receive(&mut self, frame: &[u8], last_bits: u8, crypto1: bool) -> Answer {
+        if self.auth.is_some() && !crypto1 {
+            self.power_off();
+            return Answer::Silent;
+        }
+
+        // REQA and WUPA are 7 bit short frames
+        if last_bits == 7 && frame.len() == 1 {
+            return match (frame[0], self.state) {
+                (REQA, State::Idle) | (WUPA, State::Idle | State::Halt) => {
+                    self.state = State::Ready;
+                    Answer::Bytes(self.atqa.to_vec())
+                }
+                _ => Answer::Silent,
+            };
+        }
+
+        match self.state {
+            State::Ready => self.select(frame),
+            State::Active => self.command(frame),
+            State::Idle | State::Halt => Answer::Silent,
+        }
+    }
+
+    fn select(&mut self, frame: &[u8]) -> Answer {
+        match frame {
+            [SEL_CL1, NVB_ANTICOLLISION] => {
+                let mut answer = self.uid.to_vec();
+                answer.push(bcc(&self.uid));
+                Answer::Bytes(answer)
+            }
+            [SEL_CL1, NVB_SELECT, uid @ .., check, _, _]
+                if check_crc(frame) && uid == self.uid && *check == bcc(&self.uid) =>
+            {
+                self.state = State::Active;
+                Answer::Bytes(with_crc(&[self.sak]))
+            }
+            _ => {
+                self.power_off();
+                Answer::Silent
+            }
+        }
+    }
+
+    fn command(&mut self, frame: &[u8]) -> Answer {
+        if !check_crc(frame) {
+            return Answer::Silent;
+        }
+        let frame = &frame[..frame.len() - 2];
+
+        if let Some(block) = self.pending_write.take() {
+            return match frame.try_into() {
+                Ok(data) => {
+                    self.write(block, data);
+                    Answer::Nibble(ACK)
+                }
+                Err(_) => self.nak(),
+            };
+        }
+
+        match *frame {
+            [HLTA, 0x00] => {
+                self.state = State::Halt;
+                self.auth = None;
+                Answer::Silent
+            }
+            [MF_READ, block] => match self.read(block) {
+                Some(data) => Answer::Bytes(with_crc(&data)),
+                None => self.nak(),
+            },
+            [MF_WRITE, block] if self.may_write(block) => {
+                self.pending_write = Some(block);
+                Answer::Nibble(ACK)
+            }
+            _ => self.nak(),
+        }
+    }
+
+    /// Refuse a command, which sends the card back to idle
+    fn nak(&mut self) -> Answer {
+        self.power_off();
+        Answer::Nibble(NAK)
+    }
+
+    /// Check an MFAuthent command: key type, block, key and the UID
+    pub fn authenticate(&mut self, command: &[u8]) -> bool {
+        if self.state != State::Active {
+            return false;
+        }
+        let (key_type, block, key, uid) = match command {
+            [MF_AUTH_KEY_A, block, key @ .., _, _, _, _] if key.len() == 6 => {
+                (KeyType::A, *block, key, &command[8..])
+            }
+            [MF_AUTH_KEY_B, block, key @ .., _, _, _, _] if key.len() == 6 => {
+                (KeyType::B, *block, key, &command[8..])
+            }
+            _ => {
+                self.power_off();
+                return false;
+            }
+        };
+
+        if block as usize >= self.blocks.len() {
+            self.power_off();
+            return false;
+        }
+        let sector = sector_of(block);
+        let trailer = self.blocks[trailer_block(sector) as usize];
+        let expected = match key_type {
+            KeyType::A => &trailer[..6],
+            KeyType::B => &trailer[10..],
+        };
+        if key != expected || uid != self.uid {
+            self.power_off();
+            return false;
+        }
+        self.auth = Some((sector, key_type));
+        true
+    }
+
+    /// The conditions of a sector, `None` if its access bits are broken
+    fn conditions(&self, sector: u8) -> Option<[u8; 4]> {
+        let trailer = self.blocks[trailer_block(sector) as usize];
+        card_access::decode([trailer[6], trailer[7], trailer[8]])
+    }
+
+    /// The key the sector was opened with, if it's the sector of `block`
+    /// and the key gives any access at all
+    fn key_for(&self, block: u8) -> Option<(KeyType, [u8; 4])> {
+        let (sector, key_type) = self.auth?;
+        if block as usize >= self.blocks.len() || sector_of(block) != sector {
+            return None;
+        }
+        let conditions = self.conditions(sector)?;
+        if key_type == KeyType::B && card_access::key_b_readable(conditions[3]) {
+            return None;
+        }
+        Some((key_type, conditions))
+    }
+
+    fn read(&self, block: u8) -> Option<[u8; BLOCK_SIZE]> {
+        let (key_type, conditions) = self.key_for(block)?;
+        let mut data = self.blocks[block as usize];
+
+        if is_trailer(block) {
+            let trailer = card_access::trailer(conditions[3]);
+            // Key A never reads back
+            data[..6].fill(0);
+            if !trailer.access_bits_read.allows(key_type) {
+                data[6..10].fill(0);
+            }
+            if !trailer.key_b_read.allows(key_type) {
+                data[10..].fill(0);
+            }
+            return Some(data);
+        }
+
+        let (read, _) = card_access::data(conditions[group(block)]);
+        read.allows(key_type).then_some(data)
+    }
+
+    fn may_write(&self, block: u8) -> bool {
+        // The manufacturer block is read only
+        if block == 0 {
+            return false;
+        }
+        let Some((key_type, conditions)) = self.key_for(block) else {
+            return false;
+        };
+        if is_trailer(block) {
+            // Checked part by part when the data comes
+            return true;
+        }
+        let (_, write) = card_access::data(conditions[group(block)]);
+        write.allows(key_type)
+    }
+
+    fn write(&mut self, block: u8, data: [u8; BLOCK_SIZE]) {
+        if !is_trailer(block) {
+            self.blocks[block as usize] = data;
+            return;
+        }
+
+        // Each part of a trailer is only written if the key may change it
+        let Some((key_type, conditions)) = self.key_for(block) else {
+            return;
+        };
+        let trailer = card_access::trailer(conditions[3]);
+        let stored = &mut self.blocks[block as usize];
+        let parts = [
+            (0..6, trailer.key_a_write),
+            (6..10, trailer.access_bits_write),
+            (10..16, trailer.key_b_write),
+        ];
+        for (range, access) in parts {
+            if access.allows(key_type) {
+                stored[range.clone()].copy_from_slice(&data[range]);
+            }
+        }
+    }
+}
+
+fn bcc(uid: &[u8; 4]) -> u8 {
+    uid[0] ^ uid[1] ^ uid[2] ^ uid[3]
+}
+
+fn with_crc(data: &[u8]) -> Vec<u8> {
+    let mut frame = data.to_vec();
+    frame.extend(crc_a(data));
+    frame
+}
+
+fn check_crc(frame: &[u8]) -> bool {
+    frame.len() > 2 && crc_a(&frame[..frame.len() - 2]) == frame[frame.len() - 2..]
+}
+
+/// Sectors 0 to 31 have 4 blocks, the ones after have 16
+fn first_block(sector: u8) -> u16 {
+    match sector {
+        0..32 => sector as u16 * 4,
+        _ => 128 + (sector as u16 - 32) * 16,
+    }
+}
+
+fn sector_of(block: u8) -> u8 {
+    match block {
+        0..128 => block / 4,
+        _ => 32 + (block - 128) / 16,
+    }
+}
+
+fn trailer_block(sector: u8) -> u8 {
+    (first_block(sector + 1) - 1) as u8
+}
+
+fn is_trailer(block: u8) -> bool {
+    trailer_block(sector_of(block)) == block
+}
+
+/// Index of the access condition of a data block: one per block in small
+/// sectors, one per five blocks in large ones
+fn group(block: u8) -> usize {
+    let offset = block as usize - first_block(sector_of(block)) as usize;
+    if block < 128 { offset } else { offset / 5 }
+}
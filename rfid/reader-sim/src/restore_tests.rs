@@ -0,0 +1,135 @@
+//! The trailers restore-card writes and how it compares what it reads back.
+
+use crate::access::{AccessConditions, Condition, SectorTrailer};
+use crate::keys::{KeyType, SectorKey};
+use crate::restore::{self, BLOCK_SIZE, IMAGE_SIZE, Image, Outcome};
+
+const KEY_A: SectorKey = SectorKey {
+    key_type: KeyType::A,
+    key: [0xA0, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5],
+};
+const KEY_B: SectorKey = SectorKey {
+    key_type: KeyType::B,
+    key: [0xB0, 0xB1, 0xB2, 0xB3, 0xB4, 0xB5],
+};
+
+/// Key B readable, like the factory trailers (`FF 07 80`)
+const KEY_B_READABLE: AccessConditions = AccessConditions::TRANSPORT;
+/// Key B hidden and only key B can change the trailer (`7F 07 88`)
+const KEY_B_HIDDEN: AccessConditions = AccessConditions {
+    blocks: [Condition::new(false, false, false); 3],
+    trailer: Condition::new(false, true, true),
+};
+
+/// A trailer block as a dump holds it, key A read back as zeros
+fn dumped_trailer(access: AccessConditions, key_b: [u8; 6]) -> [u8; BLOCK_SIZE] {
+    let mut block = [0; BLOCK_SIZE];
+    block[6..9].copy_from_slice(&access.encode());
+    block[9] = 0x69;
+    block[10..].copy_from_slice(&key_b);
+    block
+}
+
+/// An image with only sector 1 in it
+fn image_with(trailer: [u8; BLOCK_SIZE]) -> [u8; IMAGE_SIZE] {
+    let mut image = [0; IMAGE_SIZE];
+    let start = restore::trailer_block(1) as usize * BLOCK_SIZE;
+    image[start..start + BLOCK_SIZE].copy_from_slice(&trailer);
+    image
+}
+
+#[test]
+fn data_block_differences() {
+    let expected = [0x42; BLOCK_SIZE];
+    let mut actual = expected;
+    assert_eq!(restore::diff(&expected, &actual, false), 0);
+
+    actual[0] = 0;
+    actual[15] = 0;
+    assert_eq!(restore::diff(&expected, &actual, false), 0x8001);
+}
+
+#[test]
+fn trailer_key_a_is_not_compared() {
+    let mut expected = dumped_trailer(KEY_B_READABLE, KEY_B.key);
+    expected[..6].copy_from_slice(&KEY_A.key);
+    // What the card gives back instead of key A
+    let actual = dumped_trailer(KEY_B_READABLE, KEY_B.key);
+    assert_eq!(restore::diff(&expected, &actual, true), 0);
+}
+
+#[test]
+fn readable_key_b_is_compared() {
+    let expected = dumped_trailer(KEY_B_READABLE, KEY_B.key);
+    let mut actual = expected;
+    actual[12] ^= 0xFF;
+    assert_eq!(restore::diff(&expected, &actual, true), 1 << 12);
+}
+
+#[test]
+fn hidden_key_b_is_not_compared() {
+    let expected = dumped_trailer(KEY_B_HIDDEN, KEY_B.key);
+    let mut actual = expected;
+    actual[10..].fill(0);
+    assert_eq!(restore::diff(&expected, &actual, true), 0);
+
+    // The access bits and user byte still are
+    actual[9] = 0;
+    assert_eq!(restore::diff(&expected, &actual, true), 1 << 9);
+}
+
+#[test]
+fn key_a_filled_in_when_opened_with_key_a() {
+    let data = image_with(dumped_trailer(KEY_B_READABLE, KEY_B.key));
+    let block = restore::trailer_to_write(&Image::new(&data), 1, &KEY_A).unwrap();
+
+    let trailer = SectorTrailer::parse(&block).unwrap();
+    assert_eq!(trailer.key_a, KEY_A.key);
+    assert_eq!(trailer.key_b, KEY_B.key);
+    assert_eq!(trailer.access, KEY_B_READABLE);
+    assert_eq!(trailer.user_byte, 0x69);
+}
+
+#[test]
+fn skipped_when_opened_with_key_b() {
+    let data = image_with(dumped_trailer(KEY_B_HIDDEN, KEY_B.key));
+    assert_eq!(
+        restore::trailer_to_write(&Image::new(&data), 1, &KEY_B),
+        Err(Outcome::Skipped("Key A unknown"))
+    );
+}
+
+#[test]
+fn known_key_a_is_kept() {
+    let mut trailer = dumped_trailer(KEY_B_HIDDEN, KEY_B.key);
+    trailer[..6].copy_from_slice(&[0x11; 6]);
+    let data = image_with(trailer);
+
+    // Either key opening the sector now, the image's key A is written
+    for key in [KEY_A, KEY_B] {
+        let block = restore::trailer_to_write(&Image::new(&data), 1, &key).unwrap();
+        assert_eq!(block, trailer);
+    }
+}
+
+#[test]
+fn sector_not_in_dump() {
+    let data = image_with(dumped_trailer(KEY_B_READABLE, KEY_B.key));
+    assert_eq!(
+        restore::trailer_to_write(&Image::new(&data), 2, &KEY_A),
+        Err(Outcome::NotInDump)
+    );
+}
+
+#[test]
+fn locking_trailer_is_skipped() {
+    let locked = AccessConditions {
+        blocks: [Condition::new(false, false, false); 3],
+        trailer: Condition::new(true, true, true),
+    };
+    let data = image_with(dumped_trailer(locked, KEY_B.key));
+    assert_eq!(
+        restore::trailer_to_write(&Image::new(&data), 1, &KEY_A),
+        Err(Outcome::Skipped("Trailer would lock the sector"))
+    );
+}
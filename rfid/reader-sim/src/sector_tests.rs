@@ -0,0 +1,149 @@
+//! The read and write helpers of write-data.
+
+use mfrc522::comm::blocking::spi::{DummyDelay, SpiInterface};
+use mfrc522::{Mfrc522, Uid};
+
+use crate::error::{DriverError, Error};
+use crate::sector::{read_sector, write_block};
+use crate::{Card, Simulator};
+
+type Reader = Mfrc522<SpiInterface<Simulator, DummyDelay>, mfrc522::Initialized>;
+
+const UID: [u8; 4] = [0x13, 0x37, 0xC0, 0xDE];
+const DATA: [u8; 16] = *b"implRust\0\0\0\0\0\0\0\0";
+
+/// A reader with the card in the field and selected
+fn reader(card: Card) -> (Simulator, Reader, Uid) {
+    let sim = Simulator::new();
+    sim.insert(card);
+    let mut rfid = Mfrc522::new(SpiInterface::new(sim.clone())).init().unwrap();
+    let atqa = rfid.reqa().unwrap();
+    let uid = rfid.select(&atqa).unwrap();
+    (sim, rfid, uid)
+}
+
+#[test]
+fn write_then_read_back() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+
+    write_block(&uid, 4, 2, DATA, &mut rfid).unwrap();
+    let blocks = read_sector(&uid, 4, &mut rfid).unwrap();
+
+    assert_eq!(blocks[2], DATA);
+    assert_eq!(blocks[0], [0; 16]);
+    assert_eq!(sim.card().unwrap().block(18), DATA);
+}
+
+#[test]
+fn trailer_hides_key_a() {
+    let (_, mut rfid, uid) = reader(Card::classic_1k(UID));
+    let trailer = read_sector(&uid, 1, &mut rfid).unwrap()[3];
+    assert_eq!(
+        trailer,
+        [
+            0, 0, 0, 0, 0, 0, 0xFF, 0x07, 0x80, 0x69, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF
+        ]
+    );
+}
+
+#[test]
+fn wrong_key_fails_auth() {
+    let mut card = Card::classic_1k(UID);
+    card.set_trailer(4, [0x42; 6], [0b000, 0b000, 0b000, 0b001], [0xFF; 6]);
+    let (sim, mut rfid, uid) = reader(card);
+
+    assert_eq!(
+        write_block(&uid, 4, 2, DATA, &mut rfid),
+        Err(Error::Auth {
+            sector: 4,
+            cause: DriverError::Timeout
+        })
+    );
+    assert_eq!(
+        read_sector(&uid, 4, &mut rfid),
+        Err(Error::Auth {
+            sector: 4,
+            cause: DriverError::Timeout
+        })
+    );
+    assert_eq!(sim.card().unwrap().block(18), [0; 16]);
+}
+
+#[test]
+fn access_bits_refuse_write() {
+    let mut card = Card::classic_1k(UID);
+    // Block 2 of the sector is read only
+    card.set_trailer(4, [0xFF; 6], [0b000, 0b000, 0b010, 0b001], [0xFF; 6]);
+    card.set_block(18, [0x55; 16]);
+    let (sim, mut rfid, uid) = reader(card);
+
+    assert_eq!(
+        write_block(&uid, 4, 2, DATA, &mut rfid),
+        Err(Error::Write {
+            block: 18,
+            cause: DriverError::Nak
+        })
+    );
+    assert_eq!(sim.card().unwrap().block(18), [0x55; 16]);
+}
+
+#[test]
+fn manufacturer_block_is_read_only() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+    let block0 = sim.card().unwrap().block(0);
+
+    assert_eq!(
+        write_block(&uid, 0, 0, DATA, &mut rfid),
+        Err(Error::Write {
+            block: 0,
+            cause: DriverError::Nak
+        })
+    );
+    assert_eq!(sim.card().unwrap().block(0), block0);
+}
+
+#[test]
+fn card_removed_mid_read() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+    // Authentication and two blocks
+    sim.remove_after(3);
+
+    assert_eq!(
+        read_sector(&uid, 4, &mut rfid),
+        Err(Error::Read {
+            block: 18,
+            cause: DriverError::Timeout
+        })
+    );
+}
+
+#[test]
+fn card_removed_before_write() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+    sim.remove();
+
+    assert_eq!(
+        write_block(&uid, 4, 2, DATA, &mut rfid),
+        Err(Error::CardLost)
+    );
+    assert_eq!(sim.card().unwrap().block(18), [0; 16]);
+}
+
+#[test]
+fn errors_tell_where_and_why() {
+    let mut card = Card::classic_1k(UID);
+    // Block 17 can't be read with any key
+    card.set_trailer(4, [0xFF; 6], [0b000, 0b111, 0b000, 0b001], [0xFF; 6]);
+    let (_, mut rfid, uid) = reader(card);
+
+    let error = read_sector(&uid, 4, &mut rfid).unwrap_err();
+    assert_eq!(
+        error,
+        Error::Read {
+            block: 17,
+            cause: DriverError::Nak
+        }
+    );
+    assert!(!error.is_transient());
+    assert_eq!(error.to_string(), "Read failed on block 17: NAK");
+}
@@ -0,0 +1,179 @@
+//! The transactional writes of write-data and change-key.
+
+use mfrc522::comm::blocking::spi::{DummyDelay, SpiInterface};
+use mfrc522::{Mfrc522, Uid};
+
+use crate::error::{DriverError, Error};
+use crate::transaction::Transaction;
+use crate::{Card, Simulator};
+
+type Reader = Mfrc522<SpiInterface<Simulator, DummyDelay>, mfrc522::Initialized>;
+
+const UID: [u8; 4] = [0xC0, 0xFF, 0xEE, 0x42];
+const FACTORY_KEY: [u8; 6] = [0xFF; 6];
+const DATA: [u8; 16] = *b"implRust\0\0\0\0\0\0\0\0";
+/// Trailer with key A "Rusted", transport access bits and key B "Ferris"
+const NEW_TRAILER: [u8; 16] = [
+    b'R', b'u', b's', b't', b'e', b'd', 0xFF, 0x07, 0x80, 0x69, b'F', b'e', b'r', b'r', b'i', b's',
+];
+
+fn reader(card: Card) -> (Simulator, Reader, Uid) {
+    let sim = Simulator::new();
+    sim.insert(card);
+    let mut rfid = Mfrc522::new(SpiInterface::new(sim.clone())).init().unwrap();
+    let uid = select(&mut rfid);
+    (sim, rfid, uid)
+}
+
+fn select(rfid: &mut Reader) -> Uid {
+    let atqa = rfid.reqa().unwrap();
+    rfid.select(&atqa).unwrap()
+}
+
+/// Put the card back in the field and select it
+fn bring_back(sim: &Simulator, rfid: &mut Reader) -> Uid {
+    sim.insert(sim.card().unwrap());
+    select(rfid)
+}
+
+#[test]
+fn writes_data_then_trailer() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+
+    let mut transaction = Transaction::new(1, FACTORY_KEY)
+        .write(0, DATA)
+        .write(3, NEW_TRAILER);
+    transaction.commit(&uid, &mut rfid).unwrap();
+
+    let card = sim.card().unwrap();
+    assert_eq!(card.block(4), DATA);
+    assert_eq!(card.block(7), NEW_TRAILER);
+
+    // Only the new key opens the sector now
+    let mut transaction = Transaction::new(1, *b"Rusted").write(1, DATA);
+    transaction.commit(&uid, &mut rfid).unwrap();
+    assert_eq!(sim.card().unwrap().block(5), DATA);
+}
+
+#[test]
+fn trailer_that_doesnt_take_is_rolled_back() {
+    let mut card = Card::classic_1k(UID);
+    // Data blocks writable with key A, the trailer read only
+    card.set_trailer(1, FACTORY_KEY, [0b000, 0b000, 0b000, 0b010], FACTORY_KEY);
+    card.set_block(5, [0x55; 16]);
+    let trailer = card.block(7);
+    let (sim, mut rfid, uid) = reader(card);
+
+    let mut transaction = Transaction::new(1, FACTORY_KEY)
+        .write(1, DATA)
+        .write(3, NEW_TRAILER);
+    assert_eq!(
+        transaction.commit(&uid, &mut rfid),
+        Err(Error::VerifyMismatch { block: 7 })
+    );
+
+    // The data block was written first, and is back as it was
+    let card = sim.card().unwrap();
+    assert_eq!(card.block(5), [0x55; 16]);
+    assert_eq!(card.block(7), trailer);
+}
+
+#[test]
+fn card_removed_mid_write_is_rolled_back() {
+    let mut card = Card::classic_1k(UID);
+    card.set_block(4, [0x11; 16]);
+    card.set_block(5, [0x22; 16]);
+    let (sim, mut rfid, uid) = reader(card);
+
+    let mut transaction = Transaction::new(1, FACTORY_KEY)
+        .write(0, DATA)
+        .write(1, DATA);
+    // Backup and the first block, then gone before the data of the second
+    sim.remove_after(9);
+    assert_eq!(transaction.commit(&uid, &mut rfid), Err(Error::CardLost));
+    assert_eq!(sim.card().unwrap().block(4), DATA);
+    assert_eq!(sim.card().unwrap().block(5), [0x22; 16]);
+
+    let uid = bring_back(&sim, &mut rfid);
+    transaction.rollback(&uid, &mut rfid).unwrap();
+
+    let card = sim.card().unwrap();
+    assert_eq!(card.block(4), [0x11; 16]);
+    assert_eq!(card.block(5), [0x22; 16]);
+}
+
+#[test]
+fn card_removed_after_the_trailer_write_is_finished() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+
+    let mut transaction = Transaction::new(1, FACTORY_KEY).write(3, NEW_TRAILER);
+    // Gone after the write, before the new key is checked
+    sim.remove_after(5);
+    assert_eq!(transaction.commit(&uid, &mut rfid), Err(Error::CardLost));
+    assert_eq!(sim.card().unwrap().block(7), NEW_TRAILER);
+
+    // The factory key doesn't open the sector anymore, the new one does
+    let uid = bring_back(&sim, &mut rfid);
+    transaction.commit(&uid, &mut rfid).unwrap();
+    assert_eq!(sim.card().unwrap().block(7), NEW_TRAILER);
+}
+
+#[test]
+fn rollback_restores_the_old_key() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+    let trailer = sim.card().unwrap().block(7);
+
+    let mut transaction = Transaction::new(1, FACTORY_KEY).write(3, NEW_TRAILER);
+    sim.remove_after(5);
+    assert_eq!(transaction.commit(&uid, &mut rfid), Err(Error::CardLost));
+
+    let uid = bring_back(&sim, &mut rfid);
+    transaction.rollback(&uid, &mut rfid).unwrap();
+    assert_eq!(sim.card().unwrap().block(7), trailer);
+}
+
+#[test]
+fn unknown_key_writes_nothing() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+
+    let mut transaction = Transaction::new(1, [0x42; 6]).write(0, DATA);
+    assert_eq!(
+        transaction.commit(&uid, &mut rfid),
+        Err(Error::Auth {
+            sector: 1,
+            cause: DriverError::Timeout
+        })
+    );
+    assert_eq!(sim.card().unwrap().block(4), [0; 16]);
+}
+
+#[test]
+fn glitch_on_read_back_is_written_again() {
+    let (sim, mut rfid, uid) = reader(Card::classic_1k(UID));
+
+    let mut transaction = Transaction::new(1, FACTORY_KEY).write(0, DATA);
+    // Backup, then the read back after the write
+    sim.glitch_after(5);
+    transaction.commit(&uid, &mut rfid).unwrap();
+    assert_eq!(sim.card().unwrap().block(4), DATA);
+}
+
+#[test]
+fn refused_write_isnt_retried() {
+    let mut card = Card::classic_1k(UID);
+    // Block 5 is read only
+    card.set_trailer(1, FACTORY_KEY, [0b000, 0b010, 0b000, 0b001], FACTORY_KEY);
+    let (sim, mut rfid, uid) = reader(card);
+
+    let mut transaction = Transaction::new(1, FACTORY_KEY)
+        .write(0, DATA)
+        .write(1, DATA);
+    assert_eq!(
+        transaction.commit(&uid, &mut rfid),
+        Err(Error::Write {
+            block: 5,
+            cause: DriverError::Nak
+        })
+    );
+    assert_eq!(sim.card().unwrap().block(4), [0; 16]);
+}
@@ -0,0 +1,359 @@
+#![no_std]
+#![no_main]
+
+// Shared with change-key, not everything is needed here
+#[path = "../../change-key/src/access.rs"]
+#[allow(dead_code)]
+mod access;
+// Shared with memory-dump-usb, not everything is needed here
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+#[path = "../../memory-dump-usb/src/keys.rs"]
+#[allow(dead_code)]
+mod keys;
+#[path = "../../memory-dump-usb/src/mifare.rs"]
+mod mifare;
+mod restore;
+
+use embassy_executor::Spawner;
+use embassy_rp::block::ImageDef;
+use embassy_time::Timer;
+
+//Panic Handler
+use panic_probe as _;
+
+// Defmt Logging
+use defmt_rtt as _;
+
+// For SPI
+use embassy_rp::spi::Spi;
+use embassy_rp::{self as hal, spi};
+use embassy_time::Delay;
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+// For CS Pin
+use embassy_rp::gpio::{Level, Output};
+
+// Driver for the MFRC522
+use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
+
+// to prepare buffer with data before logging
+use core::fmt::Write;
+use heapless::String;
+
+use crate::access::SectorTrailer;
+use crate::error::Error;
+use crate::keys::{KeySelect, KeyType, SectorKey};
+use crate::restore::{
+    BLOCK_SIZE, BLOCKS_PER_SECTOR, IMAGE_SIZE, Image, MANUFACTURER_BLOCK, Outcome, SECTORS,
+};
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+/// The image written to every card, a `.mfd` file as saved by dump-saver
+static DUMP: &[u8; IMAGE_SIZE] = include_bytes!("../dump.mfd");
+
+/// Also write block 0 (UID and manufacturer data). Normal cards refuse it,
+/// only enable this for "magic" cards that are meant to be cloned onto.
+const ALLOW_MANUFACTURER_BLOCK: bool = false;
+
+const BLOCKS: usize = SECTORS * BLOCKS_PER_SECTOR;
+
+fn print_hex(data: &[u8]) {
+    let mut buff: String<64> = String::new();
+    for &d in data.iter() {
+        write!(buff, "{:02x} ", d).expect("failed to write byte into buffer");
+    }
+    defmt::println!("{}", buff);
+}
+
+/// Print `^^` under every byte set in the mask
+fn print_mask(mask: u16) {
+    let mut buff: String<64> = String::new();
+    for i in 0..BLOCK_SIZE {
+        let mark = if mask & 1 << i != 0 { "^^ " } else { "   " };
+        buff.push_str(mark).expect("failed to write into buffer");
+    }
+    defmt::println!("{}", buff);
+}
+
+/// State of restoring the image onto one card
+struct Restore<'a> {
+    image: Image<'a>,
+    outcomes: [Outcome; BLOCKS],
+    /// The key each sector was opened with before the trailers were written
+    keys: [Option<SectorKey>; SECTORS],
+    /// The trailers that were written, with key A filled in
+    trailers: [Option<[u8; BLOCK_SIZE]>; SECTORS],
+}
+
+impl<'a> Restore<'a> {
+    fn new(image: Image<'a>) -> Self {
+        Restore {
+            image,
+            outcomes: [Outcome::NotInDump; BLOCKS],
+            keys: [None; SECTORS],
+            trailers: [None; SECTORS],
+        }
+    }
+
+    /// Write the data blocks of every sector in the image
+    fn write_data_blocks<E, COMM>(
+        &mut self,
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<(), Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        for sector in 0..SECTORS as u8 {
+            if !self.image.has_sector(sector) {
+                continue;
+            }
+
+            let Some(key) = keys::authenticate_sector(uid, sector, rfid)? else {
+                let first = restore::first_block(sector) as usize;
+                self.outcomes[first..first + BLOCKS_PER_SECTOR]
+                    .fill(Outcome::Failed("No key opens the sector"));
+                continue;
+            };
+            self.keys[sector as usize] = Some(key);
+
+            for block in restore::data_blocks(sector) {
+                self.outcomes[block as usize] =
+                    if block == MANUFACTURER_BLOCK && !ALLOW_MANUFACTURER_BLOCK {
+                        Outcome::Skipped("Manufacturer block")
+                    } else if mifare::write(rfid, block, self.image.block(block)).is_ok() {
+                        Outcome::Written
+                    } else {
+                        // The card halts on a refused write
+                        mifare::reselect(uid, rfid)?;
+                        keys::authenticate_with(uid, block, &key, rfid)
+                            .map_err(|cause| Error::Auth { sector, cause })?;
+                        Outcome::Failed("Write failed")
+                    };
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the sector trailers, once every data block has been written
+    fn write_trailers<E, COMM>(
+        &mut self,
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<(), Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        for sector in 0..SECTORS as u8 {
+            let Some(key) = self.keys[sector as usize] else {
+                continue;
+            };
+            let block = restore::trailer_block(sector);
+
+            let data = match restore::trailer_to_write(&self.image, sector, &key) {
+                Ok(data) => data,
+                Err(outcome) => {
+                    self.outcomes[block as usize] = outcome;
+                    continue;
+                }
+            };
+
+            if keys::authenticate_with(uid, block, &key, rfid).is_err() {
+                mifare::reselect(uid, rfid)?;
+                self.outcomes[block as usize] = Outcome::Failed("Auth failed");
+                continue;
+            }
+
+            self.outcomes[block as usize] = if mifare::write(rfid, block, data).is_ok() {
+                self.trailers[sector as usize] = Some(data);
+                Outcome::Written
+            } else {
+                mifare::reselect(uid, rfid)?;
+                Outcome::Failed("Write failed")
+            };
+        }
+        Ok(())
+    }
+
+    /// Open a sector for reading back, with the keys it has now
+    fn authenticate_for_verify<E, COMM>(
+        &self,
+        sector: u8,
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<bool, Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        let candidates = match self.trailers[sector as usize].map(|t| SectorTrailer::parse(&t)) {
+            Some(Ok(trailer)) => [
+                Some(SectorKey {
+                    key_type: KeyType::A,
+                    key: trailer.key_a,
+                }),
+                Some(SectorKey {
+                    key_type: KeyType::B,
+                    key: trailer.key_b,
+                }),
+            ],
+            _ => [self.keys[sector as usize], None],
+        };
+
+        for key in candidates.iter().flatten() {
+            if keys::authenticate_with(uid, restore::first_block(sector), key, rfid).is_ok() {
+                return Ok(true);
+            }
+            mifare::reselect(uid, rfid)?;
+        }
+        Ok(false)
+    }
+
+    /// Read back every written block and compare it with what was written
+    fn verify<E, COMM>(
+        &mut self,
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<(), Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        for sector in 0..SECTORS as u8 {
+            let first = restore::first_block(sector);
+            let blocks = first..first + BLOCKS_PER_SECTOR as u8;
+            if !blocks
+                .clone()
+                .any(|b| self.outcomes[b as usize] == Outcome::Written)
+            {
+                continue;
+            }
+
+            let mut authenticated = self.authenticate_for_verify(sector, uid, rfid)?;
+            for block in blocks {
+                if self.outcomes[block as usize] != Outcome::Written {
+                    continue;
+                }
+                if !authenticated {
+                    self.outcomes[block as usize] = Outcome::Failed("Can't authenticate to verify");
+                    continue;
+                }
+
+                let trailer = block == restore::trailer_block(sector);
+                let expected = match self.trailers[sector as usize] {
+                    Some(data) if trailer => data,
+                    _ => self.image.block(block),
+                };
+                let Ok(actual) = mifare::read(rfid, block) else {
+                    self.outcomes[block as usize] = Outcome::Failed("Read failed");
+                    mifare::reselect(uid, rfid)?;
+                    authenticated = self.authenticate_for_verify(sector, uid, rfid)?;
+                    continue;
+                };
+
+                let mask = restore::diff(&expected, &actual, trailer);
+                self.outcomes[block as usize] = if mask == 0 {
+                    Outcome::Verified
+                } else {
+                    defmt::println!("Block {} differs", block);
+                    print_hex(&expected);
+                    print_hex(&actual);
+                    print_mask(mask);
+                    Outcome::Differs(mask)
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn print_report(&self) {
+        let mut verified = 0;
+        for (block, outcome) in self.outcomes.iter().enumerate() {
+            match outcome {
+                Outcome::NotInDump => {}
+                Outcome::Verified => verified += 1,
+                _ => defmt::println!("Block {}: {}", block, outcome),
+            }
+        }
+        defmt::println!("{} blocks written and verified", verified);
+    }
+}
+
+/// Write the image to a card: data blocks first, trailers last, then read it all back
+fn restore_card<E, COMM>(
+    uid: &mfrc522::Uid,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut restore = Restore::new(Image::new(DUMP));
+    restore.write_data_blocks(uid, rfid)?;
+    restore.write_trailers(uid, rfid)?;
+    restore.verify(uid, rfid)?;
+    restore.print_report();
+    Ok(())
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let miso = p.PIN_0;
+    let cs_pin = Output::new(p.PIN_1, Level::High);
+    let clk = p.PIN_2;
+    let mosi = p.PIN_3;
+
+    let mut config = spi::Config::default();
+    config.frequency = 1_000_000;
+
+    let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
+
+    let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
+
+    let itf = SpiInterface::new(KeySelect::new(spi));
+
+    Timer::after_millis(100).await;
+
+    let mut rfid = Mfrc522::new(itf)
+        .init()
+        .expect("failed to initialize the RFID reader");
+    defmt::info!("Initialized RFID reader, waiting for a card to restore");
+
+    loop {
+        if let Ok(atqa) = rfid.reqa()
+            && let Ok(uid) = rfid.select(&atqa)
+        {
+            if let Err(e) = restore_card(&uid, &mut rfid) {
+                defmt::error!("Error restoring card: {}", e);
+            }
+            let _ = rfid.hlta();
+            let _ = rfid.stop_crypto1();
+            Timer::after_millis(500).await;
+        }
+
+        Timer::after_millis(200).await;
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recommended to have these minimal entries.
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
+    embassy_rp::binary_info::rp_program_name!(c"restore-card"),
+    embassy_rp::binary_info::rp_program_description!(c"Write a saved dump back to a card"),
+    embassy_rp::binary_info::rp_cargo_version!(),
+    embassy_rp::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
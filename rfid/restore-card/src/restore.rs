@@ -0,0 +1,125 @@
+//! Plan and check the restore of a 1K card image.
+//!
+//! The image is in the `.mfd` layout that memory-dump-usb and dump-saver
+//! produce: 16 sectors of four 16 byte blocks, the last block of each sector
+//! being its trailer. Writes happen in this order so that a failure part way
+//! never locks us out of a sector that still has data to write:
+//!
+//! 1. the data blocks of every sector, skipping block 0
+//! 2. the sector trailers, which may change the keys and access bits
+//!
+//! Sectors whose trailer in the image doesn't have valid access bits weren't
+//! read when the dump was made (they are zeros), so they are left alone.
+
+use crate::access::{Access, AccessError, SectorTrailer};
+use crate::keys::{KeyType, SectorKey};
+
+pub const SECTORS: usize = 16;
+pub const BLOCKS_PER_SECTOR: usize = 4;
+pub const BLOCK_SIZE: usize = 16;
+pub const IMAGE_SIZE: usize = SECTORS * BLOCKS_PER_SECTOR * BLOCK_SIZE;
+
+/// Block 0 holds the UID and manufacturer data. Only "magic" cards let it
+/// be written, and a bad one can make them unreadable.
+pub const MANUFACTURER_BLOCK: u8 = 0;
+
+/// What happened to one block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub enum Outcome {
+    /// The sector wasn't read when the dump was made
+    NotInDump,
+    Skipped(&'static str),
+    Failed(&'static str),
+    /// Written, waiting to be read back
+    Written,
+    /// Read back and matches the image
+    Verified,
+    /// Read back, the bits set in the mask are the bytes that differ
+    Differs(u16),
+}
+
+/// A card image in `.mfd` layout
+pub struct Image<'a> {
+    data: &'a [u8; IMAGE_SIZE],
+}
+
+impl<'a> Image<'a> {
+    pub const fn new(data: &'a [u8; IMAGE_SIZE]) -> Self {
+        Image { data }
+    }
+
+    pub fn block(&self, block: u8) -> [u8; BLOCK_SIZE] {
+        let start = block as usize * BLOCK_SIZE;
+        let mut out = [0; BLOCK_SIZE];
+        out.copy_from_slice(&self.data[start..start + BLOCK_SIZE]);
+        out
+    }
+
+    /// The trailer of a sector, or an error if the sector isn't in the image
+    pub fn trailer(&self, sector: u8) -> Result<SectorTrailer, AccessError> {
+        SectorTrailer::parse(&self.block(trailer_block(sector)))
+    }
+
+    /// Whether the dump has this sector's contents
+    pub fn has_sector(&self, sector: u8) -> bool {
+        self.trailer(sector).is_ok()
+    }
+}
+
+pub const fn first_block(sector: u8) -> u8 {
+    sector * BLOCKS_PER_SECTOR as u8
+}
+
+pub const fn trailer_block(sector: u8) -> u8 {
+    first_block(sector) + BLOCKS_PER_SECTOR as u8 - 1
+}
+
+/// The data blocks of a sector, in the order they are written
+pub fn data_blocks(sector: u8) -> core::ops::Range<u8> {
+    first_block(sector)..trailer_block(sector)
+}
+
+/// The trailer to write for a sector, with key A filled in
+///
+/// Key A can't be read from a card, so dumps have zeros in its place. The
+/// key that opens the sector now is kept in that case, which is only known
+/// if it is key A.
+pub fn trailer_to_write(
+    image: &Image,
+    sector: u8,
+    key: &SectorKey,
+) -> Result<[u8; BLOCK_SIZE], Outcome> {
+    let mut trailer = image.trailer(sector).map_err(|_| Outcome::NotInDump)?;
+
+    if trailer.key_a == [0; 6] {
+        match key.key_type {
+            KeyType::A => trailer.key_a = key.key,
+            KeyType::B => return Err(Outcome::Skipped("Key A unknown")),
+        }
+    }
+
+    trailer.to_block().map_err(|e| match e {
+        AccessError::InvalidAccessBits => Outcome::Skipped("Invalid access bits"),
+        AccessError::PermanentLock => Outcome::Skipped("Trailer would lock the sector"),
+    })
+}
+
+/// The bytes that differ between what was written and what was read back
+///
+/// A trailer never reads back key A, and only reads back key B when its
+/// access conditions allow it, so those bytes aren't compared.
+pub fn diff(expected: &[u8; BLOCK_SIZE], actual: &[u8; BLOCK_SIZE], trailer: bool) -> u16 {
+    let compared = if !trailer {
+        0..BLOCK_SIZE
+    } else {
+        let key_b_readable = SectorTrailer::parse(expected)
+            .map(|t| t.access.trailer.trailer_permissions().key_b_read != Access::Never)
+            .unwrap_or(false);
+        6..if key_b_readable { BLOCK_SIZE } else { 10 }
+    };
+
+    compared
+        .filter(|&i| expected[i] != actual[i])
+        .fold(0, |mask, i| mask | 1 << i)
+}
@@ -0,0 +1,12 @@
+//! Host tests for the tap-logger firmware.
+//!
+//! The tap log only uses `core`, so it is pulled in from the firmware with
+//! `#[path]` and run here with `cargo test`.
+
+// The firmware module under test, at the path it has in its crate
+#[cfg(test)]
+#[path = "../../tap-logger/src/tap_log.rs"]
+#[allow(dead_code)]
+mod tap_log;
+#[cfg(test)]
+mod tap_log_tests;
@@ -0,0 +1,76 @@
+//! Tests for the tap log of the tap-logger firmware.
+
+use crate::tap_log::{FILE_NAMES, HEADER, MAX_LINE, Tap, current_file, next_file};
+
+fn line(tap: &Tap) -> String {
+    let mut line = String::new();
+    tap.write_line(&mut line).unwrap();
+    line
+}
+
+#[test]
+fn csv_lines() {
+    let tap = Tap {
+        time_s: 42,
+        uid: &[0x13, 0x37, 0x73, 0x31],
+        card_type: "MIFARE Classic 1K",
+        granted: true,
+    };
+    assert_eq!(line(&tap), "42,13377331,MIFARE Classic 1K,granted\n");
+
+    let tap = Tap {
+        time_s: 0,
+        uid: &[0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66],
+        card_type: "MIFARE Ultralight or NTAG",
+        granted: false,
+    };
+    assert_eq!(
+        line(&tap),
+        "0,04112233445566,MIFARE Ultralight or NTAG,denied\n"
+    );
+}
+
+#[test]
+fn longest_line_fits() {
+    let tap = Tap {
+        time_s: u64::MAX,
+        uid: &[0xFF; 10],
+        card_type: "MIFARE Ultralight or NTAG",
+        granted: true,
+    };
+    assert_eq!(line(&tap).len(), MAX_LINE);
+}
+
+#[test]
+fn header_names_every_column() {
+    let tap = Tap {
+        time_s: 7,
+        uid: &[0xDE, 0xAD, 0xBE, 0xEF],
+        card_type: "MIFARE Classic 4K",
+        granted: true,
+    };
+    assert_eq!(HEADER.split(',').count(), line(&tap).split(',').count());
+    assert!(HEADER.ends_with('\n'));
+}
+
+#[test]
+fn files_rotate() {
+    assert_eq!(next_file(0), 1);
+    assert_eq!(next_file(FILE_NAMES.len() - 1), 0);
+}
+
+#[test]
+fn log_continues_before_the_gap() {
+    // A new card
+    assert_eq!(current_file(|_| false), 0);
+    assert_eq!(current_file(|index| index == 0), 0);
+    assert_eq!(current_file(|index| index <= 3), 3);
+    // Wrapped around, TAPS3.CSV was deleted when TAPS2.CSV was started
+    assert_eq!(current_file(|index| index != 3), 2);
+    // The gap at the first file
+    assert_eq!(current_file(|index| index != 0), FILE_NAMES.len() - 1);
+    // Reset after TAPS4.CSV was deleted, before TAPS3.CSV was created
+    assert_eq!(current_file(|index| index != 3 && index != 4), 2);
+    // No gap, the log starts over at the first file
+    assert_eq!(current_file(|_| true), 0);
+}
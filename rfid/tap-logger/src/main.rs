@@ -0,0 +1,222 @@
+#![no_std]
+#![no_main]
+
+// Shared with access-control, the tags enrolled there are granted here
+#[path = "../../access-control/src/allow_list.rs"]
+#[allow(dead_code)]
+mod allow_list;
+// Shared with memory-dump-usb, not everything is needed here
+#[path = "../../memory-dump-usb/src/card.rs"]
+mod card;
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+// Shared with access-control, enrolment only happens there
+#[path = "../../access-control/src/storage.rs"]
+#[allow(dead_code)]
+mod storage;
+mod tap_log;
+
+use core::cell::RefCell;
+
+use embassy_executor::Spawner;
+use embassy_rp::block::ImageDef;
+use embassy_time::{Instant, Timer};
+
+//Panic Handler
+use panic_probe as _;
+
+// Defmt Logging
+use defmt_rtt as _;
+
+// For the shared SPI bus
+use embassy_rp::spi::Spi;
+use embassy_rp::{self as hal, spi};
+use embassy_time::Delay;
+use embedded_hal_bus::spi::RefCellDevice;
+
+// For the CS Pins and the LED
+use embassy_rp::gpio::{Level, Output};
+
+// For the allow-list
+use embassy_rp::flash::Flash;
+
+// Driver for the MFRC522
+use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
+
+// For SdCard
+use embedded_sdmmc::{
+    BlockDevice, Directory, Mode, SdCard, TimeSource, Timestamp, VolumeIdx, VolumeManager,
+};
+
+// to prepare the CSV line before writing it
+use heapless::String;
+
+use crate::allow_list::TagUid;
+use crate::card_type::CardType;
+use crate::storage::ListFlash;
+use crate::tap_log::{FILE_NAMES, HEADER, MAX_FILE_SIZE, MAX_LINE, Tap};
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+/// Same as in write-sdcard, a time source that always gives the same time,
+/// used for the dates of the files
+#[derive(Default)]
+pub struct DummyTimesource();
+
+impl TimeSource for DummyTimesource {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 0,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+/// Append a line to the log, moving to the next file when `current` is full
+///
+/// The file is closed after every line, so a tap is on the card even if the
+/// power goes right after.
+fn append_line<D, T, const DIRS: usize, const FILES: usize, const VOLUMES: usize>(
+    root: &Directory<'_, D, T, DIRS, FILES, VOLUMES>,
+    current: &mut usize,
+    line: &str,
+) -> Result<(), &'static str>
+where
+    D: BlockDevice,
+    T: TimeSource,
+{
+    let mut file = root
+        .open_file_in_dir(FILE_NAMES[*current], Mode::ReadWriteCreateOrAppend)
+        .map_err(|_| "Can't open the log file")?;
+
+    if file.length() + line.len() as u32 > MAX_FILE_SIZE {
+        file.close().map_err(|_| "Can't close the log file")?;
+        let next = tap_log::next_file(*current);
+        // Keep the gap after the newest file. Deleting first means a reset
+        // in between leaves two gaps, never none.
+        match root.delete_file_in_dir(FILE_NAMES[tap_log::next_file(next)]) {
+            Ok(()) | Err(embedded_sdmmc::Error::NotFound) => {}
+            Err(_) => return Err("Can't delete the oldest log file"),
+        }
+        *current = next;
+        file = root
+            .open_file_in_dir(FILE_NAMES[*current], Mode::ReadWriteCreateOrTruncate)
+            .map_err(|_| "Can't create the next log file")?;
+        defmt::info!("Logging to {}", FILE_NAMES[*current]);
+    }
+
+    if file.length() == 0 {
+        file.write(HEADER.as_bytes())
+            .map_err(|_| "Can't write the CSV header")?;
+    }
+    file.write(line.as_bytes())
+        .map_err(|_| "Can't write the tap")?;
+    file.close().map_err(|_| "Can't close the log file")
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let miso = p.PIN_0;
+    let rfid_cs = Output::new(p.PIN_1, Level::High);
+    let clk = p.PIN_2;
+    let mosi = p.PIN_3;
+    let sd_cs = Output::new(p.PIN_5, Level::High);
+
+    let mut config = spi::Config::default();
+    // An SD card has to start at 400 kHz or less, the MFRC522 works at that
+    // speed too
+    config.frequency = 400_000;
+
+    // The MFRC522 and the SD card share SPI0, each with its own CS pin. The
+    // bus is lent to one device for the length of a transaction, and both
+    // are only used from this task, so a RefCell is all the locking needed.
+    let spi_bus = RefCell::new(Spi::new_blocking(p.SPI0, clk, mosi, miso, config));
+    let rfid_spi =
+        RefCellDevice::new(&spi_bus, rfid_cs, Delay).expect("Failed to get the RFID reader device");
+    let sd_spi =
+        RefCellDevice::new(&spi_bus, sd_cs, Delay).expect("Failed to get the SD card device");
+
+    Timer::after_millis(100).await;
+
+    let itf = SpiInterface::new(rfid_spi);
+    let mut rfid = Mfrc522::new(itf)
+        .init()
+        .expect("failed to initialize the RFID reader");
+    defmt::info!("Initialized RFID reader");
+
+    let sdcard = SdCard::new(sd_spi, Delay);
+    let sd_size = sdcard.num_bytes().expect("failed to get sdcard size");
+    defmt::info!("SD card size is {} bytes", sd_size);
+
+    let volume_mgr = VolumeManager::new(sdcard, DummyTimesource::default());
+    let volume0 = volume_mgr
+        .open_volume(VolumeIdx(0))
+        .expect("failed to open volume");
+    let root_dir = volume0.open_root_dir().expect("failed to open root dir");
+
+    let mut current =
+        tap_log::current_file(|index| root_dir.find_directory_entry(FILE_NAMES[index]).is_ok());
+    defmt::info!("Logging to {}", FILE_NAMES[current]);
+
+    let mut led = Output::new(p.PIN_25, Level::Low);
+
+    let mut flash: ListFlash = Flash::new_blocking(p.FLASH);
+    let list = storage::load(&mut flash);
+    defmt::info!("Loaded the allow-list, {} tags", list.len());
+
+    loop {
+        if let Ok(card) = card::activate(&mut rfid, false) {
+            // Halted, the tag won't answer again until it leaves the field
+            let _ = rfid.hlta();
+
+            let uid = card.uid.as_bytes();
+            let granted = TagUid::new(uid).is_ok_and(|uid| list.contains(&uid));
+            let tap = Tap {
+                time_s: Instant::now().as_secs(),
+                uid,
+                card_type: CardType::identify(card.atqa, card.sak).name(),
+                granted,
+            };
+
+            let mut line: String<MAX_LINE> = String::new();
+            tap.write_line(&mut line).expect("MAX_LINE fits every line");
+            defmt::info!("{}", line.as_str().trim_end());
+
+            if let Err(e) = append_line(&root_dir, &mut current, &line) {
+                defmt::error!("Error logging the tap: {}", e);
+            }
+
+            if granted {
+                led.set_high();
+                Timer::after_millis(500).await;
+                led.set_low();
+            }
+        }
+        Timer::after_millis(100).await;
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recommended to have these minimal entries.
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
+    embassy_rp::binary_info::rp_program_name!(c"tap-logger"),
+    embassy_rp::binary_info::rp_program_description!(c"RFID tap log on an SD card"),
+    embassy_rp::binary_info::rp_cargo_version!(),
+    embassy_rp::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,70 @@
+//! The CSV lines of the tap log, and the files they rotate through.
+//!
+//! Taps are appended to `TAPS0.CSV` until it is full, then to `TAPS1.CSV`,
+//! and so on, back to `TAPS0.CSV` after the last one. Before the log moves
+//! on to the next file, the file after that one is deleted, so there is
+//! always a gap after the newest file: after a reset the log finds its place
+//! again, and the oldest taps are the first to go.
+//!
+//! This file is tested on the host by tap-logger-tests, so it only uses
+//! `core`.
+
+use core::fmt::{self, Write};
+
+/// Files the log rotates through, one of them always missing
+pub const FILE_NAMES: [&str; 8] = [
+    "TAPS0.CSV",
+    "TAPS1.CSV",
+    "TAPS2.CSV",
+    "TAPS3.CSV",
+    "TAPS4.CSV",
+    "TAPS5.CSV",
+    "TAPS6.CSV",
+    "TAPS7.CSV",
+];
+/// The log moves to the next file before one grows past this, in bytes
+pub const MAX_FILE_SIZE: u32 = 64 * 1024;
+/// First line of every file
+pub const HEADER: &str = "time_s,uid,card_type,result\n";
+/// Longest line `Tap::write_line` writes: a `u64`, a 10 byte UID, the
+/// longest card type name and the result
+pub const MAX_LINE: usize = 20 + 1 + 20 + 1 + 25 + 1 + 7 + 1;
+
+/// A card tapped on the reader
+pub struct Tap<'a> {
+    /// Seconds since boot, the Pico 2 has no clock that keeps the date
+    pub time_s: u64,
+    pub uid: &'a [u8],
+    pub card_type: &'a str,
+    pub granted: bool,
+}
+
+impl Tap<'_> {
+    /// Write the CSV line of the tap, newline included
+    ///
+    /// The UID is written in hex, the card type as is, so it must not hold a
+    /// comma.
+    pub fn write_line<W: Write>(&self, out: &mut W) -> fmt::Result {
+        write!(out, "{},", self.time_s)?;
+        for byte in self.uid {
+            write!(out, "{:02X}", byte)?;
+        }
+        let result = if self.granted { "granted" } else { "denied" };
+        writeln!(out, ",{},{}", self.card_type, result)
+    }
+}
+
+/// The file after `index`, which the log moves to once `index` is full
+pub fn next_file(index: usize) -> usize {
+    (index + 1) % FILE_NAMES.len()
+}
+
+/// The file the log was written to before a reset, the one before the gap
+///
+/// With no files at all the log starts at the first one, and so does a log
+/// with no gap, which the firmware never leaves behind.
+pub fn current_file(exists: impl Fn(usize) -> bool) -> usize {
+    (0..FILE_NAMES.len())
+        .find(|&index| exists(index) && !exists(next_file(index)))
+        .unwrap_or(0)
+}
@@ -1,6 +1,15 @@
 #![no_std]
 #![no_main]
 
+// Shared with memory-dump-usb, only the decoder of card_type is needed here
+#[path = "../../memory-dump-usb/src/card.rs"]
+mod card;
+#[path = "../../memory-dump-usb/src/card_type.rs"]
+#[allow(dead_code)]
+mod card_type;
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+
 use embassy_executor::Spawner;
 use embassy_rp as hal;
 use embassy_rp::block::ImageDef;
@@ -28,6 +37,8 @@ use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
 use core::fmt::Write;
 use heapless::String;
 
+use crate::card_type::{CardType, atqa_value};
+
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
@@ -65,7 +76,7 @@ async fn main(spawner: Spawner) {
     let mosi = p.PIN_3;
 
     let mut config = spi::Config::default();
-    config.frequency = 1000_000;
+    config.frequency = 1_000_000;
 
     let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
     let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
@@ -89,11 +100,16 @@ async fn main(spawner: Spawner) {
     log::info!("Waiting for RFID");
 
     loop {
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                print_hex_to_serial(uid.as_bytes());
-                Timer::after_millis(500).await;
-            }
+        if let Ok(card) = card::activate(&mut rfid, false) {
+            let card_type = CardType::identify(card.atqa, card.sak);
+            print_hex_to_serial(card.uid.as_bytes());
+            log::info!(
+                "ATQA: {:04x} SAK: {:02x} ({})",
+                atqa_value(card.atqa),
+                card.sak,
+                card_type.name()
+            );
+            Timer::after_millis(500).await;
         }
         Timer::after_millis(100).await;
     }
@@ -0,0 +1,12 @@
+//! Host tests for the value block encoding of value-block.
+//!
+//! `value.rs` only uses `core`, so it is pulled in from the firmware with
+//! `#[path]` and run here with `cargo test`. dump-saver includes the same
+//! file.
+
+// The firmware module under test, at the path it has in its crate
+#[cfg(test)]
+#[path = "../../value-block/src/value.rs"]
+mod value;
+#[cfg(test)]
+mod value_tests;
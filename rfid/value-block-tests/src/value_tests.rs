@@ -0,0 +1,46 @@
+//! The layout of MIFARE Classic value blocks.
+
+use crate::value::{ValueBlock, ValueError};
+
+#[test]
+fn value_block_layout() {
+    let block = ValueBlock::new(100, 4).to_block();
+    assert_eq!(
+        block,
+        [
+            0x64, 0x00, 0x00, 0x00, 0x9B, 0xFF, 0xFF, 0xFF, 0x64, 0x00, 0x00, 0x00, 0x04, 0xFB,
+            0x04, 0xFB
+        ]
+    );
+    assert_eq!(ValueBlock::parse(&block), Ok(ValueBlock::new(100, 4)));
+}
+
+#[test]
+fn round_trips_any_value() {
+    for value in [0, 1, -1, i32::MAX, i32::MIN, 123_456] {
+        for address in [0, 5, 0xFF] {
+            let block = ValueBlock::new(value, address);
+            assert_eq!(ValueBlock::parse(&block.to_block()), Ok(block));
+        }
+    }
+}
+
+#[test]
+fn every_corrupted_byte_is_caught() {
+    let block = ValueBlock::new(42, 8).to_block();
+    for i in 0..16 {
+        let mut broken = block;
+        broken[i] ^= 0x10;
+        assert_eq!(
+            ValueBlock::parse(&broken),
+            Err(ValueError::NotAValueBlock),
+            "byte {i}"
+        );
+    }
+}
+
+#[test]
+fn blank_blocks_are_not_values() {
+    assert!(ValueBlock::parse(&[0; 16]).is_err());
+    assert!(ValueBlock::parse(&[0xFF; 16]).is_err());
+}
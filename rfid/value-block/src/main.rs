@@ -0,0 +1,200 @@
+#![no_std]
+#![no_main]
+
+// Shared with memory-dump-usb, not everything is needed here
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+#[path = "../../memory-dump-usb/src/mifare.rs"]
+#[allow(dead_code)]
+mod mifare;
+mod operations;
+mod value;
+
+use embassy_executor::Spawner;
+use embassy_rp::block::ImageDef;
+use embassy_time::Timer;
+
+//Panic Handler
+use panic_probe as _;
+
+// Defmt Logging
+use defmt_rtt as _;
+
+// For SPI
+use embassy_rp::spi::Spi;
+use embassy_rp::{self as hal, spi};
+use embassy_time::Delay;
+use embedded_hal_bus::spi::ExclusiveDevice;
+
+// For CS Pin and the top up button
+use embassy_rp::gpio::{Input, Level, Output, Pull};
+
+// Driver for the MFRC522
+use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
+
+use crate::error::Error;
+use crate::value::ValueBlock;
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+/// The credit lives in sector 1, with a backup copy next to it
+const CREDIT_SECTOR: u8 = 1;
+const CREDIT_BLOCK: u8 = 4;
+const BACKUP_BLOCK: u8 = 5;
+
+/// Credit loaded onto a card the first time it is tapped
+const INITIAL_CREDIT: i32 = 10;
+/// Credit taken on every tap
+const FARE: u32 = 1;
+/// Credit added on a tap while the button is held
+const TOP_UP: u32 = 5;
+
+fn read_value<E, COMM>(
+    block: u8,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<Option<ValueBlock>, Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let data = mifare::read(rfid, block).map_err(|cause| Error::Read { block, cause })?;
+    Ok(ValueBlock::parse(&data).ok())
+}
+
+/// The credit on the card, setting the card up on its first tap
+///
+/// If the credit block is broken, a charge was cut off half way and the
+/// backup still has the credit from before it.
+fn load_credit<E, COMM>(rfid: &mut Mfrc522<COMM, mfrc522::Initialized>) -> Result<i32, Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    if let Some(credit) = read_value(CREDIT_BLOCK, rfid)? {
+        return Ok(credit.value);
+    }
+
+    if let Some(backup) = read_value(BACKUP_BLOCK, rfid)? {
+        defmt::warn!("Credit block is broken, restoring it from the backup");
+        operations::restore(BACKUP_BLOCK, rfid)?;
+        operations::transfer(CREDIT_BLOCK, rfid)?;
+        return Ok(backup.value);
+    }
+
+    defmt::info!("New card, loading {} credits", INITIAL_CREDIT);
+    let block = ValueBlock::new(INITIAL_CREDIT, CREDIT_BLOCK).to_block();
+    for target in [CREDIT_BLOCK, BACKUP_BLOCK] {
+        mifare::write(rfid, target, block).map_err(|cause| Error::Write {
+            block: target,
+            cause,
+        })?;
+    }
+    Ok(INITIAL_CREDIT)
+}
+
+/// Take one fare from the card, or add credit to it, returning the credit left
+///
+/// Returns `None` and leaves the card alone if it can't pay the fare.
+fn pay<E, COMM>(
+    uid: &mfrc522::Uid,
+    top_up: bool,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<Option<i32>, Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    const AUTH_KEY: [u8; 6] = [0xFF; 6];
+
+    rfid.mf_authenticate(uid, CREDIT_SECTOR * 4, &AUTH_KEY)
+        .map_err(|e| Error::Auth {
+            sector: CREDIT_SECTOR,
+            cause: e.into(),
+        })?;
+
+    let credit = load_credit(rfid)?;
+    if !top_up && credit < FARE as i32 {
+        return Ok(None);
+    }
+
+    // Back up the credit first, so a card pulled away half way loses nothing
+    operations::restore(CREDIT_BLOCK, rfid)?;
+    operations::transfer(BACKUP_BLOCK, rfid)?;
+
+    if top_up {
+        operations::increment(CREDIT_BLOCK, TOP_UP, rfid)?;
+    } else {
+        operations::decrement(CREDIT_BLOCK, FARE, rfid)?;
+    }
+    operations::transfer(CREDIT_BLOCK, rfid)?;
+
+    let credit = read_value(CREDIT_BLOCK, rfid)?.ok_or(Error::VerifyMismatch {
+        block: CREDIT_BLOCK,
+    })?;
+    Ok(Some(credit.value))
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let button = Input::new(p.PIN_15, Pull::Up);
+
+    let miso = p.PIN_0;
+    let cs_pin = Output::new(p.PIN_1, Level::High);
+    let clk = p.PIN_2;
+    let mosi = p.PIN_3;
+
+    let mut config = spi::Config::default();
+    config.frequency = 1_000_000;
+
+    let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
+
+    let spi = ExclusiveDevice::new(spi_bus, cs_pin, Delay).expect("Failed to get exclusive device");
+
+    let itf = SpiInterface::new(spi);
+
+    Timer::after_millis(100).await;
+
+    let mut rfid = Mfrc522::new(itf)
+        .init()
+        .expect("failed to initialize the RFID reader");
+    defmt::info!("Initialized RFID reader, tap a card to pay, hold the button to top up");
+
+    loop {
+        if let Ok(atqa) = rfid.reqa()
+            && let Ok(uid) = rfid.select(&atqa)
+        {
+            let top_up = button.is_low();
+            match pay(&uid, top_up, &mut rfid) {
+                Ok(Some(credit)) if top_up => {
+                    defmt::println!("Added {}, {} credits", TOP_UP, credit)
+                }
+                Ok(Some(credit)) => defmt::println!("Paid {}, {} credits left", FARE, credit),
+                Ok(None) => defmt::println!("Not enough credit"),
+                Err(e) => defmt::error!("Payment failed: {}", e),
+            }
+            let _ = rfid.hlta();
+            let _ = rfid.stop_crypto1();
+            Timer::after_millis(500).await;
+        }
+
+        Timer::after_millis(200).await;
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recommended to have these minimal entries.
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
+    embassy_rp::binary_info::rp_program_name!(c"value-block"),
+    embassy_rp::binary_info::rp_program_description!(c"Tap to pay credit counter"),
+    embassy_rp::binary_info::rp_cargo_version!(),
+    embassy_rp::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,118 @@
+//! Increment, decrement, restore and transfer for value blocks.
+//!
+//! The mfrc522 crate only knows read and write, so these are sent with its
+//! `transceive`. Increment, decrement and restore work in two steps: the
+//! command and block number, which the card acknowledges, then the operand.
+//! The card doesn't answer the operand, it only puts the result in its
+//! internal transfer buffer. Nothing reaches the card's memory until a
+//! transfer writes that buffer to a block, which may be another block of the
+//! same sector.
+//!
+//! The sector must be authenticated first, with a key that its access
+//! conditions allow for the operation.
+
+use mfrc522::Mfrc522;
+
+use crate::crc::crc_a;
+use crate::error::{DriverError, Error};
+
+const MF_DECREMENT: u8 = 0xC0;
+const MF_INCREMENT: u8 = 0xC1;
+const MF_RESTORE: u8 = 0xC2;
+const MF_TRANSFER: u8 = 0xB0;
+
+/// The card acknowledges with 4 bits
+const MF_ACK: u8 = 0x0A;
+
+/// Send a command with its CRC, expecting an ACK
+fn send_command<E, COMM>(
+    command: u8,
+    block: u8,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let mut tx = [command, block, 0, 0];
+    let crc = crc_a(&tx[..2]);
+    tx[2..].copy_from_slice(&crc);
+
+    let rx = rfid.transceive::<1>(&tx, 0, 0)?;
+    if rx.valid_bytes != 1 || rx.valid_bits != 4 {
+        return Err(DriverError::IncompleteFrame);
+    }
+    if rx.buffer[0] & 0x0F != MF_ACK {
+        return Err(DriverError::Nak);
+    }
+    Ok(())
+}
+
+fn value_operation<E, COMM>(
+    command: u8,
+    block: u8,
+    operand: u32,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), DriverError>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    send_command(command, block, rfid)?;
+
+    let mut tx = [0u8; 6];
+    tx[..4].copy_from_slice(&operand.to_le_bytes());
+    let crc = crc_a(&tx[..4]);
+    tx[4..].copy_from_slice(&crc);
+
+    // Silence means success, the card only answers to refuse
+    match rfid.transceive::<1>(&tx, 0, 0) {
+        Err(mfrc522::Error::Timeout) => Ok(()),
+        Ok(_) => Err(DriverError::Nak),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Add to the value of a block, keeping the result in the transfer buffer
+pub fn increment<E, COMM>(
+    block: u8,
+    delta: u32,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    value_operation(MF_INCREMENT, block, delta, rfid).map_err(|cause| Error::Write { block, cause })
+}
+
+/// Subtract from the value of a block, keeping the result in the transfer buffer
+pub fn decrement<E, COMM>(
+    block: u8,
+    delta: u32,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    value_operation(MF_DECREMENT, block, delta, rfid).map_err(|cause| Error::Write { block, cause })
+}
+
+/// Copy the value of a block into the transfer buffer
+pub fn restore<E, COMM>(
+    block: u8,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    value_operation(MF_RESTORE, block, 0, rfid).map_err(|cause| Error::Read { block, cause })
+}
+
+/// Write the transfer buffer to a block
+pub fn transfer<E, COMM>(
+    block: u8,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    send_command(MF_TRANSFER, block, rfid).map_err(|cause| Error::Write { block, cause })
+}
@@ -0,0 +1,65 @@
+//! MIFARE Classic value blocks.
+//!
+//! A value block holds a signed 32 bit value three times, once inverted, so
+//! the card can check it before changing it. The address byte is free for
+//! the application, usually the block number, and is stored four times:
+//!
+//! ```text
+//! | value (4) | !value (4) | value (4) | addr | !addr | addr | !addr |
+//! ```
+//!
+//! Values are little endian. The card only increments, decrements and
+//! restores blocks in this format.
+//!
+//! This file is shared with the host side dump-saver tool, so it only uses
+//! `core`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueError {
+    /// The copies of the value or address don't match, it isn't a value block
+    NotAValueBlock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueBlock {
+    pub value: i32,
+    pub address: u8,
+}
+
+impl ValueBlock {
+    pub const fn new(value: i32, address: u8) -> Self {
+        ValueBlock { value, address }
+    }
+
+    /// Parse a block read from the card, checking every copy
+    pub fn parse(block: &[u8; 16]) -> Result<Self, ValueError> {
+        let word =
+            |i: usize| u32::from_le_bytes([block[i], block[i + 1], block[i + 2], block[i + 3]]);
+        let (value, inverted, copy) = (word(0), word(4), word(8));
+        let address = block[12];
+
+        let value_ok = value == copy && value == !inverted;
+        let address_ok = block[14] == address && block[13] == !address && block[15] == !address;
+        if !value_ok || !address_ok {
+            return Err(ValueError::NotAValueBlock);
+        }
+
+        Ok(ValueBlock {
+            value: value as i32,
+            address,
+        })
+    }
+
+    /// The 16 bytes to write to the block
+    pub fn to_block(self) -> [u8; 16] {
+        let value = self.value.to_le_bytes();
+        let inverted = (!self.value).to_le_bytes();
+
+        let mut block = [0u8; 16];
+        block[..4].copy_from_slice(&value);
+        block[4..8].copy_from_slice(&inverted);
+        block[8..12].copy_from_slice(&value);
+        block[12..].copy_from_slice(&[self.address, !self.address, self.address, !self.address]);
+        block
+    }
+}
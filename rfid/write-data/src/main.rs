@@ -1,6 +1,17 @@
 #![no_std]
 #![no_main]
 
+// Shared with memory-dump-usb, not everything is needed here
+#[path = "../../memory-dump-usb/src/crc.rs"]
+mod crc;
+#[path = "../../memory-dump-usb/src/error.rs"]
+#[allow(dead_code)]
+mod error;
+#[path = "../../memory-dump-usb/src/mifare.rs"]
+mod mifare;
+mod sector;
+mod transaction;
+
 use embassy_executor::Spawner;
 use embassy_rp::block::ImageDef;
 use embassy_time::Timer;
@@ -27,32 +38,13 @@ use mfrc522::{Mfrc522, comm::blocking::spi::SpiInterface};
 use core::fmt::Write;
 use heapless::String;
 
+use crate::sector::{read_sector, write_block};
+
 /// Tell the Boot ROM about our application
 #[unsafe(link_section = ".start_block")]
 #[used]
 pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
 
-fn read_sector<E, COMM>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
-where
-    COMM: mfrc522::comm::Interface<Error = E>,
-{
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
-    let block_offset = sector * 4;
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
-
-    for abs_block in block_offset..block_offset + 4 {
-        let data = rfid.mf_read(abs_block).map_err(|_| "Read failed")?;
-        print_hex(&data);
-    }
-    Ok(())
-}
-
 fn print_hex(data: &[u8]) {
     let mut buff: String<64> = String::new();
     for &d in data.iter() {
@@ -61,27 +53,10 @@ fn print_hex(data: &[u8]) {
     defmt::println!("{}", buff);
 }
 
-fn write_block<E, COMM>(
-    uid: &mfrc522::Uid,
-    sector: u8,
-    rel_block: u8,
-    data: [u8; 16],
-    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
-) -> Result<(), &'static str>
-where
-    COMM: mfrc522::comm::Interface<Error = E>,
-{
-    const AUTH_KEY: [u8; 6] = [0xFF; 6];
-
-    let block_offset = sector * 4;
-    let abs_block = block_offset + rel_block;
-
-    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
-        .map_err(|_| "Auth failed")?;
-
-    rfid.mf_write(abs_block, data).map_err(|_| "Write failed")?;
-
-    Ok(())
+fn print_sector(blocks: &[[u8; 16]; 4]) {
+    for block in blocks {
+        print_hex(block);
+    }
 }
 
 #[embassy_executor::main]
@@ -94,7 +69,7 @@ async fn main(_spawner: Spawner) {
     let mosi = p.PIN_3;
 
     let mut config = spi::Config::default();
-    config.frequency = 1000_000;
+    config.frequency = 1_000_000;
 
     let spi_bus = Spi::new_blocking(p.SPI0, clk, mosi, miso, config);
 
@@ -117,25 +92,27 @@ async fn main(_spawner: Spawner) {
     ];
 
     loop {
-        if let Ok(atqa) = rfid.reqa() {
-            if let Ok(uid) = rfid.select(&atqa) {
-                defmt::info!("\r\n----Before Write----\r\n");
-                if let Err(e) = read_sector(&uid, target_sector, &mut rfid) {
-                    defmt::error!("Error reading sector: {:?}", e);
-                }
-
-                if let Err(e) = write_block(&uid, target_sector, rel_block, DATA, &mut rfid) {
-                    defmt::error!("Error writing data: {:?}", e);
-                }
-
-                defmt::info!("\r\n----After Write----\r\n");
-                if let Err(e) = read_sector(&uid, target_sector, &mut rfid) {
-                    defmt::error!("Error reading sector: {:?}", e);
-                }
-                let _ = rfid.hlta();
-                let _ = rfid.stop_crypto1();
-                Timer::after_millis(500).await;
+        if let Ok(atqa) = rfid.reqa()
+            && let Ok(uid) = rfid.select(&atqa)
+        {
+            defmt::info!("\r\n----Before Write----\r\n");
+            match read_sector(&uid, target_sector, &mut rfid) {
+                Ok(blocks) => print_sector(&blocks),
+                Err(e) => defmt::error!("Error reading sector: {}", e),
+            }
+
+            if let Err(e) = write_block(&uid, target_sector, rel_block, DATA, &mut rfid) {
+                defmt::error!("Error writing data: {}", e);
+            }
+
+            defmt::info!("\r\n----After Write----\r\n");
+            match read_sector(&uid, target_sector, &mut rfid) {
+                Ok(blocks) => print_sector(&blocks),
+                Err(e) => defmt::error!("Error reading sector: {}", e),
             }
+            let _ = rfid.hlta();
+            let _ = rfid.stop_crypto1();
+            Timer::after_millis(500).await;
         }
         Timer::after_millis(100).await;
     }
@@ -0,0 +1,55 @@
+//! Read a sector and write a block, opening the sector with the factory key.
+//!
+//! The reader-sim crate runs these on the host against a simulated reader
+//! and card, so they only use `core`.
+
+use mfrc522::Mfrc522;
+
+use crate::error::Error;
+use crate::mifare;
+use crate::transaction::Transaction;
+
+/// Key A of a card fresh from the factory
+const AUTH_KEY: [u8; 6] = [0xFF; 6];
+
+/// The four blocks of a sector
+pub fn read_sector<E, COMM>(
+    uid: &mfrc522::Uid,
+    sector: u8,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<[[u8; 16]; 4], Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    let block_offset = sector * 4;
+    rfid.mf_authenticate(uid, block_offset, &AUTH_KEY)
+        .map_err(|e| Error::Auth {
+            sector,
+            cause: e.into(),
+        })?;
+
+    let mut blocks = [[0; 16]; 4];
+    for (abs_block, block) in (block_offset..).zip(blocks.iter_mut()) {
+        *block = mifare::read(rfid, abs_block).map_err(|cause| Error::Read {
+            block: abs_block,
+            cause,
+        })?;
+    }
+    Ok(blocks)
+}
+
+/// Write a block, restoring it if it doesn't read back as written
+pub fn write_block<E, COMM>(
+    uid: &mfrc522::Uid,
+    sector: u8,
+    rel_block: u8,
+    data: [u8; 16],
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Result<(), Error>
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    Transaction::new(sector, AUTH_KEY)
+        .write(rel_block, data)
+        .commit(uid, rfid)
+}
@@ -0,0 +1,258 @@
+//! Write blocks of a sector so a card pulled away mid-write can be put back
+//! in order.
+//!
+//! The blocks are backed up before the first write, and every write is read
+//! back. A block that doesn't read back right is written again, and if it
+//! still doesn't the blocks already written are restored from the backup. If
+//! the card leaves the field the backup is kept, so the caller can finish or
+//! undo the writes the next time it's selected.
+//!
+//! Only uses `core`, so reader-sim can run it on the host.
+
+use mfrc522::Mfrc522;
+
+use crate::error::{DriverError, Error};
+use crate::mifare;
+
+/// Writes of a block before giving up on it, when the errors are transient
+const ATTEMPTS: usize = 3;
+const TRAILER: usize = 3;
+
+/// Blocks of one sector written together
+///
+/// Blocks are written in order, so a new trailer is written after the data
+/// blocks and only once they are in place.
+pub struct Transaction {
+    sector: u8,
+    /// Key A that opens the sector before the writes
+    key: [u8; 6],
+    writes: [Option<[u8; 16]>; 4],
+    backup: Option<[[u8; 16]; 4]>,
+    /// Blocks that may differ from the backup
+    touched: [bool; 4],
+}
+
+impl Transaction {
+    pub fn new(sector: u8, key: [u8; 6]) -> Self {
+        Transaction {
+            sector,
+            key,
+            writes: [None; 4],
+            backup: None,
+            touched: [false; 4],
+        }
+    }
+
+    /// Add a block to write, 3 being the sector trailer
+    pub fn write(mut self, rel_block: u8, data: [u8; 16]) -> Self {
+        self.writes[rel_block as usize] = Some(data);
+        self
+    }
+
+    /// Back up the blocks, then write and verify them
+    ///
+    /// A block is written again after a transient error, see
+    /// [`Error::is_transient`].
+    ///
+    /// On `CardLost` the backup is kept: once the card is selected again,
+    /// calling this again finishes the writes and `rollback` undoes them.
+    /// Any other error comes after the blocks were restored, unless the
+    /// restore failed too, in which case its error is returned.
+    pub fn commit<E, COMM>(
+        &mut self,
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<(), Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        if self.backup.is_none() {
+            self.backup = Some(self.read_backup(uid, rfid)?);
+        }
+
+        for rel_block in 0..4 {
+            let Some(data) = self.writes[rel_block] else {
+                continue;
+            };
+            self.touched[rel_block] = true;
+            match self.write_verified(rel_block, data, uid, rfid) {
+                Ok(()) => {}
+                Err(Error::CardLost) => return Err(Error::CardLost),
+                Err(error) => {
+                    self.rollback(uid, rfid)?;
+                    return Err(error);
+                }
+            }
+        }
+
+        self.done();
+        Ok(())
+    }
+
+    /// Write back the blocks changed since the backup
+    pub fn rollback<E, COMM>(
+        &mut self,
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<(), Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        let Some(backup) = self.backup else {
+            return Ok(());
+        };
+        for (rel_block, data) in backup.into_iter().enumerate() {
+            if self.touched[rel_block] {
+                self.write_verified(rel_block, data, uid, rfid)?;
+            }
+        }
+
+        self.done();
+        Ok(())
+    }
+
+    fn done(&mut self) {
+        self.backup = None;
+        self.touched = [false; 4];
+    }
+
+    fn block(&self, rel_block: usize) -> u8 {
+        self.sector * 4 + rel_block as u8
+    }
+
+    /// Authenticate with the old key A, or the new one if the trailer was
+    /// already written, returning the key that worked
+    fn open<E, COMM>(
+        &self,
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<[u8; 6], Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        let block = self.block(0);
+        let cause = match rfid.mf_authenticate(uid, block, &self.key) {
+            Ok(()) => return Ok(self.key),
+            Err(e) => DriverError::from(e),
+        };
+        // A failed authentication halts the card
+        mifare::reselect(uid, rfid)?;
+
+        let sector = self.sector;
+        let Some(new_key) = self.writes[TRAILER].map(|trailer| key_a(&trailer)) else {
+            return Err(Error::Auth { sector, cause });
+        };
+        rfid.mf_authenticate(uid, block, &new_key).map_err(|e| {
+            let cause = e.into();
+            recover(Error::Auth { sector, cause }, uid, rfid)
+        })?;
+        Ok(new_key)
+    }
+
+    fn read_backup<E, COMM>(
+        &self,
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<[[u8; 16]; 4], Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        let key = self.open(uid, rfid)?;
+        let mut backup = [[0; 16]; 4];
+        for (rel_block, saved) in backup.iter_mut().enumerate() {
+            if self.writes[rel_block].is_none() {
+                continue;
+            }
+            let block = self.block(rel_block);
+            *saved = mifare::read(rfid, block)
+                .map_err(|cause| recover(Error::Read { block, cause }, uid, rfid))?;
+        }
+        // Key A reads back as zeros. Key B doesn't read back either when
+        // the access bits hide it, but then key A can't write the trailer.
+        backup[TRAILER][..6].copy_from_slice(&key);
+        Ok(backup)
+    }
+
+    fn write_verified<E, COMM>(
+        &self,
+        rel_block: usize,
+        data: [u8; 16],
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<(), Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        let mut attempts = 1;
+        loop {
+            match self.write_once(rel_block, data, uid, rfid) {
+                Ok(()) => return Ok(()),
+                Err(e) if e.is_transient() && attempts < ATTEMPTS => attempts += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write_once<E, COMM>(
+        &self,
+        rel_block: usize,
+        data: [u8; 16],
+        uid: &mfrc522::Uid,
+        rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+    ) -> Result<(), Error>
+    where
+        COMM: mfrc522::comm::Interface<Error = E>,
+    {
+        let block = self.block(rel_block);
+        self.open(uid, rfid)?;
+        mifare::write(rfid, block, data)
+            .map_err(|cause| recover(Error::Write { block, cause }, uid, rfid))?;
+
+        let mut expected = data;
+        if rel_block == TRAILER {
+            // Reading the trailer back doesn't show key A, so check that the
+            // new one opens the sector
+            rfid.mf_authenticate(uid, block, &key_a(&data))
+                .map_err(|_| recover(Error::VerifyMismatch { block }, uid, rfid))?;
+            expected[..6].fill(0);
+            if !key_b_readable(&data) {
+                expected[10..].fill(0);
+            }
+        }
+
+        let read = mifare::read(rfid, block)
+            .map_err(|cause| recover(Error::Read { block, cause }, uid, rfid))?;
+        if read != expected {
+            return Err(Error::VerifyMismatch { block });
+        }
+        Ok(())
+    }
+}
+
+fn key_a(trailer: &[u8; 16]) -> [u8; 6] {
+    let mut key = [0; 6];
+    key.copy_from_slice(&trailer[..6]);
+    key
+}
+
+/// Key B reads back with key A when the trailer condition C1 C2 C3 is 000,
+/// 010 or 001
+fn key_b_readable(trailer: &[u8; 16]) -> bool {
+    let c1 = trailer[7] & 0x80 != 0;
+    let c2 = trailer[8] & 0x08 != 0;
+    let c3 = trailer[8] & 0x80 != 0;
+    !(c1 || (c2 && c3))
+}
+
+/// A failed command sends the card back to idle. Select it again, and tell
+/// a refused command from a card that's gone.
+fn recover<E, COMM>(
+    error: Error,
+    uid: &mfrc522::Uid,
+    rfid: &mut Mfrc522<COMM, mfrc522::Initialized>,
+) -> Error
+where
+    COMM: mfrc522::comm::Interface<Error = E>,
+{
+    mifare::reselect(uid, rfid).err().unwrap_or(error)
+}
@@ -0,0 +1,20 @@
+//! Host tests for the cdc-player firmware.
+//!
+//! The command parser only uses `core` and the note table of buzzer-song, so
+//! it is pulled in from the firmware with `#[path]` and run here with
+//! `cargo test`.
+
+// The firmware modules under test, at the paths they have in their crate
+#[cfg(test)]
+#[path = "../../../buzzer-song/src/envelope.rs"]
+#[allow(dead_code)]
+mod envelope;
+#[cfg(test)]
+#[path = "../../../buzzer-song/src/music.rs"]
+#[allow(dead_code)]
+mod music;
+#[cfg(test)]
+#[path = "../../cdc-player/src/protocol.rs"]
+mod protocol;
+#[cfg(test)]
+mod protocol_tests;
@@ -0,0 +1,122 @@
+//! The text commands of cdc-player.
+
+use crate::music::{NOTE_A4, NOTE_C4, NOTE_CS4, NOTE_DS8, REST};
+use crate::protocol::{Command, ProtocolError, parse_command};
+
+fn note(frequency: f64, divider: i16) -> Result<Command<'static>, ProtocolError> {
+    Ok(Command::Note { frequency, divider })
+}
+
+#[test]
+fn note_names() {
+    assert_eq!(parse_command("NOTE C4 8"), note(NOTE_C4, 8));
+    assert_eq!(parse_command("NOTE C#4 8"), note(NOTE_CS4, 8));
+    assert_eq!(parse_command("NOTE cs4 8"), note(NOTE_CS4, 8));
+    assert_eq!(parse_command("note a4 4"), note(NOTE_A4, 4));
+    assert_eq!(parse_command("NOTE DS8 1"), note(NOTE_DS8, 1));
+}
+
+#[test]
+fn rests_and_frequencies() {
+    assert_eq!(parse_command("NOTE REST 2"), note(REST, 2));
+    assert_eq!(parse_command("NOTE p 2"), note(REST, 2));
+    assert_eq!(parse_command("NOTE 440 -4"), note(440.0, -4));
+    assert_eq!(parse_command("NOTE 261.6 16"), note(261.6, 16));
+}
+
+#[test]
+fn surrounding_whitespace() {
+    assert_eq!(parse_command("  NOTE   C4   8  "), note(NOTE_C4, 8));
+}
+
+#[test]
+fn invalid_notes() {
+    for line in [
+        "NOTE H4 4",
+        "NOTE C 4",
+        "NOTE C9 4",
+        "NOTE 0 4",
+        "NOTE -440 4",
+        "NOTE inf 4",
+        "NOTE infinity 4",
+        "NOTE NaN 4",
+    ] {
+        assert_eq!(
+            parse_command(line),
+            Err(ProtocolError::InvalidNote),
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn invalid_dividers() {
+    for line in ["NOTE C4 3", "NOTE C4 0", "NOTE C4 128", "NOTE C4 x"] {
+        assert_eq!(
+            parse_command(line),
+            Err(ProtocolError::InvalidDivider),
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn missing_arguments() {
+    for line in ["NOTE", "NOTE C4", "TEMPO", "RTTTL", "RTTTL   "] {
+        assert_eq!(
+            parse_command(line),
+            Err(ProtocolError::MissingArgument),
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn tempo_range_matches_rtttl() {
+    assert_eq!(parse_command("TEMPO 120"), Ok(Command::Tempo(120)));
+    assert_eq!(parse_command("TEMPO 25"), Ok(Command::Tempo(25)));
+    assert_eq!(parse_command("TEMPO 900"), Ok(Command::Tempo(900)));
+    for line in ["TEMPO 24", "TEMPO 1", "TEMPO 0", "TEMPO 901", "TEMPO fast"] {
+        assert_eq!(
+            parse_command(line),
+            Err(ProtocolError::InvalidTempo),
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn rtttl_keeps_the_whole_ringtone() {
+    assert_eq!(
+        parse_command("RTTTL name:d=4,o=5,b=100:c,e,g"),
+        Ok(Command::Rtttl("name:d=4,o=5,b=100:c,e,g"))
+    );
+}
+
+#[test]
+fn stop_and_status() {
+    assert_eq!(parse_command("STOP"), Ok(Command::Stop));
+    assert_eq!(parse_command("stop"), Ok(Command::Stop));
+    assert_eq!(parse_command("STATUS"), Ok(Command::Status));
+}
+
+#[test]
+fn unknown_commands() {
+    for line in ["PLAY C4", "NOTES C4 4", "TEMPO120"] {
+        assert_eq!(
+            parse_command(line),
+            Err(ProtocolError::UnknownCommand),
+            "{line}"
+        );
+    }
+}
+
+#[test]
+fn reasons_sent_back() {
+    let reason = |line| parse_command(line).unwrap_err().reason();
+    assert_eq!(reason("PLAY"), "unknown command");
+    assert_eq!(reason("NOTE"), "missing argument");
+    assert_eq!(reason("NOTE inf 4"), "invalid note");
+    assert_eq!(reason("NOTE C4 3"), "invalid divider");
+    assert_eq!(reason("TEMPO 1"), "invalid tempo");
+}
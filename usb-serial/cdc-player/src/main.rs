@@ -0,0 +1,280 @@
+#![no_std]
+#![no_main]
+
+mod protocol;
+// Shared with buzzer-song, buzzer-song-tests checks them on the host
+#[path = "../../../buzzer-song/src/envelope.rs"]
+#[allow(dead_code)]
+mod envelope;
+#[path = "../../../buzzer-song/src/music.rs"]
+#[allow(dead_code)]
+mod music;
+#[path = "../../../buzzer-song/src/pwm.rs"]
+mod pwm;
+#[path = "../../../buzzer-song/src/rtttl.rs"]
+mod rtttl;
+
+use core::fmt::Write;
+
+use embassy_executor::Spawner;
+use embassy_futures::select::select;
+use embassy_rp as hal;
+use embassy_rp::bind_interrupts;
+use embassy_rp::block::ImageDef;
+use embassy_rp::clocks::clk_sys_freq;
+use embassy_rp::peripherals::USB;
+use embassy_rp::pwm::{Config as PwmConfig, Pwm, SetDutyCycle};
+use embassy_rp::usb;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Timer};
+use embassy_usb::UsbDevice;
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embassy_usb::driver::EndpointError;
+use heapless::{String, Vec};
+use static_cell::StaticCell;
+use {defmt_rtt as _, panic_probe as _};
+
+use crate::music::{NORMAL, REST, Song};
+use crate::protocol::{Command, parse_command};
+use crate::rtttl::Rtttl;
+
+/// Tell the Boot ROM about our application
+#[unsafe(link_section = ".start_block")]
+#[used]
+pub static IMAGE_DEF: ImageDef = hal::block::ImageDef::secure_exe();
+
+bind_interrupts!(struct Irqs {
+    USBCTRL_IRQ => usb::InterruptHandler<USB>;
+});
+
+type MyUsbDriver = usb::Driver<'static, USB>;
+type MyUsbDevice = UsbDevice<'static, MyUsbDriver>;
+
+/// Notes that can wait to be played
+const QUEUE_LEN: usize = 64;
+
+/// Longest command line, enough for a short RTTTL ringtone
+const LINE_LEN: usize = 512;
+
+/// Tempo used until the host sends a TEMPO command
+const DEFAULT_TEMPO: u16 = 120;
+
+#[derive(Clone, Copy)]
+struct QueuedNote {
+    frequency: f64,
+    duration: Duration,
+}
+
+static QUEUE: Channel<CriticalSectionRawMutex, QueuedNote, QUEUE_LEN> = Channel::new();
+
+/// Cut off the note that is playing right now
+static STOP: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+#[embassy_executor::task]
+async fn usb_task(mut usb: MyUsbDevice) -> ! {
+    usb.run().await
+}
+
+/// Play queued notes one after another
+#[embassy_executor::task]
+async fn player_task(mut buzzer: Pwm<'static>) {
+    let mut pwm_config = PwmConfig::default();
+
+    loop {
+        let note = QUEUE.receive().await;
+        // A STOP sent while nothing was playing has nothing left to stop
+        STOP.reset();
+
+        // Play the note for 90% of its duration, leave the rest silent
+        let play = note.duration * NORMAL as u32 / 100;
+        if note.frequency != REST {
+            match pwm::solve(clk_sys_freq(), note.frequency) {
+                Ok(settings) => {
+                    pwm_config.top = settings.top;
+                    pwm_config.divider = fixed::FixedU16::from_bits(settings.divider_bits());
+                    buzzer.set_config(&pwm_config);
+                    buzzer
+                        .set_duty_cycle_percent(50)
+                        .expect("50 is valid duty percentage");
+                }
+                Err(e) => defmt::error!("Can't play {} Hz: {}", note.frequency, e),
+            }
+        }
+
+        select(Timer::after(play), STOP.wait()).await;
+        buzzer
+            .set_duty_cycle_percent(0)
+            .expect("0 is valid duty percentage");
+        select(Timer::after(note.duration - play), STOP.wait()).await;
+    }
+}
+
+/// How long a note lasts at the song's tempo, a negative divider is a dotted note
+fn note_duration(song: &Song, divider: i16) -> Duration {
+    Duration::from_millis(song.ticks_to_millis(Song::note_ticks(divider)))
+}
+
+/// Queue every note of a ringtone, or none of them if they don't all fit
+fn queue_rtttl(text: &str) -> Result<(), &'static str> {
+    let ringtone = Rtttl::parse(text).map_err(|_| "invalid rtttl")?;
+    let song = Song::new(ringtone.tempo());
+
+    let count = ringtone.notes().count();
+    if count > QUEUE.free_capacity() {
+        return Err("queue full");
+    }
+
+    for (frequency, divider) in ringtone.notes().flatten() {
+        let duration = note_duration(&song, divider);
+        QUEUE
+            .try_send(QueuedNote {
+                frequency,
+                duration,
+            })
+            .map_err(|_| "queue full")?;
+    }
+    defmt::info!("Queued ringtone {} ({} notes)", ringtone.name(), count);
+    Ok(())
+}
+
+/// Run one command line and write the reply into `reply`
+fn handle_line(line: &str, tempo: &mut u16, reply: &mut String<64>) {
+    let result = match parse_command(line) {
+        Ok(Command::Note { frequency, divider }) => {
+            let duration = note_duration(&Song::new(*tempo), divider);
+            QUEUE
+                .try_send(QueuedNote {
+                    frequency,
+                    duration,
+                })
+                .map_err(|_| "queue full")
+        }
+        Ok(Command::Tempo(bpm)) => {
+            *tempo = bpm;
+            Ok(())
+        }
+        Ok(Command::Rtttl(text)) => queue_rtttl(text),
+        Ok(Command::Stop) => {
+            QUEUE.clear();
+            STOP.signal(());
+            Ok(())
+        }
+        Ok(Command::Status) => {
+            let _ = write!(reply, "OK {} {}\r\n", QUEUE.len(), QUEUE.free_capacity());
+            return;
+        }
+        Err(e) => Err(e.reason()),
+    };
+
+    // The free space lets the host pace itself without asking for STATUS
+    let _ = match result {
+        Ok(()) => write!(reply, "OK {}\r\n", QUEUE.free_capacity()),
+        Err(reason) => write!(reply, "ERR {}\r\n", reason),
+    };
+}
+
+/// Read command lines from the host and answer each of them
+async fn serve(class: &mut CdcAcmClass<'static, MyUsbDriver>) -> Result<(), EndpointError> {
+    let mut packet = [0u8; 64];
+    let mut line: Vec<u8, LINE_LEN> = Vec::new();
+    let mut overflow = false;
+    let mut tempo = DEFAULT_TEMPO;
+
+    loop {
+        let n = class.read_packet(&mut packet).await?;
+        for &byte in &packet[..n] {
+            if byte != b'\n' && byte != b'\r' {
+                overflow |= line.push(byte).is_err();
+                continue;
+            }
+
+            let mut reply = String::new();
+            if overflow {
+                let _ = write!(reply, "ERR line too long\r\n");
+            } else if let Ok(text) = core::str::from_utf8(&line) {
+                // Blank lines, like the \n after a \r, get no answer
+                if !text.trim().is_empty() {
+                    handle_line(text, &mut tempo, &mut reply);
+                }
+            } else {
+                let _ = write!(reply, "ERR invalid utf-8\r\n");
+            }
+            line.clear();
+            overflow = false;
+
+            if !reply.is_empty() {
+                class.write_packet(reply.as_bytes()).await?;
+            }
+        }
+    }
+}
+
+#[embassy_executor::main]
+async fn main(spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let buzzer = Pwm::new_output_b(p.PWM_SLICE7, p.PIN_15, PwmConfig::default());
+    spawner.must_spawn(player_task(buzzer));
+
+    let driver = usb::Driver::new(p.USB, Irqs);
+    // Create embassy-usb Config
+    let config = {
+        let mut config = embassy_usb::Config::new(0xc0de, 0xcafe);
+        config.manufacturer = Some("implRust");
+        config.product = Some("Ferris Buzzer");
+        config.serial_number = Some("12345678");
+        config.max_power = 100;
+        config.max_packet_size_0 = 64;
+        config
+    };
+
+    // Create embassy-usb DeviceBuilder using the driver and config.
+    // It needs some buffers for building the descriptors.
+    let mut builder = {
+        static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+        static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+        static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+
+        embassy_usb::Builder::new(
+            driver,
+            config,
+            CONFIG_DESCRIPTOR.init([0; 256]),
+            BOS_DESCRIPTOR.init([0; 256]),
+            &mut [], // no msos descriptors
+            CONTROL_BUF.init([0; 64]),
+        )
+    };
+
+    // Create classes on the builder.
+    let mut class = {
+        static STATE: StaticCell<State> = StaticCell::new();
+        let state = STATE.init(State::new());
+        CdcAcmClass::new(&mut builder, state, 64)
+    };
+
+    // Build the builder.
+    let usb = builder.build();
+    spawner.must_spawn(usb_task(usb));
+
+    loop {
+        class.wait_connection().await;
+        defmt::info!("Host connected");
+        let _ = serve(&mut class).await;
+        defmt::info!("Host disconnected");
+    }
+}
+
+// Program metadata for `picotool info`.
+// This isn't needed, but it's recommended to have these minimal entries.
+#[unsafe(link_section = ".bi_entries")]
+#[used]
+pub static PICOTOOL_ENTRIES: [embassy_rp::binary_info::EntryAddr; 4] = [
+    embassy_rp::binary_info::rp_program_name!(c"cdc-player"),
+    embassy_rp::binary_info::rp_program_description!(c"Play melodies sent over USB serial"),
+    embassy_rp::binary_info::rp_cargo_version!(),
+    embassy_rp::binary_info::rp_program_build_attribute!(),
+];
+
+// End of file
@@ -0,0 +1,133 @@
+//! Text commands the host sends over the serial port, one per line.
+//!
+//! ```text
+//! TEMPO 120          tempo for the NOTE commands that follow
+//! NOTE C4 8          an eighth note middle C (C#4 or CS4 for sharps)
+//! NOTE 440 -4        a dotted quarter note at 440 Hz
+//! NOTE REST 2        a half note pause
+//! RTTTL name:d=4,o=5,b=100:c,e,g   a whole ringtone with its own tempo
+//! STOP               stop playing and forget the queued notes
+//! STATUS             how many notes are queued
+//! ```
+//!
+//! Every line is answered with `OK ...` or `ERR <reason>`.
+
+use crate::music::{REST, midi_note};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command<'a> {
+    Note { frequency: f64, divider: i16 },
+    Tempo(u16),
+    Rtttl(&'a str),
+    Stop,
+    Status,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(target_os = "none", derive(defmt::Format))]
+pub enum ProtocolError {
+    UnknownCommand,
+    MissingArgument,
+    InvalidNote,
+    InvalidDivider,
+    InvalidTempo,
+}
+
+impl ProtocolError {
+    /// Short reason sent back to the host after `ERR`
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ProtocolError::UnknownCommand => "unknown command",
+            ProtocolError::MissingArgument => "missing argument",
+            ProtocolError::InvalidNote => "invalid note",
+            ProtocolError::InvalidDivider => "invalid divider",
+            ProtocolError::InvalidTempo => "invalid tempo",
+        }
+    }
+}
+
+pub fn parse_command(line: &str) -> Result<Command<'_>, ProtocolError> {
+    let line = line.trim();
+    let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+    let args = args.trim();
+
+    if name.eq_ignore_ascii_case("NOTE") {
+        let mut args = args.split_ascii_whitespace();
+        let note = args.next().ok_or(ProtocolError::MissingArgument)?;
+        let divider = args.next().ok_or(ProtocolError::MissingArgument)?;
+        Ok(Command::Note {
+            frequency: parse_note(note)?,
+            divider: parse_divider(divider)?,
+        })
+    } else if name.eq_ignore_ascii_case("TEMPO") {
+        match args.parse() {
+            // The same range RTTTL ringtones allow
+            Ok(tempo @ 25..=900) => Ok(Command::Tempo(tempo)),
+            Ok(_) => Err(ProtocolError::InvalidTempo),
+            Err(_) if args.is_empty() => Err(ProtocolError::MissingArgument),
+            Err(_) => Err(ProtocolError::InvalidTempo),
+        }
+    } else if name.eq_ignore_ascii_case("RTTTL") {
+        if args.is_empty() {
+            return Err(ProtocolError::MissingArgument);
+        }
+        Ok(Command::Rtttl(args))
+    } else if name.eq_ignore_ascii_case("STOP") {
+        Ok(Command::Stop)
+    } else if name.eq_ignore_ascii_case("STATUS") {
+        Ok(Command::Status)
+    } else {
+        Err(ProtocolError::UnknownCommand)
+    }
+}
+
+/// A note name like `C4`, `C#4` or `CS4`, `REST`, or a frequency in Hertz
+fn parse_note(note: &str) -> Result<f64, ProtocolError> {
+    if note.eq_ignore_ascii_case("REST") || note.eq_ignore_ascii_case("P") {
+        return Ok(REST);
+    }
+    if let Ok(frequency) = note.parse::<f64>() {
+        // "inf" and "nan" parse as well
+        return if frequency.is_finite() && frequency > 0.0 {
+            Ok(frequency)
+        } else {
+            Err(ProtocolError::InvalidNote)
+        };
+    }
+
+    let mut chars = note.chars();
+    let semitone = match chars.next().map(|c| c.to_ascii_lowercase()) {
+        Some('c') => 0,
+        Some('d') => 2,
+        Some('e') => 4,
+        Some('f') => 5,
+        Some('g') => 7,
+        Some('a') => 9,
+        Some('b') => 11,
+        _ => return Err(ProtocolError::InvalidNote),
+    };
+
+    let rest = chars.as_str();
+    let (semitone, octave) = match rest.strip_prefix(['#', 's', 'S']) {
+        Some(octave) => (semitone + 1, octave),
+        None => (semitone, rest),
+    };
+    let octave: u8 = octave.parse().map_err(|_| ProtocolError::InvalidNote)?;
+
+    // MIDI numbers octaves from -1, so C4 is note 60
+    let number = (octave as u16 + 1) * 12 + semitone;
+    u8::try_from(number)
+        .ok()
+        .and_then(midi_note)
+        .ok_or(ProtocolError::InvalidNote)
+}
+
+/// A note length like in the melody tables, negative for dotted notes
+fn parse_divider(divider: &str) -> Result<i16, ProtocolError> {
+    match divider.parse::<i16>() {
+        Ok(divider) if matches!(divider.unsigned_abs(), 1 | 2 | 4 | 8 | 16 | 32 | 64) => {
+            Ok(divider)
+        }
+        _ => Err(ProtocolError::InvalidDivider),
+    }
+}